@@ -0,0 +1,304 @@
+// compare the query strategies on a generated document with about 50k elements:
+// `plain` walks the tree top down, `index` uses the document index for the candidates,
+// `stats` also lets the planner choose the strategy by the counts, e.g. right to left,
+// `bloom` walks the tree with the ancestor bloom filter
+// run with `cargo bench --bench selector`
+use mesdoc::interface::{
+	BoxDynElement, BoxDynNode, DocumentIndex, Elements, IAttrValue, IDocumentStats, IDocumentTrait,
+	IElementTrait, IEnumTyped, IIndexProvider, INodeTrait, INodeType, InsertPosition, MaybeDoc,
+	MaybeElement,
+};
+use mesdoc::selector::Selector;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+struct NodeData {
+	uuid: String,
+	tag_name: String,
+	attrs: RefCell<Vec<(String, Option<String>)>>,
+	children: RefCell<Vec<Rc<NodeData>>>,
+	parent: RefCell<Weak<NodeData>>,
+	index: Cell<usize>,
+	doc: Weak<DocData>,
+}
+
+#[derive(Default)]
+struct DocData {
+	root: RefCell<Option<Rc<NodeData>>>,
+	ids: RefCell<HashMap<String, Rc<NodeData>>>,
+	index: RefCell<Option<Rc<DocumentIndex>>>,
+	use_index: Cell<bool>,
+	use_stats: Cell<bool>,
+	use_filter: Cell<bool>,
+}
+
+#[derive(Clone)]
+struct Node(Rc<NodeData>);
+struct Doc(Rc<DocData>);
+
+impl INodeTrait for Node {
+	fn to_node(self: Box<Self>) -> Box<dyn Any> {
+		self
+	}
+	fn clone_node<'b>(&self) -> BoxDynNode<'b> {
+		Box::new(self.clone())
+	}
+	fn typed<'b>(self: Box<Self>) -> IEnumTyped<'b> {
+		IEnumTyped::Element(self)
+	}
+	fn node_type(&self) -> INodeType {
+		INodeType::Element
+	}
+	fn parent<'b>(&self) -> MaybeElement<'b> {
+		let parent = self.0.parent.borrow().upgrade()?;
+		Some(Box::new(Node(parent)))
+	}
+	fn uuid(&self) -> Option<&str> {
+		Some(&self.0.uuid)
+	}
+	fn owner_document(&self) -> MaybeDoc<'_> {
+		let doc = self.0.doc.upgrade()?;
+		Some(Box::new(Doc(doc)))
+	}
+	fn text_content(&self) -> &str {
+		""
+	}
+	fn set_text(&mut self, _content: &str) {
+		unimplemented!("The bench document is read only");
+	}
+	fn set_html(&mut self, _content: &str) {
+		unimplemented!("The bench document is read only");
+	}
+	fn index(&self) -> usize {
+		self.0.index.get()
+	}
+}
+
+impl IElementTrait for Node {
+	fn tag_name(&self) -> &str {
+		&self.0.tag_name
+	}
+	fn child_nodes_length(&self) -> usize {
+		self.0.children.borrow().len()
+	}
+	fn child_nodes_item<'b>(&self, index: usize) -> Option<BoxDynNode<'b>> {
+		let child = self.0.children.borrow().get(index)?.clone();
+		Some(Box::new(Node(child)))
+	}
+	fn get_attribute(&self, name: &str) -> Option<IAttrValue> {
+		self
+			.0
+			.attrs
+			.borrow()
+			.iter()
+			.find(|(key, _)| key.eq_ignore_ascii_case(name))
+			.map(|(_, value)| match value {
+				Some(value) => IAttrValue::Value(value.clone(), Some('"')),
+				None => IAttrValue::True,
+			})
+	}
+	fn set_attribute(&mut self, _name: &str, _value: Option<&str>) {
+		unimplemented!("The bench document is read only");
+	}
+	fn remove_attribute(&mut self, _name: &str) {
+		unimplemented!("The bench document is read only");
+	}
+	fn attribute_names(&self) -> Vec<String> {
+		self
+			.0
+			.attrs
+			.borrow()
+			.iter()
+			.map(|(key, _)| key.clone())
+			.collect()
+	}
+	fn inner_html(&self) -> &str {
+		""
+	}
+	fn outer_html(&self) -> &str {
+		""
+	}
+	fn insert_adjacent(&mut self, _position: &InsertPosition, _ele: &BoxDynElement) {
+		unimplemented!("The bench document is read only");
+	}
+	fn remove_child(&mut self, _ele: BoxDynElement) {
+		unimplemented!("The bench document is read only");
+	}
+}
+
+impl IDocumentTrait for Doc {
+	fn get_element_by_id<'b>(&self, id: &str) -> Option<BoxDynElement<'b>> {
+		let ele = self.0.ids.borrow().get(id)?.clone();
+		Some(Box::new(Node(ele)))
+	}
+	fn source_code(&self) -> &'static str {
+		""
+	}
+	fn get_root_node<'b>(&self) -> BoxDynNode<'b> {
+		let root = self
+			.0
+			.root
+			.borrow()
+			.clone()
+			.expect("The root must be built");
+		Box::new(Node(root))
+	}
+	fn index_provider(&self) -> Option<Rc<dyn IIndexProvider>> {
+		if !self.0.use_index.get() {
+			return None;
+		}
+		let index = self.0.index.borrow().clone()?;
+		Some(index)
+	}
+	fn statistics(&self) -> Option<Rc<dyn IDocumentStats>> {
+		if !self.0.use_stats.get() {
+			return None;
+		}
+		let index = self.0.index.borrow().clone()?;
+		Some(index)
+	}
+	fn use_ancestor_filter(&self) -> bool {
+		self.0.use_filter.get()
+	}
+}
+
+// append a child element
+fn append(
+	doc: &Rc<DocData>,
+	parent: Option<&Rc<NodeData>>,
+	tag_name: &str,
+	attrs: &[(&str, &str)],
+) -> Rc<NodeData> {
+	let ele = Rc::new(NodeData {
+		uuid: next_uuid(),
+		tag_name: tag_name.to_ascii_uppercase(),
+		attrs: RefCell::new(
+			attrs
+				.iter()
+				.map(|(key, value)| (String::from(*key), Some(String::from(*value))))
+				.collect(),
+		),
+		children: RefCell::new(Vec::new()),
+		parent: RefCell::new(Weak::new()),
+		index: Cell::new(0),
+		doc: Rc::downgrade(doc),
+	});
+	if let Some(parent) = parent {
+		let mut children = parent.children.borrow_mut();
+		ele.index.set(children.len());
+		*ele.parent.borrow_mut() = Rc::downgrade(parent);
+		children.push(Rc::clone(&ele));
+	}
+	if let Some((_, id)) = attrs.iter().find(|(key, _)| *key == "id") {
+		doc
+			.ids
+			.borrow_mut()
+			.insert(String::from(*id), Rc::clone(&ele));
+	}
+	ele
+}
+
+static UUID: AtomicUsize = AtomicUsize::new(0);
+
+fn next_uuid() -> String {
+	format!("node-{}", UUID.fetch_add(1, Ordering::Relaxed))
+}
+
+// html > body > div > div > main > 100 div.section > 20 article.post > 24 leaves, every 997th leaf is `a.target`
+fn build_document() -> Rc<DocData> {
+	let doc = Rc::new(DocData::default());
+	let root = append(&doc, None, "html", &[]);
+	let body = append(&doc, Some(&root), "body", &[]);
+	// the wrappers make the sections deep enough to check the ancestors far from the context
+	let mut wrapper = body;
+	for tag_name in &["div", "div", "main"] {
+		wrapper = append(&doc, Some(&wrapper), tag_name, &[("class", "wrapper")]);
+	}
+	let mut leaf_count = 0;
+	for section_index in 0..100 {
+		let section_id = format!("section-{}", section_index);
+		let section = append(
+			&doc,
+			Some(&wrapper),
+			"div",
+			&[("class", "section"), ("id", &section_id)],
+		);
+		for _ in 0..20 {
+			let article = append(&doc, Some(&section), "article", &[("class", "post")]);
+			for leaf_index in 0..24 {
+				leaf_count += 1;
+				if leaf_count % 997 == 0 {
+					append(
+						&doc,
+						Some(&article),
+						"a",
+						&[("class", "item target"), ("href", "#")],
+					);
+				} else if leaf_index % 2 == 0 {
+					append(&doc, Some(&article), "p", &[("class", "item")]);
+				} else {
+					append(&doc, Some(&article), "span", &[("data-x", "1")]);
+				}
+			}
+		}
+	}
+	let root_ele: BoxDynElement = Box::new(Node(Rc::clone(&root)));
+	*doc.index.borrow_mut() = Some(Rc::new(DocumentIndex::new(&root_ele)));
+	*doc.root.borrow_mut() = Some(root);
+	doc
+}
+
+fn main() {
+	mesdoc::init();
+	let doc = build_document();
+	let root: BoxDynElement = Box::new(Node(doc.root.borrow().clone().unwrap()));
+	let root = Elements::with_nodes(vec![root]);
+	const TIMES: u32 = 20;
+	for selector in &[
+		".section .target",
+		"div article a.target",
+		"#section-50 .target",
+		"article > .item",
+		"article > .target",
+		"body span[data-x]",
+		".section .post .item",
+		".missing .item",
+	] {
+		println!("selector `{}`", selector);
+		let mut counts = Vec::with_capacity(4);
+		for (mode, use_index, use_stats, use_filter) in &[
+			("plain", false, false, false),
+			("bloom", false, false, true),
+			("index", true, false, false),
+			("stats", true, true, false),
+		] {
+			doc.use_index.set(*use_index);
+			doc.use_stats.set(*use_stats);
+			doc.use_filter.set(*use_filter);
+			let start = Instant::now();
+			let mut count = 0;
+			for _ in 0..TIMES {
+				count = root.find(selector).length();
+			}
+			let elapsed = start.elapsed() / TIMES;
+			println!(
+				"  {:>5}: {:>10.3?} per query, {} matched",
+				mode, elapsed, count
+			);
+			counts.push(count);
+		}
+		assert!(
+			counts.iter().all(|count| *count == counts[0]),
+			"The strategies must find the same elements"
+		);
+		let stats = doc.index.borrow().clone().unwrap();
+		let plan = Selector::from_str_with_stats(selector, true, Some(&*stats))
+			.unwrap()
+			.explain();
+		print!("{}", plan);
+	}
+}
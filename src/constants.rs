@@ -1,5 +1,7 @@
 // attr class
 pub const ATTR_CLASS: &str = "class";
+// attr style
+pub const ATTR_STYLE: &str = "style";
 // default elements initial node length
 pub const DEF_NODES_LEN: usize = 5;
 // priorities
@@ -17,6 +19,7 @@ pub const PRIORITY_ID_SELECTOR: u32 = 10000;
 // selector names
 pub const NAME_SELECTOR_ALL: &str = "all";
 pub const NAME_SELECTOR_ATTR: &str = "attr";
+pub const NAME_SELECTOR_ATTR_PREFIX: &str = "attr_prefix";
 pub const NAME_SELECTOR_NAME: &str = "name";
 pub const NAME_SELECTOR_CLASS: &str = "class";
 pub const NAME_SELECTOR_ID: &str = "id";
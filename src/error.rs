@@ -3,8 +3,12 @@ use thiserror::Error;
 pub enum Error {
 	#[error("Invalid selector:'{context}'<{reason}>")]
 	InvalidSelector { context: String, reason: String },
+	#[error("Invalid xpath:'{context}'<{reason}>")]
+	InvalidXPath { context: String, reason: String },
 	#[error("Call method '{method}' with {error}")]
 	MethodOnInvalidSelector { method: String, error: String },
 	#[error("Call method '{method}' cause an error: {message}")]
 	InvalidTraitMethodCall { method: String, message: String },
+	#[error("Invalid attribute value '{value}': {reason}")]
+	InvalidAttrValue { value: String, reason: String },
 }
@@ -1,5 +1,8 @@
-use super::{BoxDynElement, BoxDynNode, Elements};
-use crate::utils::to_static_str;
+use super::{
+	BoxDynElement, BoxDynNode, BoxDynText, Elements, IAttrValue, IDocumentStats, IIndexProvider,
+};
+use crate::constants::ATTR_CLASS;
+use crate::utils::{get_class_list, to_static_str};
 use std::error::Error;
 use std::rc::Rc;
 
@@ -7,6 +10,21 @@ pub type MaybeDoc<'a> = Option<Box<dyn IDocumentTrait + 'a>>;
 pub type IErrorHandle = Box<dyn Fn(Box<dyn Error>)>;
 pub trait IDocumentTrait {
 	fn get_element_by_id<'b>(&self, id: &str) -> Option<BoxDynElement<'b>>;
+	// create nodes, the created nodes are not in the document tree until inserted,
+	// `None` if the document can't create them, the methods need them will trigger errors
+	fn create_element<'b>(&self, _tag_name: &str) -> Option<BoxDynElement<'b>> {
+		None
+	}
+	fn create_text_node<'b>(&self, _content: &str) -> Option<BoxDynText<'b>> {
+		None
+	}
+	fn create_comment<'b>(&self, _content: &str) -> Option<BoxDynNode<'b>> {
+		None
+	}
+	// document fragment, node type is `DocumentFragement`, it's child nodes can be set by `set_html`
+	fn create_document_fragment<'b>(&self) -> Option<BoxDynElement<'b>> {
+		None
+	}
 	fn source_code(&self) -> &'static str;
 	// get root node
 	fn get_root_node<'b>(&self) -> BoxDynNode<'b>;
@@ -14,7 +32,7 @@ pub trait IDocumentTrait {
 	fn document_element<'b>(&self) -> Option<BoxDynElement<'b>> {
 		if let Some(root) = &self.get_root_node().root_element() {
 			let root = Elements::with_node(root);
-			return root.find("html").get(0).map(|ele| ele.cloned());
+			return root.find_handle("html").get(0).map(|ele| ele.cloned());
 		}
 		None
 	}
@@ -22,7 +40,10 @@ pub trait IDocumentTrait {
 	fn title(&self) -> Option<&'static str> {
 		if let Some(root) = &self.get_root_node().root_element() {
 			let root = Elements::with_node(root);
-			let title = root.find("head").eq(0).find("title");
+			let title = root
+				.find_handle("head")
+				.filter_by_handle(|index, _| index == 0)
+				.find_handle("title");
 			if !title.is_empty() {
 				return Some(to_static_str(String::from(title.text())));
 			}
@@ -33,7 +54,7 @@ pub trait IDocumentTrait {
 	fn head<'b>(&self) -> Option<BoxDynElement<'b>> {
 		if let Some(root) = &self.get_root_node().root_element() {
 			let root = Elements::with_node(root);
-			return root.find("head").get(0).map(|ele| ele.cloned());
+			return root.find_handle("head").get(0).map(|ele| ele.cloned());
 		}
 		None
 	}
@@ -41,10 +62,91 @@ pub trait IDocumentTrait {
 	fn body<'b>(&self) -> Option<BoxDynElement<'b>> {
 		if let Some(root) = &self.get_root_node().root_element() {
 			let root = Elements::with_node(root);
-			return root.find("body").get(0).map(|ele| ele.cloned());
+			return root.find_handle("body").get(0).map(|ele| ele.cloned());
+		}
+		None
+	}
+	// query selector, the elements are in document order
+	fn query_selector_all<'b>(&self, selector: &str) -> Elements<'b> {
+		if let Some(root) = &self.get_root_node().root_element() {
+			let root = Elements::with_node(root);
+			return root.find_handle(selector);
+		}
+		Elements::new()
+	}
+	// the first element matched the selector
+	fn query_selector<'b>(&self, selector: &str) -> Option<BoxDynElement<'b>> {
+		self
+			.query_selector_all(selector)
+			.get(0)
+			.map(|ele| ele.cloned())
+	}
+	// elements have all the class names splitted by whitespaces
+	fn get_elements_by_class_name<'b>(&self, class_names: &str) -> Elements<'b> {
+		let class_list = get_class_list(class_names);
+		if class_list.is_empty() {
+			return Elements::new();
+		}
+		let has_classes = |_: usize, ele: &BoxDynElement| match ele.get_attribute(ATTR_CLASS) {
+			Some(IAttrValue::Value(names, _)) => {
+				let names = get_class_list(&names);
+				class_list.iter().all(|name| names.contains(name))
+			}
+			_ => false,
+		};
+		if let Some(index) = self.index_provider() {
+			if let Some(elements) = index.elements_by_class_name(class_list[0]) {
+				let mut elements = elements.filter_by_handle(has_classes);
+				elements.sort_and_unique();
+				return elements;
+			}
+		}
+		// walk the tree, don't use `find` which calls back here when `is_lookup_indexed` is true
+		match self.get_root_node().root_element() {
+			Some(root) => root.childrens().filter_by_handle(has_classes),
+			None => Elements::new(),
+		}
+	}
+	// elements by tag name, `*` means all elements
+	fn get_elements_by_tag_name<'b>(&self, tag_name: &str) -> Elements<'b> {
+		if let Some(index) = self.index_provider() {
+			if let Some(mut elements) = index.elements_by_tag_name(tag_name) {
+				elements.sort_and_unique();
+				return elements;
+			}
 		}
+		// walk the tree, same as `get_elements_by_class_name`
+		match self.get_root_node().root_element() {
+			Some(root) if tag_name == "*" => root.childrens(),
+			Some(root) => {
+				let tag_name = tag_name.to_ascii_uppercase();
+				root
+					.childrens()
+					.filter_by_handle(|_, ele| ele.tag_name() == tag_name)
+			}
+			None => Elements::new(),
+		}
+	}
+	// if `get_elements_by_class_name` and `get_elements_by_tag_name` are overrided by indexed versions,
+	// the class and tag selectors will use them as the fast path like `get_element_by_id`
+	fn is_lookup_indexed(&self) -> bool {
+		false
+	}
+	// the class, tag and attribute name index, see `DocumentIndex`,
+	// the selectors prefer it to `is_lookup_indexed`
+	fn index_provider(&self) -> Option<Rc<dyn IIndexProvider>> {
 		None
 	}
+	// the tag, class and attribute counts, `find` uses them to plan the selectors by the costs,
+	// `DocumentIndex` can provide them too
+	fn statistics(&self) -> Option<Rc<dyn IDocumentStats>> {
+		None
+	}
+	// if `true`, the descendant selectors are matched from right to left while walking the tree,
+	// and keep a bloom filter of the ancestors' classes and tags to reject the candidates early
+	fn use_ancestor_filter(&self) -> bool {
+		false
+	}
 	// onerror
 	fn onerror(&self) -> Option<Rc<IErrorHandle>> {
 		None
@@ -56,3 +158,35 @@ pub trait IDocumentTrait {
 		}
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use crate::mock::Doc;
+
+	#[test]
+	fn test_get_elements_by_names() {
+		crate::init();
+		let doc = Doc::parse(
+			r#"<div class="a b"><p class="a">1</p><p class='a"b'>2</p></div><P class=" b  a ">3</P>"#,
+		);
+		// the class and tag selectors call back the methods
+		doc.0.lookup_indexed.set(true);
+		let root = doc.root();
+		let document = root
+			.get(0)
+			.and_then(|ele| ele.owner_document())
+			.expect("The document must exist");
+		for index in [false, true] {
+			if index {
+				doc.build_index();
+			}
+			assert_eq!(document.get_elements_by_class_name("b a").length(), 2);
+			assert_eq!(document.get_elements_by_class_name(r#"a"b"#).text(), "2");
+			assert!(document.get_elements_by_class_name(" ").is_empty());
+			assert_eq!(document.get_elements_by_tag_name("p").text(), "123");
+			assert_eq!(document.get_elements_by_tag_name("*").length(), 4);
+			assert_eq!(root.find("p.a").text(), "13");
+			assert_eq!(root.find("div > p").length(), 2);
+		}
+	}
+}
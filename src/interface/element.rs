@@ -1,7 +1,11 @@
-use super::{BoxDynNode, BoxDynText, Elements, INodeTrait, INodeType, Texts};
-use crate::{constants::DEF_NODES_LEN, error::Error as IError};
+use super::{BoxDynNode, BoxDynText, Elements, INodeTrait, INodeType, MaybeDoc, Texts};
+use crate::utils::{get_start_tag_attr_names, parse_srcset, resolve_url, SrcsetCandidate};
+use crate::{constants::DEF_NODES_LEN, error::Error as IError, selector::Selector};
+use serde_json::Value as JsonValue;
 use std::error::Error;
+use std::fmt::Display;
 use std::ops::Range;
+use std::str::FromStr;
 
 pub type BoxDynElement<'a> = Box<dyn IElementTrait + 'a>;
 pub type MaybeElement<'a> = Option<BoxDynElement<'a>>;
@@ -31,6 +35,66 @@ impl IAttrValue {
 			IAttrValue::True => vec![],
 		}
 	}
+	/// pub fn `to_comma_list`
+	/// split the value by comma, the empty items are ignored
+	pub fn to_comma_list(&self) -> Vec<&str> {
+		match self {
+			IAttrValue::Value(v, _) => v
+				.split(',')
+				.map(|item| item.trim())
+				.filter(|item| !item.is_empty())
+				.collect::<Vec<&str>>(),
+			IAttrValue::True => vec![],
+		}
+	}
+	// get the value, error if no value
+	fn value(&self) -> Result<&str, IError> {
+		match self {
+			IAttrValue::Value(v, _) => Ok(v),
+			IAttrValue::True => Err(IError::InvalidAttrValue {
+				value: String::new(),
+				reason: "The attribute has no value".into(),
+			}),
+		}
+	}
+	/// pub fn `parse`
+	/// parse the trimmed value to the type, such as integer, float, bool
+	pub fn parse<T: FromStr>(&self) -> Result<T, IError>
+	where
+		T::Err: Display,
+	{
+		let value = self.value()?;
+		value
+			.trim()
+			.parse::<T>()
+			.map_err(|e| IError::InvalidAttrValue {
+				value: String::from(value),
+				reason: e.to_string(),
+			})
+	}
+	/// pub fn `to_url`
+	/// resolve the value as an url relative to the `base`
+	pub fn to_url(&self, base: &str) -> Result<String, IError> {
+		Ok(resolve_url(base, self.value()?))
+	}
+	/// pub fn `to_srcset`
+	/// parse the value as `srcset` image candidates
+	pub fn to_srcset(&self) -> Result<Vec<SrcsetCandidate>, IError> {
+		let value = self.value()?;
+		parse_srcset(value).map_err(|reason| IError::InvalidAttrValue {
+			value: String::from(value),
+			reason,
+		})
+	}
+	/// pub fn `to_json`
+	/// parse the value as json
+	pub fn to_json(&self) -> Result<JsonValue, IError> {
+		let value = self.value()?;
+		serde_json::from_str(value).map_err(|e| IError::InvalidAttrValue {
+			value: String::from(value),
+			reason: e.to_string(),
+		})
+	}
 }
 
 /// impl `ToString` for IAttrValue
@@ -43,6 +107,29 @@ impl ToString for IAttrValue {
 	}
 }
 
+/// options for `deep_clone`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CloneOptions {
+	pub attributes: bool,
+	pub children: bool,
+}
+
+impl Default for CloneOptions {
+	fn default() -> Self {
+		CloneOptions {
+			attributes: true,
+			children: true,
+		}
+	}
+}
+
+/// the position for `insert_adjacent`, relative to the target element:
+/// `BeforeBegin`: before the target, as the previous sibling
+/// `AfterBegin`: inside the target, as the first child
+/// `BeforeEnd`: inside the target, as the last child
+/// `AfterEnd`: after the target, as the next sibling
+/// the inserted node is moved, if it already has a parent, it will be removed from the parent first,
+/// so the same node never appears in two places of the tree.
 #[derive(Debug, PartialEq, Eq)]
 pub enum InsertPosition {
 	BeforeBegin,
@@ -63,6 +150,24 @@ impl InsertPosition {
 	}
 }
 
+// trigger the selector error by the owner document
+fn trigger_selector_error(doc: MaybeDoc, method: &str, error: IError) {
+	if let Some(doc) = doc {
+		doc.trigger_error(Box::new(IError::MethodOnInvalidSelector {
+			method: String::from(method),
+			error: error.to_string(),
+		}));
+	}
+}
+
+// the error of the trait method not implemented
+pub(crate) fn not_implemented_error(method: &str) -> Box<dyn Error> {
+	Box::new(IError::InvalidTraitMethodCall {
+		method: method.into(),
+		message: format!("The {} method is not implemented.", method),
+	})
+}
+
 pub trait IElementTrait: INodeTrait {
 	fn is(&self, ele: &BoxDynElement) -> bool {
 		if let Some(uuid) = self.uuid() {
@@ -89,11 +194,104 @@ pub trait IElementTrait: INodeTrait {
 		}
 		root.unwrap_or_else(|| self.cloned())
 	}
+	/// fn `matches`
+	/// check if the element matches the selector, like the DOM `Element.matches`,
+	/// use `matches_selector` instead when checking many elements with the same selector
+	fn matches(&self, selector: &str) -> bool {
+		match selector.parse::<Selector>() {
+			Ok(selector) => self.matches_selector(&selector),
+			Err(err) => {
+				trigger_selector_error(self.owner_document(), "matches", err);
+				false
+			}
+		}
+	}
+	/// fn `matches_selector`
+	/// same as `matches` with the parsed selector
+	fn matches_selector(&self, selector: &Selector) -> bool {
+		selector.is_match(&self.cloned())
+	}
+	/// fn `closest`
+	/// the element itself or its nearest ancestor which matches the selector,
+	/// use `closest_selector` instead when calling it for many elements with the same selector
+	fn closest<'b>(&self, selector: &str) -> MaybeElement<'b> {
+		match selector.parse::<Selector>() {
+			Ok(selector) => self.closest_selector(&selector),
+			Err(err) => {
+				trigger_selector_error(self.owner_document(), "closest", err);
+				None
+			}
+		}
+	}
+	/// fn `closest_selector`
+	/// same as `closest` with the parsed selector
+	fn closest_selector<'b>(&self, selector: &Selector) -> MaybeElement<'b> {
+		let mut cur = Some(self.cloned());
+		while let Some(ele) = cur {
+			if !ele.node_type().is_element() {
+				break;
+			}
+			if selector.is_match(&ele) {
+				return Some(ele);
+			}
+			cur = ele.parent();
+		}
+		None
+	}
 	// cloned
 	fn cloned<'b>(&self) -> BoxDynElement<'b> {
 		let ele = self.clone_node();
 		ele.typed().into_element().unwrap()
 	}
+	// deep clone, a detached copy of the element with a new uuid, the copy is built by the owner document:
+	// `create_element`, the attributes, then the child nodes recursively, the text and comment nodes
+	// need `create_text_node`, `create_comment` and `insert_adjacent_node`,
+	// override it if the document can clone the nodes itself
+	fn deep_clone<'b>(&self, options: CloneOptions) -> Result<BoxDynElement<'b>, Box<dyn Error>> {
+		let doc = self
+			.owner_document()
+			.ok_or_else(|| not_implemented_error("owner_document"))?;
+		let mut ele = doc
+			.create_element(&self.tag_name().to_ascii_lowercase())
+			.ok_or_else(|| not_implemented_error("create_element"))?;
+		if options.attributes {
+			for (name, value) in self.attributes() {
+				match &value {
+					IAttrValue::Value(value, _) => ele.set_attribute(&name, Some(value)),
+					IAttrValue::True => ele.set_attribute(&name, None),
+				}
+			}
+		}
+		if options.children {
+			for node in self.child_nodes() {
+				let copy = match node.node_type() {
+					INodeType::Element => {
+						let child = node
+							.typed()
+							.into_element()
+							.expect("Call `typed` for element node.");
+						ele.insert_adjacent(&InsertPosition::BeforeEnd, &child.deep_clone(options)?);
+						continue;
+					}
+					INodeType::Text => doc
+						.create_text_node(node.text_content())
+						.ok_or_else(|| not_implemented_error("create_text_node"))?
+						.clone_node(),
+					INodeType::Comment => doc
+						.create_comment(node.text_content())
+						.ok_or_else(|| not_implemented_error("create_comment"))?,
+					node_type => {
+						return Err(Box::new(IError::InvalidTraitMethodCall {
+							method: "deep_clone".into(),
+							message: format!("Can't clone the node type '{:?}'.", node_type),
+						}))
+					}
+				};
+				ele.insert_adjacent_node(&InsertPosition::BeforeEnd, &copy)?;
+			}
+		}
+		Ok(ele)
+	}
 	// next sibling
 	fn next_element_sibling<'b>(&self) -> MaybeElement<'b> {
 		// use child_nodes instead of chilren, reduce one loop
@@ -282,6 +480,20 @@ pub trait IElementTrait: INodeTrait {
 	fn has_attribute(&self, name: &str) -> bool {
 		self.get_attribute(name).is_some()
 	}
+	// the attribute names in source order, parsed from the start tag of `outer_html()` by default,
+	// it costs the html size of the element for each call, and `attributes`, `data_all` and
+	// the `[^prefix]` selector call it for each element, implementors should override it
+	// with the attributes stored in the node
+	fn attribute_names(&self) -> Vec<String> {
+		get_start_tag_attr_names(self.outer_html())
+	}
+	fn attributes(&self) -> Vec<(String, IAttrValue)> {
+		self
+			.attribute_names()
+			.into_iter()
+			.filter_map(|name| self.get_attribute(&name).map(|value| (name, value)))
+			.collect()
+	}
 	// html
 	fn html(&self) -> &str {
 		self.inner_html()
@@ -290,8 +502,43 @@ pub trait IElementTrait: INodeTrait {
 	fn outer_html(&self) -> &str;
 
 	// append child, insert before, remove child
+	// `insert_adjacent` moves the element, see `InsertPosition`
 	fn insert_adjacent(&mut self, position: &InsertPosition, ele: &BoxDynElement);
+	// the removed element is detached, it's `parent()` should be `None`
 	fn remove_child(&mut self, ele: BoxDynElement);
+	// check if `insert_adjacent_node` can insert the node, the methods moving the child nodes
+	// such as `wrap_inner`, `unwrap` and `append_html` check all the nodes before moving any,
+	// override it together with `insert_adjacent_node`
+	fn can_insert_node(&self, node: &BoxDynNode) -> bool {
+		node.node_type().is_element()
+	}
+	// insert any type node, the node will be moved out from it's parent
+	// element nodes use `insert_adjacent`, others such as text need implemented
+	fn insert_adjacent_node(
+		&mut self,
+		position: &InsertPosition,
+		node: &BoxDynNode,
+	) -> Result<(), Box<dyn Error>> {
+		if node.node_type().is_element() {
+			let ele = node
+				.clone_node()
+				.typed()
+				.into_element()
+				.expect("Call `typed` for element node.");
+			if let Some(parent) = ele.parent().as_mut() {
+				parent.remove_child(ele.cloned());
+			}
+			self.insert_adjacent(position, &ele);
+			return Ok(());
+		}
+		Err(Box::new(IError::InvalidTraitMethodCall {
+			method: "insert_adjacent_node".into(),
+			message: format!(
+				"The insert_adjacent_node method is not implemented for node type '{:?}'.",
+				node.node_type()
+			),
+		}))
+	}
 	// texts
 	fn texts<'b>(&self, _limit_depth: u32) -> Option<Texts<'b>> {
 		None
@@ -305,3 +552,74 @@ pub trait IElementTrait: INodeTrait {
 		}))
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use crate::mock::Doc;
+	use crate::selector::Selector;
+	#[test]
+	fn test_matches_and_closest() {
+		crate::init();
+		let doc = Doc::parse(
+			r#"<div id="a" class="box"><section><p class="t">1</p><p>2</p></section></div><p class="t">3</p>"#,
+		);
+		let root = doc.root();
+		let ps = root.find("p");
+		let first = ps.get(0).unwrap();
+		assert!(first.matches("p"));
+		assert!(first.matches("div > section > p.t"));
+		assert!(first.matches("span, .box p"));
+		assert!(!first.matches("div > p"));
+		assert!(!ps.get(2).unwrap().matches(".box p"));
+		// the element itself, then the ancestors
+		assert_eq!(first.closest("p").unwrap().text(), "1");
+		assert_eq!(
+			first
+				.closest(".box")
+				.unwrap()
+				.get_attribute("id")
+				.unwrap()
+				.to_string(),
+			"a"
+		);
+		assert_eq!(
+			first.closest("div > section").unwrap().tag_name(),
+			"SECTION"
+		);
+		assert!(first.closest("article").is_none());
+		assert!(ps.get(2).unwrap().closest(".box").is_none());
+		// parse once for many elements
+		let selector: Selector = "section, .t".parse().unwrap();
+		let matched = ps
+			.get_ref()
+			.iter()
+			.map(|ele| ele.matches_selector(&selector))
+			.collect::<Vec<_>>();
+		assert_eq!(matched, [true, false, true]);
+		let closest = ps
+			.get_ref()
+			.iter()
+			.map(|ele| {
+				ele
+					.closest_selector(&selector)
+					.map(|ele| ele.tag_name().to_string())
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(
+			closest,
+			[
+				Some("P".to_string()),
+				Some("SECTION".to_string()),
+				Some("P".to_string())
+			]
+		);
+		// the invalid selectors trigger the errors
+		assert!(doc.0.errors.borrow().is_empty());
+		assert!(!first.matches("p >"));
+		assert!(first.closest("[").is_none());
+		let errors = doc.0.errors.borrow();
+		assert_eq!(errors.len(), 2);
+		assert!(errors[0].contains("'matches'"));
+		assert!(errors[1].contains("'closest'"));
+	}
+}
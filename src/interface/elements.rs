@@ -1,20 +1,36 @@
-use super::{BoxDynElement, IAttrValue, INodeType, InsertPosition, MaybeDoc, Texts};
-use crate::utils::{get_class_list, retain_by_index, to_static_str};
-use crate::{constants::ATTR_CLASS, error::Error as IError};
+use super::element::not_implemented_error;
+use super::{
+	BoxDynElement, BoxDynNode, CloneOptions, IAttrValue, IDocumentStats, IDocumentTrait, INodeType,
+	InsertPosition, MaybeDoc, Texts,
+};
+use crate::utils::{
+	get_class_list, retain_by_index, to_data_attr_name, to_dataset_name, to_static_str,
+	SrcsetCandidate,
+};
 use crate::{
 	constants::DEF_NODES_LEN,
 	selector::{
+		explain::{SelectorPlan, SelectorProfiler},
+		is_compound_matched,
 		rule::{MatchAllHandle, MatchOneHandle},
-		Combinator, QueryProcess, Selector, SelectorSegment,
+		Combinator, QueryProcess, QueryStrategy, Selector, SelectorSegment,
 	},
 };
+use crate::{
+	constants::{ATTR_CLASS, ATTR_STYLE},
+	error::Error as IError,
+	style::{split_important, Declarations},
+	xpath::{sort_nodes, XPath, XPathNode},
+};
+use serde_json::Value as JsonValue;
 use std::collections::HashSet;
+use std::rc::Rc;
 use std::{
 	cmp::Ordering,
 	collections::VecDeque,
 	ops::{Bound, RangeBounds},
 };
-use std::{collections::HashMap, error::Error};
+use std::{collections::HashMap, error::Error, fmt::Display, str::FromStr};
 
 // get the ele indexs in tree
 fn get_tree_indexs(ele: &BoxDynElement) -> VecDeque<usize> {
@@ -45,6 +61,99 @@ fn compare_indexs(a: &VecDeque<usize>, b: &VecDeque<usize>) -> Ordering {
 	a_total.cmp(&b_total)
 }
 
+// the counting bloom filter of the ancestors' classes and tags,
+// the keys are formatted like the `ancestor_key` of the matchers, e.g. `.class`, `DIV`
+struct AncestorFilter {
+	counters: Vec<u16>,
+}
+
+type AncestorFilterSlots = [usize; 2];
+const ANCESTOR_FILTER_BITS: usize = 12;
+const ANCESTOR_FILTER_MASK: u32 = (1 << ANCESTOR_FILTER_BITS) - 1;
+
+impl AncestorFilter {
+	fn new() -> Self {
+		AncestorFilter {
+			counters: vec![0; 1 << ANCESTOR_FILTER_BITS],
+		}
+	}
+	// two slots from the fnv-1a hash of the prefix and the name
+	fn slots(prefix: &str, name: &str) -> AncestorFilterSlots {
+		let mut hash: u32 = 0x811c_9dc5;
+		for byte in prefix.bytes().chain(name.bytes()) {
+			hash ^= u32::from(byte);
+			hash = hash.wrapping_mul(0x0100_0193);
+		}
+		[
+			(hash & ANCESTOR_FILTER_MASK) as usize,
+			((hash >> ANCESTOR_FILTER_BITS) & ANCESTOR_FILTER_MASK) as usize,
+		]
+	}
+	// the slots of the element's keys
+	fn element_slots(ele: &BoxDynElement) -> Vec<AncestorFilterSlots> {
+		let mut slots = vec![AncestorFilter::slots(
+			"",
+			&ele.tag_name().to_ascii_uppercase(),
+		)];
+		if let Some(IAttrValue::Value(names, _)) = ele.get_attribute(ATTR_CLASS) {
+			for class_name in get_class_list(&names) {
+				slots.push(AncestorFilter::slots(".", class_name));
+			}
+		}
+		slots
+	}
+	fn insert(&mut self, slots: &[AncestorFilterSlots]) {
+		for slot in slots.iter().flatten() {
+			self.counters[*slot] = self.counters[*slot].saturating_add(1);
+		}
+	}
+	fn remove(&mut self, slots: &[AncestorFilterSlots]) {
+		for slot in slots.iter().flatten() {
+			// a saturated counter can't be decreased
+			if self.counters[*slot] != u16::MAX {
+				self.counters[*slot] -= 1;
+			}
+		}
+	}
+	// `false` if the key can't be in the filter
+	fn may_contain(&self, slots: &AncestorFilterSlots) -> bool {
+		slots.iter().all(|slot| self.counters[*slot] > 0)
+	}
+}
+
+// match the compounds from right to left on the ancestors, `comb` is the combinator
+// of the element matched before, the ancestors are in the context elements
+fn is_ancestors_matched(
+	query: &[Vec<SelectorSegment>],
+	comb: &Combinator,
+	ancestors: &[BoxDynElement],
+) -> bool {
+	let total = query.len();
+	if total == 0 {
+		// the first compound must be in the descendants of the context elements
+		return true;
+	}
+	let compound = &query[total - 1];
+	let next_comb = &compound[0].1;
+	if matches!(comb, Combinator::Children) {
+		return match ancestors.last() {
+			Some(parent) => {
+				is_compound_matched(compound, parent)
+					&& is_ancestors_matched(
+						&query[..total - 1],
+						next_comb,
+						&ancestors[..ancestors.len() - 1],
+					)
+			}
+			None => false,
+		};
+	}
+	(0..ancestors.len()).rev().any(|index| {
+		is_compound_matched(compound, &ancestors[index])
+			&& is_ancestors_matched(&query[..total - 1], next_comb, &ancestors[..index])
+	})
+}
+
 enum ElementRelation {
 	Ancestor,
 	Equal,
@@ -88,7 +197,10 @@ pub(crate) enum FilterType {
 
 #[derive(Default)]
 pub struct Elements<'a> {
-	nodes: Vec<BoxDynElement<'a>>,
+	// shared with the traversal stack, copied when changed
+	nodes: Rc<Vec<BoxDynElement<'a>>>,
+	// the previous elements in the traversal stack
+	prev: Option<Rc<Elements<'a>>>,
 }
 
 /*
@@ -97,13 +209,26 @@ pub struct Elements<'a> {
 impl<'a> Elements<'a> {
 	/*-----------trigger method proxy---------------*/
 	pub(crate) fn trigger_method<F, T: Default>(&self, method: &str, selector: &str, handle: F) -> T
+	where
+		F: Fn(&mut Selector) -> T,
+	{
+		// filter handles don't use lookup
+		self.trigger_method_with_stats(method, selector, None, handle)
+	}
+	// the lookup is used if the document statistics are provided, the planner will choose it by the costs
+	fn trigger_method_with_stats<F, T: Default>(
+		&self,
+		method: &str,
+		selector: &str,
+		stats: Option<&dyn IDocumentStats>,
+		handle: F,
+	) -> T
 	where
 		F: Fn(&mut Selector) -> T,
 	{
 		if !self.is_empty() {
-			// filter handles don't use lookup
-			const USE_LOOKUP: bool = false;
-			let s = Selector::from_str(selector, USE_LOOKUP);
+			let use_lookup = stats.is_some();
+			let s = Selector::from_str_with_stats(selector, use_lookup, stats);
 			if let Ok(mut s) = s {
 				return handle(&mut s);
 			}
@@ -131,20 +256,36 @@ impl<'a> Elements<'a> {
 	}
 	// crate only methods
 	pub(crate) fn with_node(ele: &BoxDynElement) -> Self {
-		Elements {
-			nodes: vec![ele.cloned()],
-		}
+		Elements::with_nodes(vec![ele.cloned()])
 	}
 	// with nodes
 	pub fn with_nodes(nodes: Vec<BoxDynElement<'a>>) -> Self {
-		Elements { nodes }
+		Elements {
+			nodes: Rc::new(nodes),
+			prev: None,
+		}
 	}
 
 	// with capacity
 	pub fn with_capacity(size: usize) -> Self {
-		Elements {
-			nodes: Vec::with_capacity(size),
+		Elements::with_nodes(Vec::with_capacity(size))
+	}
+	/// pub fn `from_html`
+	/// create elements from html by the document, the elements are not in the document tree
+	pub fn from_html(doc: &dyn IDocumentTrait, html: &str) -> Self {
+		let mut fragment = match doc.create_document_fragment() {
+			Some(fragment) => fragment,
+			None => {
+				doc.trigger_error(not_implemented_error("create_document_fragment"));
+				return Elements::new();
+			}
+		};
+		fragment.set_html(html);
+		let result = fragment.children();
+		for ele in result.get_ref() {
+			fragment.remove_child(ele.cloned());
 		}
+		result
 	}
 	/*------------get/set element nodes---------------*/
 	// get a element from the set
@@ -157,14 +298,50 @@ impl<'a> Elements<'a> {
 		&self.nodes
 	}
 
-	// get mut ref
+	// get mut ref, copy the nodes if they are shared with the traversal stack
 	pub(crate) fn get_mut_ref(&mut self) -> &mut Vec<BoxDynElement<'a>> {
-		&mut self.nodes
+		if Rc::get_mut(&mut self.nodes).is_none() {
+			self.nodes = Rc::new(self.nodes.iter().map(|ele| ele.cloned()).collect());
+		}
+		Rc::get_mut(&mut self.nodes).expect("The nodes are not shared")
 	}
 	// push node
 	pub(crate) fn push(&mut self, ele: BoxDynElement<'a>) {
 		self.get_mut_ref().push(ele);
 	}
+	/*------------traversal stack---------------*/
+	// set self as the previous elements of the result, the nodes are shared not copied
+	fn push_stack(&self, mut result: Elements<'a>) -> Elements<'a> {
+		result.prev = Some(Rc::new(self.stack_cloned()));
+		result
+	}
+	// share the nodes and the traversal stack
+	fn stack_cloned(&self) -> Elements<'a> {
+		Elements {
+			nodes: Rc::clone(&self.nodes),
+			prev: self.prev.clone(),
+		}
+	}
+	/// pub fn `end`
+	/// get the previous elements in the traversal stack, empty if no previous
+	pub fn end(&self) -> Elements<'a> {
+		self
+			.prev
+			.as_ref()
+			.map(|prev| prev.stack_cloned())
+			.unwrap_or_default()
+	}
+	/// pub fn `add_back`
+	/// add the previous elements in the traversal stack to self, filtered by the selector if not empty
+	pub fn add_back(&self, selector: &str) -> Elements<'a> {
+		let prev = self.end();
+		let prev = if selector.is_empty() {
+			prev
+		} else {
+			prev.filter_with_type("add_back", selector, FilterType::Filter)
+		};
+		self.push_stack(self.merge(prev))
+	}
 }
 
 /*
@@ -360,20 +537,16 @@ impl<'a> Elements<'a> {
 		}
 		result
 	}
-	// sort
+	// sort, the tree indexs of each element are computed once, they are compared like `compare_indexs`
 	fn sort(&mut self) {
-		self.get_mut_ref().sort_by(|a, b| {
-			let a_index = get_tree_indexs(a);
-			let b_index = get_tree_indexs(b);
-			compare_indexs(&a_index, &b_index)
-		});
+		self.get_mut_ref().sort_by_cached_key(get_tree_indexs);
 	}
 	// unique
 	fn unique(&mut self) {
 		self.get_mut_ref().dedup_by(|a, b| a.is(b));
 	}
 	// sort then unique
-	fn sort_and_unique(&mut self) {
+	pub(crate) fn sort_and_unique(&mut self) {
 		self.sort();
 		self.unique();
 	}
@@ -407,99 +580,153 @@ impl<'a> Elements<'a> {
 	) -> Elements<'a> {
 		let selector = selector.parse::<Selector>();
 		if let Ok(selector) = &selector {
-			let segment = Selector::make_comb_all(comb);
-			let next_selector = Selector::from_segment(segment);
-			let mut result = Elements::with_capacity(DEF_NODES_LEN);
-			let (next_ok, filter) = if !filter.is_empty() {
-				let filter = filter.parse::<Selector>();
-				if let Ok(filter) = filter {
-					(true, Some(filter))
-				} else {
-					self.trigger_method_throw_error(method, Box::new(filter.unwrap_err()));
-					(false, None)
-				}
+			return self.select_with_comb_until_by(
+				method,
+				|cur_eles| cur_eles.filter_type_handle(selector, &FilterType::Is).1,
+				filter,
+				contains,
+				comb,
+			);
+		}
+		self.trigger_method_throw_error(method, Box::new(selector.unwrap_err()));
+		Elements::new()
+	}
+	// for all combinator until elements
+	fn select_with_comb_until_by<F>(
+		&self,
+		method: &str,
+		is_until: F,
+		filter: &str,
+		contains: bool,
+		comb: Combinator,
+	) -> Elements<'a>
+	where
+		F: Fn(&Elements) -> bool,
+	{
+		let segment = Selector::make_comb_all(comb);
+		let next_selector = Selector::from_segment(segment);
+		let mut result = Elements::with_capacity(DEF_NODES_LEN);
+		let filter = if !filter.is_empty() {
+			let filter = filter.parse::<Selector>();
+			if let Ok(filter) = filter {
+				Some(filter)
 			} else {
-				(true, None)
-			};
-			if next_ok {
-				// has filter
-				for ele in self.get_ref() {
-					let mut cur_eles = Elements::with_node(ele);
-					loop {
-						// find the next element
-						cur_eles = cur_eles.find_selector(&next_selector);
-						if !cur_eles.is_empty() {
-							let meet_until = cur_eles.filter_type_handle(&selector, &FilterType::Is).1;
-							// meet the until element, and not contains, stop before check element
-							if meet_until && !contains {
-								break;
-							}
-							// check if cur_eles filter
-							let should_add = if let Some(filter) = &filter {
-								// filter true
-								cur_eles.filter_type_handle(filter, &FilterType::Is).1
-							} else {
-								// no need filter, just add
-								true
-							};
-							if should_add {
-								result.push(
-									cur_eles
-										.get(0)
-										.expect("Elements get 0 must have when length > 0")
-										.cloned(),
-								);
-							}
-							// find the until, stop the loop at the end whenever contains or not
-							if meet_until {
-								break;
-							}
-						} else {
-							break;
-						}
+				self.trigger_method_throw_error(method, Box::new(filter.unwrap_err()));
+				return Elements::new();
+			}
+		} else {
+			None
+		};
+		for ele in self.get_ref() {
+			let mut cur_eles = Elements::with_node(ele);
+			loop {
+				// find the next element
+				cur_eles = cur_eles.find_selector(&next_selector);
+				if !cur_eles.is_empty() {
+					let meet_until = is_until(&cur_eles);
+					// meet the until element, and not contains, stop before check element
+					if meet_until && !contains {
+						break;
+					}
+					// check if cur_eles filter
+					let should_add = if let Some(filter) = &filter {
+						// filter true
+						cur_eles.filter_type_handle(filter, &FilterType::Is).1
+					} else {
+						// no need filter, just add
+						true
+					};
+					if should_add {
+						result.push(
+							cur_eles
+								.get(0)
+								.expect("Elements get 0 must have when length > 0")
+								.cloned(),
+						);
+					}
+					// find the until, stop the loop at the end whenever contains or not
+					if meet_until {
+						break;
 					}
+				} else {
+					break;
 				}
-				return result;
 			}
-		} else {
-			self.trigger_method_throw_error(method, Box::new(selector.unwrap_err()));
 		}
-		Elements::new()
+		result
 	}
 
 	// prev
 	pub fn prev(&self, selector: &str) -> Elements<'a> {
-		self.select_with_comb("prev", selector, Combinator::Prev)
+		self.push_stack(self.select_with_comb("prev", selector, Combinator::Prev))
 	}
 	// prev_all
 	pub fn prev_all(&self, selector: &str) -> Elements<'a> {
 		let uniques = self.unique_sibling_last();
-		uniques.select_with_comb("prev_all", selector, Combinator::PrevAll)
+		self.push_stack(uniques.select_with_comb("prev_all", selector, Combinator::PrevAll))
 	}
 	// prev_until
 	pub fn prev_until(&self, selector: &str, filter: &str, contains: bool) -> Elements<'a> {
 		let uniques = self.unique_sibling_last();
-		uniques.select_with_comb_until("prev_until", selector, filter, contains, Combinator::Prev)
+		self.push_stack(uniques.select_with_comb_until(
+			"prev_until",
+			selector,
+			filter,
+			contains,
+			Combinator::Prev,
+		))
+	}
+	// prev_until_in
+	pub fn prev_until_in(&self, until: &Elements, filter: &str, contains: bool) -> Elements<'a> {
+		let uniques = self.unique_sibling_last();
+		self.push_stack(uniques.select_with_comb_until_by(
+			"prev_until_in",
+			|cur_eles| cur_eles.is_in(until),
+			filter,
+			contains,
+			Combinator::Prev,
+		))
 	}
 	// next
 	pub fn next(&self, selector: &str) -> Elements<'a> {
-		self.select_with_comb("next", selector, Combinator::Next)
+		self.push_stack(self.select_with_comb("next", selector, Combinator::Next))
 	}
 	// next_all
 	pub fn next_all(&self, selector: &str) -> Elements<'a> {
 		// unique, keep the first sibling node
 		let uniques = self.unique_sibling_first();
-		uniques.select_with_comb("next_all", selector, Combinator::NextAll)
+		self.push_stack(uniques.select_with_comb("next_all", selector, Combinator::NextAll))
 	}
 	// next_until
 	pub fn next_until(&self, selector: &str, filter: &str, contains: bool) -> Elements<'a> {
 		// unique, keep the first sibling node
 		let uniques = self.unique_sibling_first();
-		uniques.select_with_comb_until("next_until", selector, filter, contains, Combinator::Next)
+		self.push_stack(uniques.select_with_comb_until(
+			"next_until",
+			selector,
+			filter,
+			contains,
+			Combinator::Next,
+		))
+	}
+	// next_until_in
+	pub fn next_until_in(&self, until: &Elements, filter: &str, contains: bool) -> Elements<'a> {
+		// unique, keep the first sibling node
+		let uniques = self.unique_sibling_first();
+		self.push_stack(uniques.select_with_comb_until_by(
+			"next_until_in",
+			|cur_eles| cur_eles.is_in(until),
+			filter,
+			contains,
+			Combinator::Next,
+		))
 	}
 
 	// siblings
 	pub fn siblings(&self, selector: &str) -> Elements<'a> {
+		self.push_stack(self.siblings_handle(selector))
+	}
+	fn siblings_handle(&self, selector: &str) -> Elements<'a> {
 		let uniques = self.unique_all_siblings();
 		// when selector is empty or only
 		let mut siblings_selector: Selector;
@@ -553,11 +780,15 @@ impl<'a> Elements<'a> {
 	}
 	// children
 	pub fn children(&self, selector: &str) -> Elements<'a> {
-		self.select_with_comb("children", selector, Combinator::Children)
+		self.push_stack(self.select_with_comb("children", selector, Combinator::Children))
 	}
 
 	// parent
 	pub fn parent(&self, selector: &str) -> Elements<'a> {
+		self.push_stack(self.parent_handle(selector))
+	}
+	// `parent` without the traversal stack
+	fn parent_handle(&self, selector: &str) -> Elements<'a> {
 		// unique, keep the first sibling node
 		let uniques = self.unique_sibling_first();
 		uniques.select_with_comb("parent", selector, Combinator::Parent)
@@ -568,7 +799,7 @@ impl<'a> Elements<'a> {
 		let uniques = self.unique_sibling_first();
 		let mut result = uniques.select_with_comb("parents", selector, Combinator::ParentAll);
 		result.sort_and_unique();
-		result
+		self.push_stack(result)
 	}
 	// parents_until
 	pub fn parents_until(&self, selector: &str, filter: &str, contains: bool) -> Elements<'a> {
@@ -582,10 +813,52 @@ impl<'a> Elements<'a> {
 			Combinator::Parent,
 		);
 		result.sort_and_unique();
-		result
+		self.push_stack(result)
+	}
+	// parents_until_in
+	pub fn parents_until_in(&self, until: &Elements, filter: &str, contains: bool) -> Elements<'a> {
+		// unique, keep the first sibling node
+		let uniques = self.unique_sibling_first();
+		let mut result = uniques.select_with_comb_until_by(
+			"parents_until_in",
+			|cur_eles| cur_eles.is_in(until),
+			filter,
+			contains,
+			Combinator::Parent,
+		);
+		result.sort_and_unique();
+		self.push_stack(result)
+	}
+	// offset_parent
+	// the closest ancestor whose inline style `position` is not static, or the root element
+	pub fn offset_parent(&self) -> Elements<'a> {
+		fn is_positioned(ele: &BoxDynElement) -> bool {
+			if let Some(IAttrValue::Value(style, _)) = ele.get_attribute(ATTR_STYLE) {
+				if let Some(value) = Declarations::parse(&style).get_value("position") {
+					return !value.eq_ignore_ascii_case("static");
+				}
+			}
+			false
+		}
+		let mut result = Elements::with_capacity(self.length());
+		for ele in self.get_ref() {
+			let mut cur = ele.parent();
+			while let Some(parent) = cur {
+				if parent.parent().is_none() || is_positioned(&parent) {
+					result.push(parent);
+					break;
+				}
+				cur = parent.parent();
+			}
+		}
+		result.sort_and_unique();
+		self.push_stack(result)
 	}
 	// closest
 	pub fn closest(&self, selector: &str) -> Elements<'a> {
+		self.push_stack(self.closest_handle(selector))
+	}
+	fn closest_handle(&self, selector: &str) -> Elements<'a> {
 		// when selector is not provided
 		if selector.is_empty() {
 			return Elements::new();
@@ -638,11 +911,150 @@ impl<'a> Elements<'a> {
 		}
 	}
 	// for `find` and `select_with_comb`
-	fn find_selector(&self, selector: &Selector) -> Elements<'a> {
+	// search the candidates of the last compound in the descendants,
+	// then check the previous compounds from right to left until the elements
+	fn select_right_to_left(&self, query: &[Vec<SelectorSegment>], use_filter: bool) -> Elements<'a> {
+		let last_index = query.len() - 1;
+		let last_query = &query[last_index];
+		let candidates = if use_filter {
+			// the candidates from the document caches needn't walk the tree
+			match last_query[0]
+				.0
+				.cache_handle
+				.as_ref()
+				.and_then(|handle| handle(self))
+			{
+				Some(cached) => {
+					Elements::select_in_cached(self, last_query, &Combinator::ChildrenAll, Some(cached))
+				}
+				None => {
+					let slots = Elements::ancestor_slots(query);
+					return self.select_right_to_left_filtered(query, &slots);
+				}
+			}
+		} else {
+			Elements::select(self, last_query, Some(&Combinator::ChildrenAll))
+		};
+		if candidates.is_empty() {
+			return candidates;
+		}
+		candidates
+			.filter_by_handle(|_, ele| self.has_ele(ele, &last_query[0].1, Some(&query[..last_index])))
+	}
+	// the slots of the keys must be in the ancestors
+	fn ancestor_slots(query: &[Vec<SelectorSegment>]) -> Vec<AncestorFilterSlots> {
+		query[..query.len() - 1]
+			.iter()
+			.flatten()
+			.filter_map(|(matcher, _)| matcher.ancestor_key.as_ref())
+			.map(|key| AncestorFilter::slots("", key))
+			.collect()
+	}
+	// walk the descendants with the ancestor filter, the candidates of the last compound
+	// are checked on the ancestors only if all the keys may be in them,
+	// all the combinators must be `>` or descendant, and the first must be descendant
+	fn select_right_to_left_filtered(
+		&self,
+		query: &[Vec<SelectorSegment>],
+		slots: &[AncestorFilterSlots],
+	) -> Elements<'a> {
+		let last_index = query.len() - 1;
+		let mut result = Elements::with_capacity(DEF_NODES_LEN);
+		let mut filter = AncestorFilter::new();
+		let mut ancestors: Vec<BoxDynElement> = Vec::with_capacity(DEF_NODES_LEN);
+		fn loop_handle<'a>(
+			ele: &BoxDynElement,
+			query: (&[Vec<SelectorSegment>], &[AncestorFilterSlots]),
+			filter: &mut AncestorFilter,
+			ancestors: &mut Vec<BoxDynElement<'a>>,
+			result: &mut Elements<'a>,
+		) {
+			let (query, slots) = query;
+			let last_index = query.len() - 1;
+			let last_query = &query[last_index];
+			let child_nodes_len = ele.child_nodes_length();
+			for index in 0..child_nodes_len {
+				let node = ele
+					.child_nodes_item(index)
+					.expect("child nodes index < length");
+				if !matches!(node.node_type(), INodeType::Element) {
+					continue;
+				}
+				let child_ele = node
+					.typed()
+					.into_element()
+					.expect("Call typed for element node");
+				if is_compound_matched(last_query, &child_ele)
+					&& slots.iter().all(|slot| filter.may_contain(slot))
+					&& is_ancestors_matched(&query[..last_index], &last_query[0].1, ancestors)
+				{
+					result.push(child_ele.cloned());
+				}
+				if child_ele.child_nodes_length() > 0 {
+					let child_slots = AncestorFilter::element_slots(&child_ele);
+					filter.insert(&child_slots);
+					ancestors.push(child_ele.cloned());
+					loop_handle(&child_ele, (query, slots), filter, ancestors, result);
+					ancestors.pop();
+					filter.remove(&child_slots);
+				}
+			}
+		}
+		debug_assert!(last_index > 0);
+		for ele in self.unique_parents().get_ref() {
+			loop_handle(
+				ele,
+				(query, slots),
+				&mut filter,
+				&mut ancestors,
+				&mut result,
+			);
+		}
+		result
+	}
+	// the ancestor filter can be used if all the combinators are `>` or descendant and the first is descendant,
+	// the compounds except the last have no `#id`, starting from the id is fast enough
+	fn can_use_ancestor_filter(query: &[Vec<SelectorSegment>]) -> bool {
+		let last_index = query.len() - 1;
+		last_index > 0
+			&& matches!(query[0][0].1, Combinator::ChildrenAll)
+			&& query[1..].iter().all(|compound| {
+				matches!(
+					compound[0].1,
+					Combinator::Children | Combinator::ChildrenAll
+				)
+			}) && query[..last_index]
+			.iter()
+			.flatten()
+			.all(|(matcher, _)| !matcher.in_cache)
+	}
+	// if the document uses the ancestor filter
+	pub(crate) fn use_ancestor_filter(&self) -> bool {
+		self
+			.document()
+			.map(|doc| doc.use_ancestor_filter())
+			.unwrap_or(false)
+	}
+	// the ancestor filter is used for the query if the document uses it and the query can
+	pub(crate) fn is_ancestor_filtered(process: &QueryProcess, use_filter: bool) -> bool {
+		use_filter && process.should_in.is_none() && Elements::can_use_ancestor_filter(&process.query)
+	}
+	pub(crate) fn find_selector(&self, selector: &Selector) -> Elements<'a> {
 		let mut result = Elements::with_capacity(DEF_NODES_LEN);
 		if !self.is_empty() {
+			let use_filter = self.use_ancestor_filter();
 			for p in &selector.process {
-				let QueryProcess { should_in, query } = p;
+				let QueryProcess {
+					should_in,
+					query,
+					strategy,
+				} = p;
+				// the ancestor filter also runs the descendant selectors from right to left
+				let use_filter = Elements::is_ancestor_filtered(p, use_filter);
+				if use_filter || matches!(strategy, QueryStrategy::RightToLeft) {
+					result = result.merge(self.select_right_to_left(query, use_filter));
+					continue;
+				}
 				let first_query = &query[0];
 				let mut group: Option<Elements> = None;
 				let mut start_rule_index: usize = 0;
@@ -709,6 +1121,8 @@ impl<'a> Elements<'a> {
 					let query = &query[start_rule_index..];
 					if !query.is_empty() {
 						let mut is_empty = false;
+						// the elements selected from several parents are grouped by the parents
+						let need_sort = query.len() > 1 || group.is_some();
 						let mut group = Elements::select(group.as_ref().unwrap_or(self), &query[0], None);
 
 						for rules in &query[1..] {
@@ -719,12 +1133,15 @@ impl<'a> Elements<'a> {
 							}
 						}
 						if !is_empty {
-							result = result.add(group);
+							if need_sort {
+								group.sort_and_unique();
+							}
+							result = result.merge(group);
 						}
 					} else {
 						let group = group.unwrap_or_else(|| self.cloned());
 						if !group.is_empty() {
-							result = result.add(group);
+							result = result.merge(group);
 						}
 					}
 				}
@@ -736,7 +1153,97 @@ impl<'a> Elements<'a> {
 	/// pub fn `find`
 	/// get elements by selector, support most of css selectors
 	pub fn find(&self, selector: &str) -> Elements<'a> {
-		self.trigger_method("find", selector, |selector| self.find_selector(selector))
+		self.push_stack(self.find_handle(selector))
+	}
+	// `find` without the traversal stack, for the crate inner calls
+	pub(crate) fn find_handle(&self, selector: &str) -> Elements<'a> {
+		let stats = self.document().and_then(|doc| doc.statistics());
+		self.trigger_method_with_stats("find", selector, stats.as_deref(), |selector| {
+			self.find_selector(selector)
+		})
+	}
+
+	/// pub fn `explain`
+	/// the plan that `find` uses for the selector in the elements, see `Selector::explain`
+	pub fn explain(&self, selector: &str) -> SelectorPlan {
+		let stats = self.document().and_then(|doc| doc.statistics());
+		self.trigger_method_with_stats("explain", selector, stats.as_deref(), |selector| {
+			selector.explain(self)
+		})
+	}
+
+	/// pub fn `find_profiled`
+	/// same as `find`, also count the matcher invocations and the elements visited of each segment,
+	/// see `Elements::explain` for the steps
+	pub fn find_profiled(&self, selector: &str) -> (Elements<'a>, SelectorProfiler) {
+		let stats = self.document().and_then(|doc| doc.statistics());
+		let (result, profiler) =
+			self.trigger_method_with_stats("find_profiled", selector, stats.as_deref(), |selector| {
+				let profiler = selector.profile(self);
+				(self.find_selector(selector), profiler)
+			});
+		(self.push_stack(result), profiler)
+	}
+
+	// evaluate the xpath with each element as the context node, the nodes are in document order
+	fn xpath_handle(&self, method: &str, expr: &str) -> Vec<XPathNode<'a>> {
+		if self.is_empty() {
+			return Vec::new();
+		}
+		let xpath = match XPath::parse(expr) {
+			Ok(xpath) => xpath,
+			Err(err) => {
+				self.trigger_method_throw_error(method, Box::new(err));
+				return Vec::new();
+			}
+		};
+		let mut nodes = Vec::with_capacity(DEF_NODES_LEN);
+		for ele in self.get_ref() {
+			match xpath.evaluate(ele).map(|value| value.into_nodes()) {
+				Ok(Some(cur_nodes)) => nodes.extend(cur_nodes),
+				Ok(None) => {
+					self.trigger_method_throw_error(
+						method,
+						Box::new(IError::InvalidXPath {
+							context: String::from(expr),
+							reason: String::from("The result must be a node-set"),
+						}),
+					);
+					return Vec::new();
+				}
+				Err(err) => {
+					self.trigger_method_throw_error(method, Box::new(err));
+					return Vec::new();
+				}
+			}
+		}
+		sort_nodes(nodes)
+	}
+
+	/// pub fn `xpath`
+	/// get elements by xpath 1.0 expression, each element is the context node
+	pub fn xpath(&self, expr: &str) -> Elements<'a> {
+		let mut result = Elements::with_capacity(DEF_NODES_LEN);
+		for node in self.xpath_handle("xpath", expr) {
+			if let XPathNode::Element(ele) = node {
+				if ele.node_type().is_element() {
+					result.push(ele);
+				}
+			}
+		}
+		self.push_stack(result)
+	}
+
+	/// pub fn `xpath_texts`
+	/// get text nodes by xpath 1.0 expression, e.g. `.//text()`
+	pub fn xpath_texts(&self, expr: &str) -> Texts<'a> {
+		let mut result = Texts::with_capacity(DEF_NODES_LEN);
+		for node in self.xpath_handle("xpath_texts", expr) {
+			if let XPathNode::Text(text) = node {
+				result.get_mut_ref().push(text);
+			}
+		}
+		result
 	}
 	// select one rule
 	// the rule must not in cache
@@ -1023,10 +1530,29 @@ impl<'a> Elements<'a> {
 	) -> Elements<'a> {
 		let first_rule = &rules[0];
 		let comb = comb.unwrap_or(&first_rule.1);
-		let mut elements = if first_rule.0.in_cache && matches!(comb, Combinator::ChildrenAll) {
-			let (matcher, ..) = first_rule;
-			// set use cache true
-			let cached = matcher.apply(&elements, Some(true));
+		let (matcher, ..) = first_rule;
+		let cached = if matches!(comb, Combinator::ChildrenAll) {
+			if let Some(handle) = &matcher.cache_handle {
+				handle(elements)
+			} else if matcher.in_cache {
+				// set use cache true
+				Some(matcher.apply(&elements, Some(true)))
+			} else {
+				None
+			}
+		} else {
+			None
+		};
+		Elements::select_in_cached(elements, rules, comb, cached)
+	}
+	// select ele by rules, the `cached` are the candidates of the first rule from the document caches
+	fn select_in_cached(
+		elements: &Elements<'a>,
+		rules: &[SelectorSegment],
+		comb: &Combinator,
+		cached: Option<Elements<'a>>,
+	) -> Elements<'a> {
+		let mut elements = if let Some(cached) = cached {
 			let count = cached.length();
 			if count > 0 {
 				let mut result = Elements::with_capacity(count);
@@ -1041,7 +1567,7 @@ impl<'a> Elements<'a> {
 				Elements::new()
 			}
 		} else {
-			Elements::select_by_rule(&elements, first_rule, Some(comb))
+			Elements::select_by_rule(&elements, &rules[0], Some(comb))
 		};
 		if !elements.is_empty() && rules.len() > 1 {
 			for rule in &rules[1..] {
@@ -1056,7 +1582,7 @@ impl<'a> Elements<'a> {
 	// cloned
 	pub fn cloned(&self) -> Elements<'a> {
 		let mut result = Elements::with_capacity(self.length());
-		for ele in &self.nodes {
+		for ele in self.nodes.iter() {
 			result.push(ele.cloned());
 		}
 		result
@@ -1093,18 +1619,12 @@ impl<'a> Elements<'a> {
 			}
 			ParentAll => {
 				for ele in elements.get_ref() {
-					if let Some(parent) = &ele.parent() {
-						if self.includes(&parent) {
+					let mut cur = ele.parent();
+					while let Some(ancestor) = cur {
+						if self.includes(&ancestor) {
 							return true;
 						}
-						if let Some(ancestor) = &parent.parent() {
-							if self.includes(&ancestor) {
-								return true;
-							}
-							if self.has_ele(&ancestor, comb, None) {
-								return true;
-							}
-						}
+						cur = ancestor.parent();
 					}
 				}
 			}
@@ -1204,7 +1724,7 @@ impl<'a> Elements<'a> {
 						break;
 					}
 					_ => {
-						result = result.add(filtered);
+						result = result.merge(filtered);
 					}
 				}
 			}
@@ -1220,7 +1740,7 @@ impl<'a> Elements<'a> {
 					result = self.cloned();
 				} else {
 					// filtered by not in
-					result = self.not_in(&result);
+					result = self.filter_in_handle(&result, FilterType::Not).0;
 				}
 			}
 			_ => {
@@ -1242,7 +1762,7 @@ impl<'a> Elements<'a> {
 				let mut start_index = 0;
 				let search_total = search.length();
 				for ele in eles {
-					if let Some(index) = search.index_of(ele, start_index) {
+					if let Some(index) = search.position_of(ele, start_index) {
 						// also in search, include
 						start_index = index + 1;
 						result.push(ele.cloned());
@@ -1255,7 +1775,7 @@ impl<'a> Elements<'a> {
 			FilterType::Not => {
 				let mut start_index = 0;
 				for ele in eles {
-					if let Some(index) = search.index_of(ele, start_index) {
+					if let Some(index) = search.position_of(ele, start_index) {
 						// also in search, exclude
 						start_index = index + 1;
 					} else {
@@ -1276,7 +1796,7 @@ impl<'a> Elements<'a> {
 					let mut is_all_matched = true;
 					let mut start_index = 0;
 					for ele in eles {
-						if let Some(index) = search.index_of(ele, start_index) {
+						if let Some(index) = search.position_of(ele, start_index) {
 							// also in search, exclude
 							start_index = index + 1;
 						} else {
@@ -1291,16 +1811,34 @@ impl<'a> Elements<'a> {
 		(result, all_matched)
 	}
 
+	// filter or not by the selector, without the traversal stack
+	pub(crate) fn filter_with_type(
+		&self,
+		method: &str,
+		selector: &str,
+		filter_type: FilterType,
+	) -> Elements<'a> {
+		self.trigger_method(method, selector, |selector| {
+			self.filter_type_handle(&selector, &filter_type).0
+		})
+	}
+
 	// filter
 	pub fn filter(&self, selector: &str) -> Elements<'a> {
 		const METHOD: &str = "filter";
-		self.trigger_method(METHOD, selector, |selector| {
-			self.filter_type_handle(&selector, &FilterType::Filter).0
-		})
+		self.push_stack(self.filter_with_type(METHOD, selector, FilterType::Filter))
 	}
 
 	// filter_by
 	pub fn filter_by<F>(&self, handle: F) -> Elements<'a>
+	where
+		F: Fn(usize, &BoxDynElement) -> bool,
+	{
+		self.push_stack(self.filter_by_handle(handle))
+	}
+
+	// `filter_by` without the traversal stack, for the crate inner calls
+	pub(crate) fn filter_by_handle<F>(&self, handle: F) -> Elements<'a>
 	where
 		F: Fn(usize, &BoxDynElement) -> bool,
 	{
@@ -1316,7 +1854,7 @@ impl<'a> Elements<'a> {
 
 	// filter in
 	pub fn filter_in(&self, search: &Elements) -> Elements<'a> {
-		self.filter_in_handle(search, FilterType::Filter).0
+		self.push_stack(self.filter_in_handle(search, FilterType::Filter).0)
 	}
 
 	// is
@@ -1378,9 +1916,7 @@ impl<'a> Elements<'a> {
 	// not
 	pub fn not(&self, selector: &str) -> Elements<'a> {
 		const METHOD: &str = "not";
-		self.trigger_method(METHOD, selector, |selector| {
-			self.filter_type_handle(&selector, &FilterType::Not).0
-		})
+		self.push_stack(self.filter_with_type(METHOD, selector, FilterType::Not))
 	}
 
 	// not by
@@ -1394,13 +1930,13 @@ impl<'a> Elements<'a> {
 				result.push(ele.cloned());
 			}
 		}
-		result
+		self.push_stack(result)
 	}
 
 	/// pub fn `not_in`
 	/// remove element from `Self` which is also in `search`
 	pub fn not_in(&self, search: &Elements) -> Elements<'a> {
-		self.filter_in_handle(search, FilterType::Not).0
+		self.push_stack(self.filter_in_handle(search, FilterType::Not).0)
 	}
 
 	// has
@@ -1421,9 +1957,9 @@ impl<'a> Elements<'a> {
 			}
 			false
 		}
-		self.trigger_method(METHOD, selector, |selector| {
-			self.filter_by(|_, ele| loop_handle(ele, selector))
-		})
+		self.push_stack(self.trigger_method(METHOD, selector, |selector| {
+			self.filter_by_handle(|_, ele| loop_handle(ele, selector))
+		}))
 	}
 
 	// has_in
@@ -1443,7 +1979,7 @@ impl<'a> Elements<'a> {
 			}
 			false
 		}
-		self.filter_by(|_, ele| loop_handle(ele, &search))
+		self.push_stack(self.filter_by_handle(|_, ele| loop_handle(ele, &search)))
 	}
 }
 
@@ -1455,11 +1991,12 @@ impl<'a> Elements<'a> {
 	/// pub fn `eq`
 	/// get a element by index
 	pub fn eq(&self, index: usize) -> Elements<'a> {
-		if let Some(ele) = self.get(index) {
+		let result = if let Some(ele) = self.get(index) {
 			Elements::with_node(ele)
 		} else {
 			Elements::new()
-		}
+		};
+		self.push_stack(result)
 	}
 
 	/// pub fn `first`
@@ -1478,6 +2015,9 @@ impl<'a> Elements<'a> {
 	/// get elements by a range parameter
 	/// `slice(0..1)` equal to `eq(0)`, `first`
 	pub fn slice<T: RangeBounds<usize>>(&self, range: T) -> Elements<'a> {
+		self.push_stack(self.slice_handle(range))
+	}
+	fn slice_handle<T: RangeBounds<usize>>(&self, range: T) -> Elements<'a> {
 		let mut start = 0;
 		let mut end = self.length();
 		match range.start_bound() {
@@ -1524,6 +2064,11 @@ impl<'a> Elements<'a> {
 	/// concat two element set to a new set,
 	/// it will take the owership of the parameter element set, but no sence to `Self`
 	pub fn add(&self, eles: Elements<'a>) -> Elements<'a> {
+		self.push_stack(self.merge(eles))
+	}
+
+	// merge two element set in tree order, without the traversal stack
+	fn merge(&self, eles: Elements<'a>) -> Elements<'a> {
 		if self.is_empty() {
 			return eles;
 		}
@@ -1684,13 +2229,46 @@ impl<'a> Elements<'a> {
 		result
 	}
 
+	/// pub fn `index`
+	/// get the first element's index in it's sibling elements
+	pub fn index(&self) -> Option<usize> {
+		self
+			.get(0)
+			.map(|ele| ele.previous_element_siblings().length())
+	}
+
+	/// pub fn `index_of`
+	/// get the first element's index in the elements matched the selector in the whole tree
+	pub fn index_of(&self, selector: &str) -> Option<usize> {
+		self.trigger_method("index_of", selector, |selector| {
+			let ele = self.get(0).expect("Elements must not empty");
+			let root = Elements::with_node(&ele.root());
+			let search = root
+				.filter_type_handle(selector, &FilterType::Filter)
+				.0
+				.merge(root.find_selector(selector));
+			// the matched elements are in tree order, use binary search
+			let indexs = get_tree_indexs(ele);
+			search
+				.get_ref()
+				.binary_search_by(|cur| compare_indexs(&get_tree_indexs(cur), &indexs))
+				.ok()
+		})
+	}
+
+	/// pub fn `index_in`
+	/// get the first element's index in the `elements`
+	pub fn index_in(&self, elements: &Elements) -> Option<usize> {
+		self.get(0).and_then(|ele| elements.position_of(ele, 0))
+	}
+
 	/// check if the ele list contains some ele
 	fn includes(&self, ele: &BoxDynElement) -> bool {
 		self.get_ref().iter().any(|n| ele.is(n))
 	}
 
-	/// index of
-	fn index_of(&self, ele: &BoxDynElement, start_index: usize) -> Option<usize> {
+	/// position of the ele, search from the start index
+	fn position_of(&self, ele: &BoxDynElement, start_index: usize) -> Option<usize> {
 		let total = self.length();
 		if start_index < total {
 			let nodes = self.get_ref();
@@ -1718,7 +2296,7 @@ impl<'a> Elements<'a> {
 /*
 *** Content APIs
 **  [Methods]
-**  text, html, set_text, set_html, texts
+**  text, html, set_text, set_html, contents, texts
 */
 impl<'a> Elements<'a> {
 	// -------------Content API----------------
@@ -1736,6 +2314,7 @@ impl<'a> Elements<'a> {
 	/// set each element's text to content
 	pub fn set_text(&mut self, content: &str) -> &mut Self {
 		for ele in self.get_mut_ref() {
+			Elements::unsync_index(ele, false);
 			ele.set_text(content);
 		}
 		self
@@ -1754,7 +2333,9 @@ impl<'a> Elements<'a> {
 	/// set each element's html to content
 	pub fn set_html(&mut self, content: &str) -> &mut Self {
 		for ele in self.get_mut_ref() {
+			Elements::unsync_index(ele, false);
 			ele.set_html(content);
+			Elements::sync_index(ele, true);
 		}
 		self
 	}
@@ -1768,6 +2349,16 @@ impl<'a> Elements<'a> {
 		""
 	}
 
+	/// pub fn `contents`
+	/// get the child nodes of each element, including text nodes and comments
+	pub fn contents(&self) -> Vec<BoxDynNode<'a>> {
+		let mut result = Vec::with_capacity(DEF_NODES_LEN);
+		for ele in self.get_ref() {
+			result.extend(ele.child_nodes());
+		}
+		result
+	}
+
 	/// pub fn `texts`
 	/// get the text node of each element
 	pub fn texts(&self, limit_depth: u32) -> Texts<'a> {
@@ -1784,7 +2375,7 @@ impl<'a> Elements<'a> {
 /*
 *** Attribute APIs
 **  [Methods]
-**  attr, set_attr, remove_attr,
+**  attr, attrs, attr_as, attr_url, attr_srcset, attr_json, set_attr, remove_attr,
 **  has_class, add_class, remove_class, toggle_class
 */
 impl<'a> Elements<'a> {
@@ -1797,11 +2388,76 @@ impl<'a> Elements<'a> {
 		None
 	}
 
+	/// pub fn `attrs`
+	/// get all the attributes of the first element in source order
+	pub fn attrs(&self) -> Vec<(String, IAttrValue)> {
+		if let Some(ele) = self.get(0) {
+			return ele.attributes();
+		}
+		Vec::new()
+	}
+
+	/// pub fn `attr_as`
+	/// get the first element's attribute value, parsed as the type
+	pub fn attr_as<T: FromStr>(&self, attr_name: &str) -> Result<Option<T>, IError>
+	where
+		T::Err: Display,
+	{
+		self.attr(attr_name).map(|value| value.parse()).transpose()
+	}
+
+	/// pub fn `attr_url`
+	/// get the first element's attribute value as an url,
+	/// relative to the `base`, or the document's `<base href>` if `base` is `None`,
+	/// if no base url can be found, the value is returned as it is.
+	pub fn attr_url(&self, attr_name: &str, base: Option<&str>) -> Result<Option<String>, IError> {
+		if let Some(value) = self.attr(attr_name) {
+			let base = match base {
+				Some(base) => Some(String::from(base)),
+				None => self.base_href(),
+			};
+			return match base {
+				Some(base) => value.to_url(&base).map(Some),
+				None => Ok(Some(value.to_string())),
+			};
+		}
+		Ok(None)
+	}
+
+	// the document's `<base href>`
+	fn base_href(&self) -> Option<String> {
+		let ele = self.get(0)?;
+		let root = Elements::with_node(&ele.root());
+		root
+			.find_selector(&"base[href]".parse::<Selector>().ok()?)
+			.attr("href")
+			.map(|href| href.to_string())
+	}
+
+	/// pub fn `attr_srcset`
+	/// get the first element's `srcset` attribute value as image candidates
+	pub fn attr_srcset(&self) -> Result<Option<Vec<SrcsetCandidate>>, IError> {
+		self
+			.attr("srcset")
+			.map(|value| value.to_srcset())
+			.transpose()
+	}
+
+	/// pub fn `attr_json`
+	/// get the first element's attribute value parsed as json
+	pub fn attr_json(&self, attr_name: &str) -> Result<Option<JsonValue>, IError> {
+		self
+			.attr(attr_name)
+			.map(|value| value.to_json())
+			.transpose()
+	}
+
 	/// pub fn `set_attr`
 	/// set each element's attribute to `key` = attr_name, `value` = value.  
 	pub fn set_attr(&mut self, attr_name: &str, value: Option<&str>) -> &mut Self {
 		for ele in self.get_mut_ref() {
 			ele.set_attribute(attr_name, value);
+			Elements::sync_index(ele, false);
 		}
 		self
 	}
@@ -1810,6 +2466,7 @@ impl<'a> Elements<'a> {
 	pub fn remove_attr(&mut self, attr_name: &str) -> &mut Self {
 		for ele in self.get_mut_ref() {
 			ele.remove_attribute(attr_name);
+			Elements::sync_index(ele, false);
 		}
 		self
 	}
@@ -1850,9 +2507,11 @@ impl<'a> Elements<'a> {
 						}
 					}
 					ele.set_attribute(ATTR_CLASS, Some(orig_class_list.join(" ").as_str()));
+					Elements::sync_index(ele, false);
 					continue;
 				}
 				ele.set_attribute(ATTR_CLASS, Some(class_name));
+				Elements::sync_index(ele, false);
 			}
 		}
 		self
@@ -1875,6 +2534,7 @@ impl<'a> Elements<'a> {
 					if !removed_indexs.is_empty() {
 						retain_by_index(&mut orig_class_list, &removed_indexs);
 						ele.set_attribute(ATTR_CLASS, Some(orig_class_list.join(" ").as_str()));
+						Elements::sync_index(ele, false);
 					}
 				}
 			}
@@ -1911,42 +2571,234 @@ impl<'a> Elements<'a> {
 					}
 					if need_set {
 						ele.set_attribute(ATTR_CLASS, Some(orig_class_list.join(" ").as_str()));
+						Elements::sync_index(ele, false);
 					}
 					continue;
 				}
 				ele.set_attribute(ATTR_CLASS, Some(class_name));
+				Elements::sync_index(ele, false);
 			}
 		}
 		self
 	}
 }
 
+/*
+*** Style APIs
+**  [Methods]
+**  css, css_map, set_css, remove_css
+*/
+impl<'a> Elements<'a> {
+	// the inline style declarations
+	fn style_handle(ele: &BoxDynElement) -> Declarations {
+		match ele.get_attribute(ATTR_STYLE) {
+			Some(IAttrValue::Value(style, _)) => Declarations::parse(&style),
+			_ => Declarations::default(),
+		}
+	}
+	// write back the inline style, remove the attribute if no declarations left
+	fn set_style_handle(ele: &mut BoxDynElement, style: &Declarations) {
+		if style.is_empty() {
+			ele.remove_attribute(ATTR_STYLE);
+		} else {
+			ele.set_attribute(ATTR_STYLE, Some(style.to_string().as_str()));
+		}
+		Elements::sync_index(ele, false);
+	}
+
+	/// pub fn `css`
+	/// get the first element's inline style property value
+	pub fn css(&self, name: &str) -> Option<String> {
+		self.get(0).and_then(|ele| {
+			Elements::style_handle(ele)
+				.get_value(name)
+				.map(String::from)
+		})
+	}
+
+	/// pub fn `css_map`
+	/// get the first element's inline style declarations in order
+	pub fn css_map(&self) -> Declarations {
+		self.get(0).map(Elements::style_handle).unwrap_or_default()
+	}
+
+	/// pub fn `set_css`
+	/// set the inline style property of each element, the value can end with `!important`,
+	/// an empty value removes the property
+	pub fn set_css(&mut self, name: &str, value: &str) -> &mut Self {
+		let (value, important) = split_important(value);
+		let value = value.trim();
+		if value.is_empty() {
+			return self.remove_css(name);
+		}
+		for ele in self.get_mut_ref() {
+			let mut style = Elements::style_handle(ele);
+			style.set(name, value, important);
+			Elements::set_style_handle(ele, &style);
+		}
+		self
+	}
+
+	/// pub fn `remove_css`
+	/// remove the inline style property of each element
+	pub fn remove_css(&mut self, name: &str) -> &mut Self {
+		for ele in self.get_mut_ref() {
+			let mut style = Elements::style_handle(ele);
+			if style.remove(name).is_some() {
+				Elements::set_style_handle(ele, &style);
+			}
+		}
+		self
+	}
+}
+
+/*
+*** Data APIs
+**  [Methods]
+**  data, data_all, set_data
+*/
+impl<'a> Elements<'a> {
+	// coerce the `data-*` attribute value like jQuery
+	fn coerce_data(value: IAttrValue) -> JsonValue {
+		let value = value.to_string();
+		match value.as_str() {
+			"true" => JsonValue::Bool(true),
+			"false" => JsonValue::Bool(false),
+			"null" => JsonValue::Null,
+			_ => {
+				let is_json = (value.starts_with('{') && value.ends_with('}'))
+					|| (value.starts_with('[') && value.ends_with(']'));
+				// number only if it keeps the same after convert
+				let is_number = !is_json
+					&& value
+						.parse::<f64>()
+						.map(|num| num.is_finite() && num.to_string() == value)
+						.unwrap_or(false);
+				if is_json || is_number {
+					if let Ok(data) = serde_json::from_str::<JsonValue>(&value) {
+						return data;
+					}
+				}
+				JsonValue::String(value)
+			}
+		}
+	}
+
+	/// pub fn `data`
+	/// get the first element's `data-*` attribute value, the `key` is the camel case name,
+	/// values like `true`, `false`, `null`, numbers and json are converted
+	pub fn data(&self, key: &str) -> Option<JsonValue> {
+		self
+			.attr(&to_data_attr_name(key))
+			.map(Elements::coerce_data)
+	}
+
+	/// pub fn `data_all`
+	/// get all the `data-*` attributes of the first element, the keys are camel case names
+	pub fn data_all(&self) -> HashMap<String, JsonValue> {
+		let mut result = HashMap::with_capacity(DEF_NODES_LEN);
+		if let Some(ele) = self.get(0) {
+			for (attr_name, value) in ele.attributes() {
+				if let Some(key) = to_dataset_name(&attr_name) {
+					result.insert(key, Elements::coerce_data(value));
+				}
+			}
+		}
+		result
+	}
+
+	/// pub fn `set_data`
+	/// set each element's `data-*` attribute, strings are set as it is, others as json
+	pub fn set_data<T: Into<JsonValue>>(&mut self, key: &str, value: T) -> &mut Self {
+		let value = match value.into() {
+			JsonValue::String(value) => value,
+			value => value.to_string(),
+		};
+		self.set_attr(&to_data_attr_name(key), Some(&value))
+	}
+}
+
 /*
 *** Mutations
 **  [methods]
-**  remove, empty,
+**  deep_clone, remove, detach, empty,
 **  append, append_to, prepend, prepend_to,
-**  before, insert_before, after, insert_after
+**  before, insert_before, after, insert_after,
+**  wrap, wrap_all, wrap_inner, unwrap, replace_with, replace_all,
+**  append_html, prepend_html, before_html, after_html
 */
 impl<'a> Elements<'a> {
+	/// pub fn `deep_clone`
+	/// detached copies of the elements, see `IElementTrait::deep_clone`,
+	/// if any element can't be cloned, trigger the error and return empty
+	pub fn deep_clone(&self, options: CloneOptions) -> Elements<'a> {
+		match self.try_deep_clone(options) {
+			Ok(copies) => copies,
+			Err(e) => {
+				self.trigger_method_throw_error("deep_clone", e);
+				Elements::new()
+			}
+		}
+	}
+	// clone all the elements or none
+	fn try_deep_clone(&self, options: CloneOptions) -> Result<Elements<'a>, Box<dyn Error>> {
+		let mut result = Elements::with_capacity(self.length());
+		for ele in self.get_ref() {
+			result.push(ele.deep_clone(options)?);
+		}
+		Ok(result)
+	}
 	/// pub fn `remove`
 	pub fn remove(self) {
 		for ele in self.into_iter() {
 			if let Some(parent) = ele.parent().as_mut() {
+				Elements::unsync_index(&ele, true);
 				parent.remove_child(ele);
 			}
 		}
 	}
+	/// pub fn `detach`
+	/// remove the elements from their parents but keep them, so they can be inserted again
+	pub fn detach(self) -> Elements<'a> {
+		for ele in self.get_ref() {
+			Elements::detach_ele(ele);
+		}
+		self
+	}
 	// pub fn `empty`
 	pub fn empty(&mut self) -> &mut Self {
 		self.set_text("");
 		self
 	}
 	// `insert`
+	// the inserted elements keep their order at any position, e.g. `append` [a, b] gets `..., a, b`,
+	// `prepend` gets `a, b, ...`, the elements are moved to the last target, others use the copies.
+	// it's changed from the old behavior: inserting the elements in reverse order to every target.
+	// if the document can't make the copies, the elements are moved through every target like before,
+	// so they end up in the last target.
 	fn insert(&mut self, dest: &Elements, position: &InsertPosition) -> &mut Self {
-		for ele in self.get_mut_ref() {
-			for inserted in dest.get_ref().iter().rev() {
-				ele.insert_adjacent(position, inserted);
+		let total = self.length();
+		// insert from the last element to keep the order
+		let is_reverse = matches!(
+			position,
+			InsertPosition::AfterBegin | InsertPosition::AfterEnd
+		);
+		// make the copies before moving any
+		let mut copies = (1..total)
+			.map(|_| dest.try_deep_clone(Default::default()))
+			.collect::<Result<Vec<_>, _>>()
+			.unwrap_or_else(|_| (1..total).map(|_| dest.cloned()).collect());
+		copies.push(dest.cloned());
+		for (ele, inserted) in self.get_mut_ref().iter_mut().zip(copies) {
+			let inserted = inserted.get_ref();
+			if is_reverse {
+				for inserted in inserted.iter().rev() {
+					Elements::move_ele(ele, position, inserted);
+				}
+			} else {
+				for inserted in inserted {
+					Elements::move_ele(ele, position, inserted);
+				}
 			}
 		}
 		self
@@ -1993,18 +2845,840 @@ impl<'a> Elements<'a> {
 		self.insert(elements, &InsertPosition::AfterEnd);
 		self
 	}
+	/// pub(crate) fn `sync_index`
+	/// keep the document index in sync after the element or it's subtree changed
+	pub(crate) fn sync_index(ele: &BoxDynElement, subtree: bool) {
+		if let Some(doc) = ele.owner_document() {
+			if let Some(index) = doc.index_provider() {
+				if subtree {
+					index.update_subtree(ele);
+				} else {
+					index.update_element(ele);
+				}
+			}
+		}
+	}
+	// drop the element or only it's descendants from the document index before they are removed
+	fn unsync_index(ele: &BoxDynElement, self_included: bool) {
+		if let Some(doc) = ele.owner_document() {
+			if let Some(index) = doc.index_provider() {
+				if self_included {
+					index.remove_subtree(ele);
+				} else {
+					for child in ele.children().get_ref() {
+						index.remove_subtree(child);
+					}
+				}
+			}
+		}
+	}
+	// detach the element from it's parent
+	fn detach_ele(ele: &BoxDynElement) {
+		if let Some(parent) = ele.parent().as_mut() {
+			Elements::unsync_index(ele, true);
+			parent.remove_child(ele.cloned());
+		}
+	}
+	// move the element to the position of the target
+	fn move_ele(target: &mut BoxDynElement, position: &InsertPosition, ele: &BoxDynElement) {
+		if target.is(ele) {
+			return;
+		}
+		Elements::detach_ele(ele);
+		target.insert_adjacent(position, ele);
+		Elements::sync_index(ele, true);
+	}
+	// make detached copies of the element, all or none
+	fn copy_ele(ele: &BoxDynElement, count: usize) -> Result<Elements<'a>, Box<dyn Error>> {
+		let mut copies = Elements::with_capacity(count);
+		for _ in 0..count {
+			copies.push(ele.deep_clone(Default::default())?);
+		}
+		Ok(copies)
+	}
+	// the deepest first descendant, the wrapped content will be put in
+	fn innermost(ele: &BoxDynElement) -> BoxDynElement<'a> {
+		let mut cur = ele.cloned();
+		while let Some(child) = cur.children().get(0).map(|child| child.cloned()) {
+			cur = child;
+		}
+		cur
+	}
+	// move all the child nodes of the element into the target
+	fn move_child_nodes(
+		&self,
+		method: &str,
+		ele: &BoxDynElement,
+		target: &mut BoxDynElement,
+		position: &InsertPosition,
+	) -> bool {
+		let mut nodes = ele.child_nodes();
+		// insert from the last node to keep the order
+		if matches!(
+			position,
+			InsertPosition::AfterBegin | InsertPosition::AfterEnd
+		) {
+			nodes.reverse();
+		}
+		// check all the nodes first, so the tree is never half moved
+		if let Some(node) = nodes.iter().find(|node| !target.can_insert_node(node)) {
+			self.trigger_method_throw_error(
+				method,
+				Box::new(IError::InvalidTraitMethodCall {
+					method: "insert_adjacent_node".into(),
+					message: format!(
+						"The insert_adjacent_node method is not implemented for node type '{:?}'.",
+						node.node_type()
+					),
+				}),
+			);
+			return false;
+		}
+		for node in nodes {
+			if let Err(e) = target.insert_adjacent_node(position, &node) {
+				self.trigger_method_throw_error(method, e);
+				return false;
+			}
+			if let Some(ele) = node.typed().into_element() {
+				Elements::sync_index(&ele, true);
+			}
+		}
+		true
+	}
+	// `insert_html`
+	fn insert_html(&mut self, method: &str, html: &str, position: &InsertPosition) -> &mut Self {
+		if let Some(doc) = &self.document() {
+			for ele in self.get_ref() {
+				let mut fragment = match doc.create_document_fragment() {
+					Some(fragment) => fragment,
+					None => {
+						self.trigger_method_throw_error(
+							method,
+							not_implemented_error("create_document_fragment"),
+						);
+						break;
+					}
+				};
+				fragment.set_html(html);
+				let mut target = ele.cloned();
+				if !self.move_child_nodes(method, &fragment, &mut target, position) {
+					break;
+				}
+			}
+		}
+		self
+	}
+	/// pub fn `append_html`
+	pub fn append_html(&mut self, html: &str) -> &mut Self {
+		self.insert_html("append_html", html, &InsertPosition::BeforeEnd)
+	}
+	/// pub fn `prepend_html`
+	pub fn prepend_html(&mut self, html: &str) -> &mut Self {
+		self.insert_html("prepend_html", html, &InsertPosition::AfterBegin)
+	}
+	/// pub fn `before_html`
+	pub fn before_html(&mut self, html: &str) -> &mut Self {
+		self.insert_html("before_html", html, &InsertPosition::BeforeBegin)
+	}
+	/// pub fn `after_html`
+	pub fn after_html(&mut self, html: &str) -> &mut Self {
+		self.insert_html("after_html", html, &InsertPosition::AfterEnd)
+	}
+	/// pub fn `wrap`
+	/// wrap each element with a copy of the first element of `wrapper`,
+	/// trigger the error and change nothing if the copies can't be made
+	pub fn wrap(&mut self, wrapper: &Elements) -> &mut Self {
+		if let Some(wrap_ele) = wrapper.get(0) {
+			let total = self
+				.get_ref()
+				.iter()
+				.filter(|ele| ele.parent().is_some())
+				.count();
+			let copies = match Elements::copy_ele(wrap_ele, total) {
+				Ok(copies) => copies,
+				Err(e) => {
+					self.trigger_method_throw_error("wrap", e);
+					return self;
+				}
+			};
+			let mut copies = copies.into_iter();
+			for ele in self.get_mut_ref() {
+				if ele.parent().is_none() {
+					continue;
+				}
+				let copy = match copies.next() {
+					Some(copy) => copy,
+					None => break,
+				};
+				ele.insert_adjacent(&InsertPosition::BeforeBegin, &copy);
+				Elements::sync_index(&copy, true);
+				let mut inner = Elements::innermost(&copy);
+				Elements::move_ele(&mut inner, &InsertPosition::BeforeEnd, ele);
+			}
+		}
+		self
+	}
+	/// pub fn `wrap_all`
+	/// wrap all the elements with one copy of the first element of `wrapper`,
+	/// the copy is inserted before the first element
+	pub fn wrap_all(&mut self, wrapper: &Elements) -> &mut Self {
+		if self.is_empty() {
+			return self;
+		}
+		if let Some(wrap_ele) = wrapper.get(0) {
+			let copy = match wrap_ele.deep_clone(Default::default()) {
+				Ok(copy) => copy,
+				Err(e) => {
+					self.trigger_method_throw_error("wrap_all", e);
+					return self;
+				}
+			};
+			if let Some(first) = self.get_mut_ref().get_mut(0) {
+				if first.parent().is_none() {
+					return self;
+				}
+				first.insert_adjacent(&InsertPosition::BeforeBegin, &copy);
+				Elements::sync_index(&copy, true);
+			}
+			let mut inner = Elements::innermost(&copy);
+			for ele in self.get_ref() {
+				Elements::move_ele(&mut inner, &InsertPosition::BeforeEnd, ele);
+			}
+		}
+		self
+	}
+	/// pub fn `wrap_inner`
+	/// wrap the contents of each element with a copy of the first element of `wrapper`,
+	/// trigger the error and change nothing if the copies can't be made
+	pub fn wrap_inner(&mut self, wrapper: &Elements) -> &mut Self {
+		if let Some(wrap_ele) = wrapper.get(0) {
+			let copies = match Elements::copy_ele(wrap_ele, self.length()) {
+				Ok(copies) => copies,
+				Err(e) => {
+					self.trigger_method_throw_error("wrap_inner", e);
+					return self;
+				}
+			};
+			for (ele, copy) in self.get_ref().iter().zip(copies.get_ref()) {
+				let mut inner = Elements::innermost(copy);
+				if !self.move_child_nodes("wrap_inner", ele, &mut inner, &InsertPosition::BeforeEnd) {
+					break;
+				}
+				let mut ele = ele.cloned();
+				ele.insert_adjacent(&InsertPosition::BeforeEnd, copy);
+				Elements::sync_index(copy, true);
+			}
+		}
+		self
+	}
+	/// pub fn `unwrap`
+	/// remove the parents of the elements, keep the parents' child nodes in place,
+	/// the parents can be filtered by the `selector`
+	pub fn unwrap(&mut self, selector: &str) -> &mut Self {
+		let parents = self.parent_handle(selector);
+		for parent in parents.get_ref() {
+			// the root element can't be removed
+			if parent.parent().is_none() {
+				continue;
+			}
+			let mut target = parent.cloned();
+			if !self.move_child_nodes("unwrap", parent, &mut target, &InsertPosition::BeforeBegin) {
+				break;
+			}
+			Elements::detach_ele(parent);
+		}
+		self
+	}
+	/// pub fn `replace_with`
+	/// replace each element with `elements`, the last element use the `elements` itself,
+	/// others use the copies, the replaced elements are detached.
+	/// if the document can't make the copies, the `elements` are moved through every element like `append`,
+	/// so only the last element is replaced by them, the others are just detached.
+	pub fn replace_with(&mut self, elements: &mut Elements) -> &mut Self {
+		let targets = self.filter_by_handle(|_, ele| ele.parent().is_some() && !elements.includes(ele));
+		let total = targets.length();
+		if total == 0 {
+			return self;
+		}
+		// the copies of `elements` for the targets except the last one
+		let copies = elements
+			.get_ref()
+			.iter()
+			.map(|ele| Elements::copy_ele(ele, total - 1))
+			.collect::<Result<Vec<_>, _>>()
+			.ok();
+		for (index, ele) in targets.get_ref().iter().enumerate() {
+			let mut target = ele.cloned();
+			if let (Some(copies), true) = (&copies, index < total - 1) {
+				for copy in copies.iter().filter_map(|copies| copies.get(index)) {
+					target.insert_adjacent(&InsertPosition::BeforeBegin, copy);
+					Elements::sync_index(copy, true);
+				}
+			} else {
+				for inserted in elements.get_ref() {
+					Elements::move_ele(&mut target, &InsertPosition::BeforeBegin, inserted);
+				}
+			}
+			Elements::detach_ele(ele);
+		}
+		self
+	}
+	/// pub fn `replace_all`
+	/// replace each element of `elements` with self
+	pub fn replace_all(&mut self, elements: &mut Elements) -> &mut Self {
+		elements.replace_with(self);
+		self
+	}
 }
 
 impl<'a> IntoIterator for Elements<'a> {
 	type Item = BoxDynElement<'a>;
 	type IntoIter = Box<dyn Iterator<Item = Self::Item> + 'a>;
 	fn into_iter(self) -> Self::IntoIter {
-		Box::new(self.nodes.into_iter())
+		let nodes = Rc::try_unwrap(self.nodes)
+			.unwrap_or_else(|nodes| nodes.iter().map(|ele| ele.cloned()).collect());
+		Box::new(nodes.into_iter())
 	}
 }
 
 impl<'a> From<Vec<BoxDynElement<'a>>> for Elements<'a> {
 	fn from(nodes: Vec<BoxDynElement<'a>>) -> Self {
-		Elements { nodes }
+		Elements::with_nodes(nodes)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{AncestorFilter, CloneOptions, Elements, INodeType};
+	use crate::interface::IDocumentStats;
+	use crate::mock::Doc;
+	use crate::selector::{QueryStrategy, Selector};
+	use serde_json::{json, Value as JsonValue};
+	use std::rc::Rc;
+	type InsertHandle = fn(&mut Elements, &mut Elements);
+	// the `.rare` class is the most selective, the `article` tag is less
+	struct Stats;
+	impl IDocumentStats for Stats {
+		fn total_elements(&self) -> usize {
+			1000
+		}
+		fn tag_count(&self, tag_name: &str) -> Option<usize> {
+			Some(if tag_name.eq_ignore_ascii_case("article") {
+				5
+			} else {
+				300
+			})
+		}
+		fn class_count(&self, class_name: &str) -> Option<usize> {
+			Some(if class_name == "rare" { 1 } else { 500 })
+		}
+		fn attr_count(&self, _attr_name: &str) -> Option<usize> {
+			None
+		}
+	}
+	#[test]
+	fn test_move_child_nodes() {
+		crate::init();
+		let doc = Doc::parse(r#"<div id="box"><b>b</b>a<i>c</i><!--d--></div>"#);
+		let root = doc.root();
+		let mut target = root.find("#box");
+		let wrapper = Elements::from_html(&doc, "<p></p>");
+		// the text nodes can't be inserted, nothing is moved
+		target.wrap_inner(&wrapper);
+		assert_eq!(target.html(), "<b>b</b>a<i>c</i><!--d-->");
+		target.append_html("e<i>f</i>");
+		assert_eq!(target.html(), "<b>b</b>a<i>c</i><!--d-->");
+		// move the text and comment nodes in order
+		doc.0.node_insertable.set(true);
+		target.wrap_inner(&wrapper);
+		assert_eq!(target.html(), "<p><b>b</b>a<i>c</i><!--d--></p>");
+		root.find("#box b").unwrap("p");
+		assert_eq!(target.html(), "<b>b</b>a<i>c</i><!--d-->");
+		target.append_html("e<i>f</i>");
+		target.prepend_html("x<i>y</i>");
+		assert_eq!(target.html(), "x<i>y</i><b>b</b>a<i>c</i><!--d-->e<i>f</i>");
+	}
+	#[test]
+	fn test_traversal_stack() {
+		crate::init();
+		let doc = Doc::parse("<ul><li>a</li><li>b</li></ul>");
+		let root = doc.root();
+		let items = root.find("ul").children("li");
+		assert_eq!(items.length(), 2);
+		assert_eq!(items.end().get(0).unwrap().tag_name(), "UL");
+		assert_eq!(items.end().end().length(), 1);
+		assert!(items.end().end().end().is_empty());
+		// the crate inner calls don't record the stack
+		assert!(root.find_handle("li").prev.is_none());
+		assert!(items.filter_by_handle(|index, _| index == 0).prev.is_none());
+		// the stack shares the nodes, they are copied when changed
+		let mut list = root.find("ul");
+		let items = list.children("li");
+		let prev = items.prev.as_ref().unwrap();
+		assert!(Rc::ptr_eq(&prev.nodes, &list.nodes));
+		list.get_mut_ref().clear();
+		assert!(list.is_empty());
+		assert_eq!(items.end().length(), 1);
+		assert_eq!(items.end().into_iter().count(), 1);
+	}
+	#[test]
+	fn test_index() {
+		crate::init();
+		let doc = Doc::parse(
+			r#"<ul><li>a</li>text<li class="b">b</li><li class="c">c</li></ul><ul><li class="c">d</li></ul>"#,
+		);
+		let root = doc.root();
+		let items = root.find("li");
+		// the text nodes are not counted
+		assert_eq!(root.find(".b").index(), Some(1));
+		assert_eq!(root.find(".c").index(), Some(2));
+		assert_eq!(root.find("ul").eq(1).index(), Some(1));
+		assert_eq!(root.find(".missing").index(), None);
+		// the index in the whole tree
+		assert_eq!(root.find(".c").index_of("li"), Some(2));
+		assert_eq!(root.find(".c").eq(1).index_of("li"), Some(3));
+		assert_eq!(root.find(".c").eq(1).index_of(".c"), Some(1));
+		assert_eq!(root.find(".c").eq(1).index_of("ul, .b, .c"), Some(4));
+		assert_eq!(root.find(".b").index_of(".c"), None);
+		assert_eq!(root.find(".missing").index_of("li"), None);
+		assert_eq!(root.find(".b").index_of("li["), None);
+		// the index of the first element in the elements
+		assert_eq!(root.find(".c").index_in(&items), Some(2));
+		assert_eq!(root.find(".c").eq(1).index_in(&items), Some(3));
+		assert_eq!(items.index_in(&root.find(".c, .b")), None);
+		assert_eq!(items.eq(1).index_in(&root.find(".c, .b")), Some(0));
+		assert_eq!(root.find(".b").index_in(&Elements::new()), None);
+		assert_eq!(Elements::new().index_in(&items), None);
+	}
+	#[test]
+	fn test_until() {
+		crate::init();
+		let doc = Doc::parse(
+			r#"<div id="box" style="position: relative"><ul id="list"><li id="a">a</li><li class="x" id="b">b</li><li id="c">c</li><li class="x" id="d">d</li><li id="e">e</li></ul>text<!--note--></div><p id="free"><span id="s">s</span></p>"#,
+		);
+		let root = doc.root();
+		let ids = |eles: &Elements| {
+			eles
+				.get_ref()
+				.iter()
+				.map(|ele| {
+					ele
+						.get_attribute("id")
+						.map(|id| id.to_string())
+						.unwrap_or_default()
+				})
+				.collect::<Vec<_>>()
+				.join(",")
+		};
+		let e = root.find("#e");
+		let a = root.find("#a");
+		let until = root.find("#b, #list");
+		assert_eq!(
+			ids(&e.prev_until("#b", "", false)),
+			ids(&e.prev_until_in(&until, "", false))
+		);
+		assert_eq!(ids(&e.prev_until_in(&until, "", false)), "d,c");
+		assert_eq!(ids(&e.prev_until_in(&until, "", true)), "d,c,b");
+		assert_eq!(ids(&e.prev_until_in(&until, ".x", true)), "d,b");
+		assert_eq!(ids(&a.prev_until_in(&until, "", false)), "");
+		assert_eq!(
+			ids(&e.prev_until_in(&Elements::new(), "", false)),
+			"d,c,b,a"
+		);
+		let until = root.find("#d");
+		assert_eq!(
+			ids(&a.next_until("#d", "", false)),
+			ids(&a.next_until_in(&until, "", false))
+		);
+		assert_eq!(ids(&a.next_until_in(&until, "", false)), "b,c");
+		assert_eq!(ids(&a.next_until_in(&until, ":not(.x)", true)), "c");
+		assert_eq!(
+			ids(&root.find("#c, #a").next_until_in(&until, "", true)),
+			"b,c,d"
+		);
+		let until = root.find("#box");
+		let s = root.find("#s");
+		assert_eq!(
+			ids(&a.parents_until("#box", "", false)),
+			ids(&a.parents_until_in(&until, "", false))
+		);
+		assert_eq!(ids(&a.parents_until_in(&until, "", false)), "list");
+		assert_eq!(ids(&a.parents_until_in(&until, "", true)), "box,list");
+		assert_eq!(
+			ids(&root.find("#a, #s").parents_until_in(&until, "ul, p", true)),
+			"list,free"
+		);
+		assert_eq!(ids(&s.parents_until_in(&until, "p", false)), "free");
+		// the filter is a selector
+		assert!(a.next_until_in(&until, "li[", false).is_empty());
+		// the positioned ancestor, or the root
+		assert_eq!(ids(&root.find("li").offset_parent()), "box");
+		assert_eq!(ids(&root.find("#box").offset_parent()), "");
+		let offset_parent = s.offset_parent();
+		assert_eq!(offset_parent.length(), 1);
+		assert!(offset_parent.get(0).unwrap().parent().is_none());
+		assert_eq!(root.find("#a, #s").offset_parent().length(), 2);
+		root
+			.find("#box")
+			.set_attr("style", Some("position: static"));
+		assert!(root
+			.find("li")
+			.offset_parent()
+			.get(0)
+			.unwrap()
+			.parent()
+			.is_none());
+		// the child nodes include the texts and comments
+		let contents = root.find("#box, #free").contents();
+		assert_eq!(contents.len(), 4);
+		assert_eq!(contents[1].text_content(), "text");
+		assert!(matches!(contents[2].node_type(), INodeType::Comment));
+		assert!(root
+			.find("#a, #s")
+			.contents()
+			.iter()
+			.all(|node| matches!(node.node_type(), INodeType::Text)));
+		assert!(Elements::new().contents().is_empty());
+	}
+	#[test]
+	fn test_attr_helpers() {
+		crate::init();
+		let html = r#"<div id="box" data-count=" 12 " data-ratio="1.5" data-bad="1x" data-json='{"a":[1,2]}' hidden><img src="img/a.png" srcset="a.png 1x, b-2.png 2x, c.png 300w"><a href="../b.html?x=1">b</a></div>"#;
+		let doc = Doc::parse(html);
+		let root = doc.root();
+		let div = root.find("div");
+		assert_eq!(div.attr_as::<u32>("data-count").unwrap(), Some(12));
+		assert_eq!(div.attr_as::<f64>("data-ratio").unwrap(), Some(1.5));
+		assert!(div.attr_as::<u32>("data-bad").is_err());
+		assert!(div.attr_as::<u32>("hidden").is_err());
+		assert_eq!(div.attr_as::<u32>("data-missing").unwrap(), None);
+		assert_eq!(root.find("p").attr_as::<u32>("data-count").unwrap(), None);
+		// the json values
+		let json = div.attr_json("data-json").unwrap().unwrap();
+		assert_eq!(json["a"][1], 2);
+		assert!(div.attr_json("data-bad").is_err());
+		assert_eq!(div.attr_json("data-missing").unwrap(), None);
+		// the srcset candidates
+		let img = root.find("img");
+		let candidates = img.attr_srcset().unwrap().unwrap();
+		let urls = candidates
+			.iter()
+			.map(|candidate| candidate.url.as_str())
+			.collect::<Vec<_>>();
+		assert_eq!(urls, ["a.png", "b-2.png", "c.png"]);
+		assert_eq!(candidates[1].density, Some(2.0));
+		assert_eq!(candidates[2].width, Some(300));
+		assert_eq!(div.attr_srcset().unwrap(), None);
+		// the urls without the `<base href>` are returned as they are
+		assert_eq!(img.attr_url("src", None).unwrap().unwrap(), "img/a.png");
+		assert_eq!(
+			root
+				.find("a")
+				.attr_url("href", Some("https://example.com/x/y/"))
+				.unwrap()
+				.unwrap(),
+			"https://example.com/x/b.html?x=1"
+		);
+		assert!(div
+			.attr_url("hidden", Some("https://example.com/"))
+			.is_err());
+		assert_eq!(div.attr_url("data-missing", None).unwrap(), None);
+		// resolved by the `<base href>`, the `base` parameter is preferred
+		let doc = Doc::parse(&format!(
+			r#"<head><base href="https://example.com/docs/"></head><body>{}</body>"#,
+			html
+		));
+		let root = doc.root();
+		assert_eq!(
+			root.find("img").attr_url("src", None).unwrap().unwrap(),
+			"https://example.com/docs/img/a.png"
+		);
+		assert_eq!(
+			root.find("a").attr_url("href", None).unwrap().unwrap(),
+			"https://example.com/b.html?x=1"
+		);
+		assert_eq!(
+			root
+				.find("img")
+				.attr_url("src", Some("http://other.com/"))
+				.unwrap()
+				.unwrap(),
+			"http://other.com/img/a.png"
+		);
+	}
+	#[test]
+	fn test_data() {
+		crate::init();
+		let doc = Doc::parse(
+			r#"<div data-user-name="tom" data-id="01" data-price="1.50" data-exp="1e3" data-count="12" data-rate="-0.5" data-on="true" data-off="false" data-none="null" data-list='[1,"a"]' data-obj='{"a":{"b":true}}' data-bad='{"a"' data-flag data-x-y="xy">1</div><p>2</p>"#,
+		);
+		let root = doc.root();
+		let div = root.find("div");
+		// the camel case keys, the dashed keys are the attribute names too
+		assert_eq!(div.data("userName"), Some(json!("tom")));
+		assert_eq!(div.data("user-name"), Some(json!("tom")));
+		assert_eq!(div.data("xY"), Some(json!("xy")));
+		// the numbers only if they keep the same
+		assert_eq!(div.data("id"), Some(json!("01")));
+		assert_eq!(div.data("price"), Some(json!("1.50")));
+		assert_eq!(div.data("exp"), Some(json!("1e3")));
+		assert_eq!(div.data("count"), Some(json!(12)));
+		assert_eq!(div.data("rate"), Some(json!(-0.5)));
+		assert_eq!(div.data("on"), Some(json!(true)));
+		assert_eq!(div.data("off"), Some(json!(false)));
+		assert_eq!(div.data("none"), Some(JsonValue::Null));
+		assert_eq!(div.data("list"), Some(json!([1, "a"])));
+		assert_eq!(div.data("obj"), Some(json!({"a": {"b": true}})));
+		assert_eq!(div.data("bad"), Some(json!(r#"{"a""#)));
+		assert_eq!(div.data("flag"), Some(json!("")));
+		assert_eq!(div.data("missing"), None);
+		assert_eq!(root.find("p").data("id"), None);
+		let all = div.data_all();
+		assert_eq!(all.len(), 14);
+		assert_eq!(all["userName"], json!("tom"));
+		assert_eq!(all["id"], json!("01"));
+		assert_eq!(all["xY"], json!("xy"));
+		assert!(!all.contains_key("user-name"));
+		assert!(root.find("p").data_all().is_empty());
+		// set then get
+		let mut items = root.find("div, p");
+		items.set_data("userName", "jerry");
+		items.set_data("size", 3);
+		items.set_data("opts", json!({"a": [1, 2]}));
+		items.set_data("empty", JsonValue::Null);
+		let p = root.find("p");
+		assert_eq!(p.attr("data-user-name").unwrap().to_string(), "jerry");
+		assert_eq!(p.attr("data-opts").unwrap().to_string(), r#"{"a":[1,2]}"#);
+		for key in ["userName", "size", "opts", "empty"] {
+			assert_eq!(div.data(key), p.data(key), "{}", key);
+		}
+		assert_eq!(p.data("size"), Some(json!(3)));
+		assert_eq!(p.data("opts"), Some(json!({"a": [1, 2]})));
+		assert_eq!(p.data("empty"), Some(JsonValue::Null));
+		items.set_data("id", "01");
+		assert_eq!(p.data("id"), Some(json!("01")));
+	}
+	#[test]
+	fn test_insert_order() {
+		crate::init();
+		let html = "<ul><li>x</li></ul><ul><li>y</li></ul><i>a</i><b>b</b>";
+		let cases: [(&str, InsertHandle); 4] = [
+			(
+				"<ul><li>x</li><i>a</i><b>b</b></ul><ul><li>y</li><i>a</i><b>b</b></ul>",
+				|targets, sources| {
+					targets.append(sources);
+				},
+			),
+			(
+				"<ul><i>a</i><b>b</b><li>x</li></ul><ul><i>a</i><b>b</b><li>y</li></ul>",
+				|targets, sources| {
+					targets.prepend(sources);
+				},
+			),
+			(
+				"<i>a</i><b>b</b><ul><li>x</li></ul><i>a</i><b>b</b><ul><li>y</li></ul>",
+				|targets, sources| {
+					targets.before(sources);
+				},
+			),
+			(
+				"<ul><li>x</li></ul><i>a</i><b>b</b><ul><li>y</li></ul><i>a</i><b>b</b>",
+				|targets, sources| {
+					targets.after(sources);
+				},
+			),
+		];
+		for (expected, handle) in cases.iter() {
+			let doc = Doc::parse(html);
+			doc.0.node_insertable.set(true);
+			let root = doc.root();
+			let mut sources = root.find("i, b");
+			handle(&mut root.find("ul"), &mut sources);
+			assert_eq!(root.html(), *expected);
+			// the last target gets the elements themselves
+			assert!(!root.find("i").eq(0).is_in(&sources));
+			assert!(root.find("i").eq(1).is_in(&sources));
+		}
+		// the text can't be inserted so no copies, the element is moved to the last target
+		let doc = Doc::parse("<p>1</p><p>2</p><i>n</i>");
+		let root = doc.root();
+		root.find("p").append(&mut root.find("i"));
+		assert_eq!(root.html(), "<p>1</p><p>2<i>n</i></p>");
+		doc.0.node_insertable.set(true);
+		root.find("p").append(&mut root.find("i"));
+		assert_eq!(root.html(), "<p>1<i>n</i></p><p>2<i>n</i></p>");
+	}
+	#[test]
+	fn test_wrap_and_replace() {
+		crate::init();
+		let html = r#"<div><p>1</p></div><section><p>2</p><p>3</p></section><b class="w"><u></u></b>"#;
+		// the targets under different parents
+		let doc = Doc::parse(html);
+		let root = doc.root();
+		root.find("p").wrap(&root.find(".w"));
+		assert_eq!(
+			root.html(),
+			r#"<div><b class="w"><u><p>1</p></u></b></div><section><b class="w"><u><p>2</p></u></b><b class="w"><u><p>3</p></u></b></section><b class="w"><u></u></b>"#
+		);
+		let doc = Doc::parse(html);
+		let root = doc.root();
+		root.find("p").wrap_all(&root.find(".w"));
+		assert_eq!(
+			root.html(),
+			r#"<div><b class="w"><u><p>1</p><p>2</p><p>3</p></u></b></div><section></section><b class="w"><u></u></b>"#
+		);
+		let doc = Doc::parse(html);
+		let root = doc.root();
+		let mut replacement = root.find(".w");
+		root.find("p").replace_with(&mut replacement);
+		assert_eq!(
+			root.html(),
+			r#"<div><b class="w"><u></u></b></div><section><b class="w"><u></u></b><b class="w"><u></u></b></section>"#
+		);
+		assert!(root.find(".w").eq(2).is_in(&replacement));
+		let doc = Doc::parse(html);
+		let root = doc.root();
+		root
+			.find(".w")
+			.replace_all(&mut root.find("div p, section p:last-child"));
+		assert_eq!(
+			root.html(),
+			r#"<div><b class="w"><u></u></b></div><section><p>2</p><b class="w"><u></u></b></section>"#
+		);
+		// the replacement with text can't be cloned, it's moved to the last target
+		let html = "<div><p>1</p></div><section><p>2</p></section><i>n</i>";
+		let doc = Doc::parse(html);
+		let root = doc.root();
+		root.find("p").replace_with(&mut root.find("i"));
+		assert_eq!(root.html(), "<div></div><section><i>n</i></section>");
+		let doc = Doc::parse(html);
+		doc.0.node_insertable.set(true);
+		let root = doc.root();
+		root.find("p").replace_with(&mut root.find("i"));
+		assert_eq!(
+			root.html(),
+			"<div><i>n</i></div><section><i>n</i></section>"
+		);
+	}
+	#[test]
+	fn test_deep_clone() {
+		crate::init();
+		let doc = Doc::parse(r#"<table><tr class="row"><td>a<!--b--></td><td><br></td></tr></table>"#);
+		let root = doc.root();
+		let row = root.find("tr");
+		// the text nodes can't be inserted, the element can't be cloned
+		assert!(row.deep_clone(Default::default()).is_empty());
+		doc.0.node_insertable.set(true);
+		let copy = row.deep_clone(Default::default());
+		assert_eq!(copy.outer_html(), row.outer_html());
+		let copy_ele = copy.get(0).unwrap();
+		assert!(copy_ele.parent().is_none());
+		assert!(!copy_ele.is(row.get(0).unwrap()));
+		let copy = row.deep_clone(CloneOptions {
+			attributes: false,
+			children: false,
+		});
+		assert_eq!(copy.outer_html(), "<tr></tr>");
+		let copy = row.deep_clone(CloneOptions {
+			attributes: true,
+			children: false,
+		});
+		assert_eq!(copy.outer_html(), r#"<tr class="row"></tr>"#);
+	}
+	#[test]
+	fn test_find_with_ancestor_filter() {
+		crate::init();
+		// the fillers keep the keys uncommon, so the indexed candidates are used
+		let doc = Doc::parse(&format!(
+			r#"<div class="post" id="a"><section><p class="item">1</p><div><p class="item">2</p></div></section><p>3</p></div><section class="post"><div><span class="item">4</span></div><p class="item">5</p></section><p class="item">6</p>{}"#,
+			"<i></i>".repeat(60)
+		));
+		let root = doc.root();
+		let selectors = [
+			"p",
+			".post p",
+			".post > p",
+			"div .item",
+			"div > .item",
+			"section div .item",
+			".post section > div > p",
+			"#a .item",
+			"section > p, div span",
+			".missing .item",
+		];
+		for index in [false, true] {
+			if index {
+				doc.build_index();
+			}
+			for selector in &selectors {
+				doc.0.use_ancestor_filter.set(false);
+				let expected = String::from(root.find(selector).text());
+				doc.0.use_ancestor_filter.set(true);
+				assert_eq!(root.find(selector).text(), expected, "{}", selector);
+			}
+		}
+		assert_eq!(root.find(".post p").text(), "1235");
+	}
+
+	#[test]
+	fn test_find_in_document_order() {
+		crate::init();
+		let doc = Doc::parse(&format!(
+			r#"<div id="a"><section><p class="rare common">deep</p></section><p class="rare" id="top">top</p><article><p class="rare">in</p><div><span class="rare">span</span></div></article></div><article><p class="rare common">last</p></article>{}"#,
+			"<i></i>".repeat(60)
+		));
+		let root = doc.root();
+		let all = root.find("*");
+		let selectors = [
+			":not(article) > .rare",
+			"* > .rare",
+			"div .rare",
+			"div > .rare",
+			"article .rare",
+			":not(article) .rare",
+			"div .common.rare",
+			"#a .rare",
+			"section + .rare",
+			"section ~ .rare, article > .rare",
+		];
+		for index in [false, true] {
+			if index {
+				doc.build_index();
+			}
+			for use_filter in [false, true] {
+				doc.0.use_ancestor_filter.set(use_filter);
+				for selector in &selectors {
+					let expected = String::from(all.filter(selector).text());
+					for stats in [None, Some(&Stats as &dyn IDocumentStats)] {
+						let mut parsed = Selector::from_str_with_stats(selector, true, stats).unwrap();
+						assert_eq!(root.find_selector(&parsed).text(), expected, "{}", selector);
+						for process in &mut parsed.process {
+							process.strategy = QueryStrategy::TopDown;
+						}
+						assert_eq!(root.find_selector(&parsed).text(), expected, "{}", selector);
+					}
+				}
+			}
+		}
+		assert_eq!(root.find(":not(article) > .rare").text(), "deeptopspan");
+	}
+
+	#[test]
+	fn test_ancestor_filter() {
+		crate::init();
+		let mut filter = AncestorFilter::new();
+		let post = [
+			AncestorFilter::slots(".", "post"),
+			AncestorFilter::slots("", "DIV"),
+		];
+		let section = [AncestorFilter::slots(".", "section")];
+		filter.insert(&post);
+		filter.insert(&section);
+		assert!(filter.may_contain(&AncestorFilter::slots("", ".post")));
+		assert!(filter.may_contain(&AncestorFilter::slots("", "DIV")));
+		filter.remove(&section);
+		assert!(!filter.may_contain(&AncestorFilter::slots("", ".section")));
+		filter.remove(&post);
+		assert!(filter.counters.iter().all(|count| *count == 0));
 	}
 }
@@ -0,0 +1,357 @@
+use super::{BoxDynElement, Elements, IAttrValue, INodeType};
+use crate::constants::ATTR_CLASS;
+use crate::utils::get_class_list;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+// the lookups of the document index, the selectors use them as the fast path when `use_cache` is set,
+// return `None` if the lookup is not indexed, then the selectors will walk the tree.
+// the mutation APIs of `Elements` call the update and remove methods, the changes made by `IElementTrait`
+// directly, e.g. `set_attribute`, `remove_child`, `set_html`, are not seen, call the methods after them.
+pub trait IIndexProvider {
+	// elements have the class name
+	fn elements_by_class_name<'b>(&self, class_name: &str) -> Option<Elements<'b>>;
+	// elements by tag name
+	fn elements_by_tag_name<'b>(&self, tag_name: &str) -> Option<Elements<'b>>;
+	// elements have the attribute, the attribute name is case insensitive
+	fn elements_by_attr_name<'b>(&self, attr_name: &str) -> Option<Elements<'b>>;
+	// the attributes of the element are changed
+	fn update_element(&self, ele: &BoxDynElement);
+	// the element and it's descendants are inserted or changed
+	fn update_subtree(&self, ele: &BoxDynElement) {
+		self.update_element(ele);
+		for ele in ele.childrens().get_ref() {
+			self.update_element(ele);
+		}
+	}
+	// the element is removed from the document
+	fn remove_element(&self, ele: &BoxDynElement);
+	// the element and it's descendants are removed
+	fn remove_subtree(&self, ele: &BoxDynElement) {
+		self.remove_element(ele);
+		for ele in ele.childrens().get_ref() {
+			self.remove_element(ele);
+		}
+	}
+}
+
+// the statistics of the document, the query planner uses them to estimate the costs of the selectors,
+// return `None` if the count is unknown
+pub trait IDocumentStats {
+	// the total elements
+	fn total_elements(&self) -> usize;
+	fn tag_count(&self, tag_name: &str) -> Option<usize>;
+	fn class_count(&self, class_name: &str) -> Option<usize>;
+	fn attr_count(&self, attr_name: &str) -> Option<usize>;
+}
+
+// the lookup is used only if the elements of the key are less than 1/8 of all elements
+const MAX_LOOKUP_RATIO: usize = 8;
+
+// elements by uuid
+type IndexBucket = HashMap<String, BoxDynElement<'static>>;
+type IndexMap = HashMap<String, IndexBucket>;
+
+// the indexed keys of an element
+struct IndexKeys {
+	tag_name: String,
+	class_list: Vec<String>,
+	attr_names: Vec<String>,
+}
+
+#[derive(Default)]
+struct IndexData {
+	classes: IndexMap,
+	tags: IndexMap,
+	attrs: IndexMap,
+	keys: HashMap<String, IndexKeys>,
+	// an element without uuid can't be indexed
+	incomplete: bool,
+}
+
+impl IndexData {
+	fn add(map: &mut IndexMap, key: &str, uuid: &str, ele: &BoxDynElement) {
+		map
+			.entry(String::from(key))
+			.or_default()
+			.insert(String::from(uuid), ele.cloned());
+	}
+	fn remove(map: &mut IndexMap, key: &str, uuid: &str) {
+		if let Some(bucket) = map.get_mut(key) {
+			bucket.remove(uuid);
+			if bucket.is_empty() {
+				map.remove(key);
+			}
+		}
+	}
+	fn unindex(&mut self, uuid: &str) {
+		if let Some(keys) = self.keys.remove(uuid) {
+			IndexData::remove(&mut self.tags, &keys.tag_name, uuid);
+			for class_name in &keys.class_list {
+				IndexData::remove(&mut self.classes, class_name, uuid);
+			}
+			for attr_name in &keys.attr_names {
+				IndexData::remove(&mut self.attrs, attr_name, uuid);
+			}
+		}
+	}
+	fn index(&mut self, ele: &BoxDynElement) {
+		if !matches!(ele.node_type(), INodeType::Element) {
+			return;
+		}
+		let uuid = match ele.uuid() {
+			Some(uuid) => uuid,
+			None => {
+				self.incomplete = true;
+				return;
+			}
+		};
+		self.unindex(uuid);
+		let tag_name = ele.tag_name().to_ascii_uppercase();
+		IndexData::add(&mut self.tags, &tag_name, uuid, ele);
+		let class_list = match ele.get_attribute(ATTR_CLASS) {
+			Some(IAttrValue::Value(names, _)) => get_class_list(&names)
+				.into_iter()
+				.map(String::from)
+				.collect::<Vec<String>>(),
+			_ => vec![],
+		};
+		for class_name in &class_list {
+			IndexData::add(&mut self.classes, class_name, uuid, ele);
+		}
+		let attr_names = ele
+			.attribute_names()
+			.into_iter()
+			.map(|name| name.to_ascii_lowercase())
+			.collect::<Vec<String>>();
+		for attr_name in &attr_names {
+			IndexData::add(&mut self.attrs, attr_name, uuid, ele);
+		}
+		self.keys.insert(
+			String::from(uuid),
+			IndexKeys {
+				tag_name,
+				class_list,
+				attr_names,
+			},
+		);
+	}
+}
+
+/// pub struct `DocumentIndex`
+/// an `IIndexProvider` of the root element's descendants, the document should build it
+/// after the tree is parsed, then return it in `IDocumentTrait::index_provider`.
+/// The mutation APIs of `Elements` keep it in sync, the lookups also re-index the elements whose attributes
+/// are changed directly, but the elements gain the keys or removed directly are missed, see `IIndexProvider`.
+/// The common keys, more than 1/8 of all elements, are not looked up, the selectors will walk the tree.
+pub struct DocumentIndex {
+	data: RefCell<IndexData>,
+}
+
+impl DocumentIndex {
+	/// pub fn `new`
+	/// index the root and it's descendants
+	pub fn new(root: &BoxDynElement) -> Self {
+		let index = DocumentIndex {
+			data: RefCell::new(Default::default()),
+		};
+		index.update_subtree(root);
+		index
+	}
+	// the count of the indexed elements of the key
+	fn count<K>(&self, kind: K, key: &str) -> Option<usize>
+	where
+		K: Fn(&IndexData) -> &IndexMap,
+	{
+		let data = self.data.borrow();
+		if data.incomplete {
+			return None;
+		}
+		Some(kind(&data).get(key).map(|bucket| bucket.len()).unwrap_or(0))
+	}
+	// get the elements of the key, the elements not pass the `check` are changed outside,
+	// so they will be indexed again
+	fn lookup<'b, K, F>(&self, kind: K, key: &str, check: F) -> Option<Elements<'b>>
+	where
+		K: Fn(&IndexData) -> &IndexMap,
+		F: Fn(&BoxDynElement) -> bool,
+	{
+		let mut changed: Vec<BoxDynElement> = vec![];
+		let mut result = Elements::new();
+		{
+			let data = self.data.borrow();
+			if data.incomplete {
+				return None;
+			}
+			if let Some(bucket) = kind(&data).get(key) {
+				// walking the tree is faster than checking and sorting too many candidates
+				if bucket.len() > data.keys.len() / MAX_LOOKUP_RATIO {
+					return None;
+				}
+				result = Elements::with_capacity(bucket.len());
+				for ele in bucket.values() {
+					if !check(ele) {
+						changed.push(ele.cloned());
+					} else {
+						result.push(ele.cloned());
+					}
+				}
+			}
+		}
+		if !changed.is_empty() {
+			let mut data = self.data.borrow_mut();
+			for ele in &changed {
+				data.index(ele);
+			}
+		}
+		Some(result)
+	}
+}
+
+impl IIndexProvider for DocumentIndex {
+	fn elements_by_class_name<'b>(&self, class_name: &str) -> Option<Elements<'b>> {
+		self.lookup(
+			|data| &data.classes,
+			class_name,
+			|ele| match ele.get_attribute(ATTR_CLASS) {
+				Some(IAttrValue::Value(names, _)) => get_class_list(&names).contains(&class_name),
+				_ => false,
+			},
+		)
+	}
+	fn elements_by_tag_name<'b>(&self, tag_name: &str) -> Option<Elements<'b>> {
+		if tag_name == "*" {
+			return None;
+		}
+		let tag_name = tag_name.to_ascii_uppercase();
+		self.lookup(
+			|data| &data.tags,
+			&tag_name,
+			|ele| ele.tag_name().eq_ignore_ascii_case(&tag_name),
+		)
+	}
+	fn elements_by_attr_name<'b>(&self, attr_name: &str) -> Option<Elements<'b>> {
+		self.lookup(
+			|data| &data.attrs,
+			&attr_name.to_ascii_lowercase(),
+			|ele| ele.has_attribute(attr_name),
+		)
+	}
+	fn update_element(&self, ele: &BoxDynElement) {
+		self.data.borrow_mut().index(ele);
+	}
+	fn remove_element(&self, ele: &BoxDynElement) {
+		if let Some(uuid) = ele.uuid() {
+			self.data.borrow_mut().unindex(uuid);
+		}
+	}
+}
+
+// the counts may include the elements changed directly, they are just estimates
+impl IDocumentStats for DocumentIndex {
+	fn total_elements(&self) -> usize {
+		self.data.borrow().keys.len()
+	}
+	fn tag_count(&self, tag_name: &str) -> Option<usize> {
+		self.count(|data| &data.tags, &tag_name.to_ascii_uppercase())
+	}
+	fn class_count(&self, class_name: &str) -> Option<usize> {
+		self.count(|data| &data.classes, class_name)
+	}
+	fn attr_count(&self, attr_name: &str) -> Option<usize> {
+		self.count(|data| &data.attrs, &attr_name.to_ascii_lowercase())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::mock::Doc;
+	use crate::selector::{QueryStrategy, Selector};
+
+	#[test]
+	fn test_index_removed() {
+		crate::init();
+		// the fillers keep the lookup keys uncommon
+		let doc = Doc::parse(&format!(
+			r#"<div>{}<p class="a">1</p><div class="w"><p class="a">2</p></div><section><p class="a">3</p></section><ul><li class="a">4</li></ul></div>"#,
+			"<i></i>".repeat(40)
+		));
+		doc.build_index();
+		let root = doc.root();
+		let index = root
+			.get(0)
+			.and_then(|ele| ele.owner_document())
+			.and_then(|doc| doc.index_provider())
+			.expect("The index must be built");
+		let lookup = |class_name: &str| {
+			let mut elements = index
+				.elements_by_class_name(class_name)
+				.expect("The class name must be looked up");
+			elements.sort_and_unique();
+			String::from(elements.text())
+		};
+		assert_eq!(lookup("a"), "1234");
+		root.find("p").eq(0).remove();
+		assert_eq!(lookup("a"), "234");
+		root.find(".w > p").unwrap("");
+		assert_eq!(lookup("a"), "234");
+		assert!(index.elements_by_class_name("w").unwrap().is_empty());
+		root.find("section").set_html(r#"<p class="a">5</p>"#);
+		assert_eq!(lookup("a"), "254");
+		root.find("ul").set_text("");
+		assert_eq!(lookup("a"), "25");
+		root.find("section").detach();
+		assert_eq!(lookup("a"), "2");
+		// removed directly, the index is not told
+		let p = root.find("p").get(0).unwrap().cloned();
+		p.parent().unwrap().remove_child(p.cloned());
+		assert_eq!(lookup("a"), "2");
+		index.remove_subtree(&p);
+		assert_eq!(lookup("a"), "");
+	}
+
+	#[test]
+	fn test_find_with_statistics() {
+		crate::init();
+		let doc = Doc::parse(&format!(
+			r#"<div id="box"><article><p class="common">1</p><div><span class="common rare">2</span></div></article>{}<section><p class="common">3</p><article><b class="common">4</b></article></section></div><article><p class="common rare">5</p></article>"#,
+			r#"<div class="common"><i></i></div>"#.repeat(30)
+		));
+		doc.build_index();
+		let root = doc.root();
+		let stats = root
+			.get(0)
+			.and_then(|ele| ele.owner_document())
+			.and_then(|doc| doc.statistics())
+			.expect("The statistics must be provided by the index");
+		// the statistics show the tag is rarer than the class, so search from left to right
+		let strategy = |selector: &Selector| selector.process[0].strategy;
+		let planned = Selector::from_str_with_stats("article .common", true, Some(&*stats)).unwrap();
+		assert_eq!(strategy(&planned), QueryStrategy::TopDown);
+		let unplanned = Selector::from_str("article .common", true).unwrap();
+		assert_eq!(strategy(&unplanned), QueryStrategy::RightToLeft);
+		let selectors = [
+			"article .common",
+			"article > .common",
+			"div .common.rare",
+			"section article .common",
+			"article p, div .rare",
+			".common.rare",
+		];
+		let contexts = [root.cloned(), root.find("#box"), root.find("section")];
+		for context in &contexts {
+			for selector in &selectors {
+				let expected = context.find_selector(&Selector::from_str(selector, true).unwrap());
+				assert_eq!(
+					context.find(selector).text(),
+					expected.text(),
+					"{}",
+					selector
+				);
+			}
+		}
+		assert_eq!(root.find("article .common").text(), "1245");
+		assert_eq!(root.find("#box").find("article .common").text(), "124");
+		assert_eq!(root.find("section").find("article .common").text(), "4");
+	}
+}
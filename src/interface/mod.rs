@@ -3,13 +3,18 @@ mod node;
 pub use node::{BoxDynNode, IEnumTyped, INodeTrait, INodeType};
 // element trait
 mod element;
-pub use element::{BoxDynElement, IAttrValue, IElementTrait, InsertPosition, MaybeElement};
+pub use element::{
+	BoxDynElement, CloneOptions, IAttrValue, IElementTrait, InsertPosition, MaybeElement,
+};
 // text trait
 mod text;
 pub use text::{BoxDynText, ITextTrait};
 // document trait
 mod document;
 pub use document::{IDocumentTrait, IErrorHandle, MaybeDoc};
+// document index
+mod index;
+pub use index::{DocumentIndex, IDocumentStats, IIndexProvider};
 // uncare
 mod uncare;
 pub use uncare::{BoxDynUncareNode, IUncareNodeTrait};
@@ -19,3 +24,4 @@ pub use texts::Texts;
 // elements
 mod elements;
 pub use elements::Elements;
+pub(crate) use elements::FilterType;
@@ -69,6 +69,7 @@ pub trait INodeTrait {
 	fn set_text(&mut self, content: &str);
 	// set html
 	fn set_html(&mut self, content: &str);
-	// ele index
+	// the index in the parent's child nodes, the text and comment nodes are counted too,
+	// the sibling methods and the xpath document order rely on it
 	fn index(&self) -> usize;
 }
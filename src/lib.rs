@@ -8,10 +8,17 @@ pub mod selector;
 pub mod interface;
 // export error
 pub mod error;
+// style declarations
+pub mod style;
+// xpath 1.0
+pub mod xpath;
 // utils for crate
 pub mod utils;
 // constants
 pub(crate) mod constants;
+// the mock document for tests
+#[cfg(test)]
+pub(crate) mod mock;
 
 // export init, must execute `init()` first
 pub fn init() {
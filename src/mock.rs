@@ -0,0 +1,564 @@
+// a mutable document for the tests, the html is parsed by a simple parser:
+// no entities, no implied end tags, `style` and `script` contents are raw texts
+use crate::interface::{
+	BoxDynElement, BoxDynNode, BoxDynText, DocumentIndex, Elements, IAttrValue, IDocumentStats,
+	IDocumentTrait, IElementTrait, IEnumTyped, IErrorHandle, IIndexProvider, INodeTrait, INodeType,
+	ITextTrait, IUncareNodeTrait, InsertPosition, MaybeDoc, MaybeElement,
+};
+use crate::utils::to_static_str;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const VOID_TAGS: [&str; 6] = ["br", "hr", "img", "input", "link", "meta"];
+const RAW_TEXT_TAGS: [&str; 2] = ["script", "style"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+	Document,
+	Fragment,
+	Element,
+	Text,
+	Comment,
+}
+
+pub(crate) struct NodeData {
+	uuid: String,
+	kind: Kind,
+	tag_name: String,
+	content: RefCell<String>,
+	attrs: RefCell<Vec<(String, Option<String>)>>,
+	children: RefCell<Vec<Rc<NodeData>>>,
+	parent: RefCell<Weak<NodeData>>,
+	doc: Weak<DocData>,
+}
+
+#[derive(Default)]
+pub(crate) struct DocData {
+	root: RefCell<Option<Rc<NodeData>>>,
+	index: RefCell<Option<Rc<DocumentIndex>>>,
+	// insert the text and comment nodes by `insert_adjacent_node`
+	pub(crate) node_insertable: Cell<bool>,
+	pub(crate) use_ancestor_filter: Cell<bool>,
+	// the class and tag selectors use the `get_elements_by_*` methods without index
+	pub(crate) lookup_indexed: Cell<bool>,
+	// the messages of the errors triggered by the methods
+	pub(crate) errors: Rc<RefCell<Vec<String>>>,
+}
+
+#[derive(Clone)]
+pub(crate) struct Node(Rc<NodeData>);
+
+#[derive(Clone)]
+pub(crate) struct Doc(pub(crate) Rc<DocData>);
+
+static UUID: AtomicUsize = AtomicUsize::new(0);
+
+fn create_node(doc: &Rc<DocData>, kind: Kind, tag_name: &str, content: &str) -> Rc<NodeData> {
+	Rc::new(NodeData {
+		uuid: format!("mock-{}", UUID.fetch_add(1, Ordering::Relaxed)),
+		kind,
+		tag_name: tag_name.to_ascii_uppercase(),
+		content: RefCell::new(String::from(content)),
+		attrs: RefCell::new(Vec::new()),
+		children: RefCell::new(Vec::new()),
+		parent: RefCell::new(Weak::new()),
+		doc: Rc::downgrade(doc),
+	})
+}
+
+// the node data of a boxed node, the nodes must be created by the mock document
+fn node_data(node: &dyn INodeTrait) -> Rc<NodeData> {
+	let node = node
+		.clone_node()
+		.to_node()
+		.downcast::<Node>()
+		.expect("The node must be a mock node");
+	node.0
+}
+
+fn detach(node: &Rc<NodeData>) {
+	let parent = node.parent.borrow().upgrade();
+	if let Some(parent) = parent {
+		parent
+			.children
+			.borrow_mut()
+			.retain(|child| !Rc::ptr_eq(child, node));
+	}
+	*node.parent.borrow_mut() = Weak::new();
+}
+
+fn append_child(parent: &Rc<NodeData>, child: Rc<NodeData>) {
+	*child.parent.borrow_mut() = Rc::downgrade(parent);
+	parent.children.borrow_mut().push(child);
+}
+
+// insert the node at the position of the target, the node is moved
+fn insert_at(target: &Rc<NodeData>, position: &InsertPosition, node: Rc<NodeData>) {
+	if Rc::ptr_eq(target, &node) {
+		return;
+	}
+	detach(&node);
+	let (parent, index) = match position {
+		InsertPosition::AfterBegin => (Rc::clone(target), 0),
+		InsertPosition::BeforeEnd => (Rc::clone(target), target.children.borrow().len()),
+		InsertPosition::BeforeBegin | InsertPosition::AfterEnd => {
+			let parent = target
+				.parent
+				.borrow()
+				.upgrade()
+				.expect("The target must have a parent");
+			let index = child_index(&parent, target);
+			let index = if *position == InsertPosition::AfterEnd {
+				index + 1
+			} else {
+				index
+			};
+			(parent, index)
+		}
+	};
+	*node.parent.borrow_mut() = Rc::downgrade(&parent);
+	parent.children.borrow_mut().insert(index, node);
+}
+
+fn child_index(parent: &Rc<NodeData>, child: &Rc<NodeData>) -> usize {
+	parent
+		.children
+		.borrow()
+		.iter()
+		.position(|cur| Rc::ptr_eq(cur, child))
+		.expect("The child must be in the parent")
+}
+
+fn set_children(parent: &Rc<NodeData>, children: Vec<Rc<NodeData>>) {
+	let old = parent.children.replace(Vec::new());
+	for child in old {
+		*child.parent.borrow_mut() = Weak::new();
+	}
+	for child in children {
+		append_child(parent, child);
+	}
+}
+
+// parse the html to the nodes
+fn parse(doc: &Rc<DocData>, html: &str) -> Vec<Rc<NodeData>> {
+	let fragment = create_node(doc, Kind::Fragment, "", "");
+	let mut stack: Vec<Rc<NodeData>> = vec![Rc::clone(&fragment)];
+	let mut rest = html;
+	while !rest.is_empty() {
+		let parent = Rc::clone(stack.last().expect("The stack has the fragment"));
+		if let Some(comment) = rest.strip_prefix("<!--") {
+			let end = comment.find("-->").unwrap_or(comment.len());
+			append_child(
+				&parent,
+				create_node(doc, Kind::Comment, "", &comment[..end]),
+			);
+			rest = &comment[(end + 3).min(comment.len())..];
+		} else if let Some(end_tag) = rest.strip_prefix("</") {
+			let end = end_tag.find('>').unwrap_or(end_tag.len());
+			let tag_name = end_tag[..end].trim();
+			if let Some(index) = stack.iter().rposition(|node| {
+				node.kind == Kind::Element && node.tag_name.eq_ignore_ascii_case(tag_name)
+			}) {
+				stack.truncate(index);
+			}
+			rest = &end_tag[(end + 1).min(end_tag.len())..];
+		} else if rest.starts_with('<') && rest[1..].starts_with(|ch: char| ch.is_ascii_alphabetic()) {
+			let (ele, self_closed, next) = parse_start_tag(doc, &rest[1..]);
+			rest = next;
+			append_child(&parent, Rc::clone(&ele));
+			let tag_name = ele.tag_name.to_ascii_lowercase();
+			if RAW_TEXT_TAGS.contains(&tag_name.as_str()) {
+				let end_tag = format!("</{}", tag_name);
+				let end = rest
+					.to_ascii_lowercase()
+					.find(&end_tag)
+					.unwrap_or(rest.len());
+				if end > 0 {
+					append_child(&ele, create_node(doc, Kind::Text, "", &rest[..end]));
+				}
+				rest = &rest[end..];
+			} else if !self_closed && !VOID_TAGS.contains(&tag_name.as_str()) {
+				stack.push(ele);
+			}
+		} else {
+			let end = rest[1..].find('<').map(|end| end + 1).unwrap_or(rest.len());
+			append_child(&parent, create_node(doc, Kind::Text, "", &rest[..end]));
+			rest = &rest[end..];
+		}
+	}
+	let nodes = fragment.children.replace(Vec::new());
+	for node in &nodes {
+		*node.parent.borrow_mut() = Weak::new();
+	}
+	nodes
+}
+
+// parse the start tag after '<', return the element, whether it's self closed and the rest html
+fn parse_start_tag<'h>(doc: &Rc<DocData>, html: &'h str) -> (Rc<NodeData>, bool, &'h str) {
+	let is_name_end = |ch: char| ch.is_whitespace() || ch == '/' || ch == '>';
+	let end = html.find(is_name_end).unwrap_or(html.len());
+	let ele = create_node(doc, Kind::Element, &html[..end], "");
+	let mut rest = &html[end..];
+	loop {
+		rest = rest.trim_start();
+		if let Some(next) = rest.strip_prefix("/>") {
+			return (ele, true, next);
+		}
+		if let Some(next) = rest.strip_prefix('>') {
+			return (ele, false, next);
+		}
+		if rest.is_empty() {
+			return (ele, false, rest);
+		}
+		if let Some(next) = rest.strip_prefix('/') {
+			rest = next;
+			continue;
+		}
+		let end = rest
+			.find(|ch: char| is_name_end(ch) || ch == '=')
+			.unwrap_or(rest.len());
+		let name = String::from(&rest[..end]);
+		rest = rest[end..].trim_start();
+		let mut value = None;
+		if let Some(next) = rest.strip_prefix('=') {
+			let next = next.trim_start();
+			let (content, next) = match next.chars().next() {
+				Some(quote) if quote == '"' || quote == '\'' => {
+					let end = next[1..]
+						.find(quote)
+						.map(|end| end + 1)
+						.unwrap_or(next.len());
+					(&next[1..end], &next[(end + 1).min(next.len())..])
+				}
+				_ => {
+					let end = next.find(is_name_end).unwrap_or(next.len());
+					(&next[..end], &next[end..])
+				}
+			};
+			value = Some(String::from(content));
+			rest = next;
+		}
+		ele.attrs.borrow_mut().push((name, value));
+	}
+}
+
+fn serialize(node: &NodeData, outer: bool, html: &mut String) {
+	match node.kind {
+		Kind::Text => html.push_str(&node.content.borrow()),
+		Kind::Comment => {
+			html.push_str("<!--");
+			html.push_str(&node.content.borrow());
+			html.push_str("-->");
+		}
+		_ => {
+			let tag_name = node.tag_name.to_ascii_lowercase();
+			let is_element = outer && node.kind == Kind::Element;
+			if is_element {
+				html.push('<');
+				html.push_str(&tag_name);
+				for (name, value) in node.attrs.borrow().iter() {
+					html.push(' ');
+					html.push_str(name);
+					if let Some(value) = value {
+						html.push_str("=\"");
+						html.push_str(&value.replace('"', "&quot;"));
+						html.push('"');
+					}
+				}
+				html.push('>');
+				if VOID_TAGS.contains(&tag_name.as_str()) {
+					return;
+				}
+			}
+			for child in node.children.borrow().iter() {
+				serialize(child, true, html);
+			}
+			if is_element {
+				html.push_str("</");
+				html.push_str(&tag_name);
+				html.push('>');
+			}
+		}
+	}
+}
+
+fn text_content(node: &NodeData, content: &mut String) {
+	match node.kind {
+		Kind::Text => content.push_str(&node.content.borrow()),
+		Kind::Comment => {}
+		_ => {
+			for child in node.children.borrow().iter() {
+				text_content(child, content);
+			}
+		}
+	}
+}
+
+impl Doc {
+	// parse the html as the document's child nodes
+	pub(crate) fn parse(html: &str) -> Self {
+		let data = Rc::new(DocData::default());
+		let root = create_node(&data, Kind::Document, "#document", "");
+		set_children(&root, parse(&data, html));
+		*data.root.borrow_mut() = Some(root);
+		Doc(data)
+	}
+	// the document node
+	pub(crate) fn root(&self) -> Elements<'static> {
+		let root = self
+			.0
+			.root
+			.borrow()
+			.clone()
+			.expect("The root must be parsed");
+		Elements::with_nodes(vec![Box::new(Node(root))])
+	}
+	// build the document index, the selectors will use it
+	pub(crate) fn build_index(&self) {
+		let root = self.root();
+		let root = root.get(0).expect("The root must be parsed");
+		*self.0.index.borrow_mut() = Some(Rc::new(DocumentIndex::new(root)));
+	}
+}
+
+impl INodeTrait for Node {
+	fn to_node(self: Box<Self>) -> Box<dyn Any> {
+		self
+	}
+	fn clone_node<'b>(&self) -> BoxDynNode<'b> {
+		Box::new(self.clone())
+	}
+	fn typed<'b>(self: Box<Self>) -> IEnumTyped<'b> {
+		match self.0.kind {
+			Kind::Text => IEnumTyped::Text(self),
+			Kind::Comment => IEnumTyped::UncareNode(self),
+			_ => IEnumTyped::Element(self),
+		}
+	}
+	fn node_type(&self) -> INodeType {
+		match self.0.kind {
+			Kind::Document => INodeType::Document,
+			Kind::Fragment => INodeType::DocumentFragement,
+			Kind::Element => INodeType::Element,
+			Kind::Text => INodeType::Text,
+			Kind::Comment => INodeType::Comment,
+		}
+	}
+	fn parent<'b>(&self) -> MaybeElement<'b> {
+		let parent = self.0.parent.borrow().upgrade()?;
+		Some(Box::new(Node(parent)))
+	}
+	fn uuid(&self) -> Option<&str> {
+		Some(&self.0.uuid)
+	}
+	fn owner_document(&self) -> MaybeDoc<'_> {
+		let doc = self.0.doc.upgrade()?;
+		Some(Box::new(Doc(doc)))
+	}
+	fn text_content(&self) -> &str {
+		if self.0.kind == Kind::Comment {
+			return to_static_str(self.0.content.borrow().clone());
+		}
+		let mut content = String::new();
+		text_content(&self.0, &mut content);
+		to_static_str(content)
+	}
+	fn set_text(&mut self, content: &str) {
+		match self.0.kind {
+			Kind::Text | Kind::Comment => *self.0.content.borrow_mut() = String::from(content),
+			_ => {
+				let doc = self.0.doc.upgrade().expect("The document must exist");
+				let text = create_node(&doc, Kind::Text, "", content);
+				set_children(
+					&self.0,
+					if content.is_empty() {
+						vec![]
+					} else {
+						vec![text]
+					},
+				);
+			}
+		}
+	}
+	fn set_html(&mut self, content: &str) {
+		let doc = self.0.doc.upgrade().expect("The document must exist");
+		set_children(&self.0, parse(&doc, content));
+	}
+	fn index(&self) -> usize {
+		match self.0.parent.borrow().upgrade() {
+			Some(parent) => child_index(&parent, &self.0),
+			None => 0,
+		}
+	}
+}
+
+impl IElementTrait for Node {
+	fn tag_name(&self) -> &str {
+		&self.0.tag_name
+	}
+	fn child_nodes_length(&self) -> usize {
+		self.0.children.borrow().len()
+	}
+	fn child_nodes_item<'b>(&self, index: usize) -> Option<BoxDynNode<'b>> {
+		let child = self.0.children.borrow().get(index)?.clone();
+		Some(Box::new(Node(child)))
+	}
+	fn get_attribute(&self, name: &str) -> Option<IAttrValue> {
+		self
+			.0
+			.attrs
+			.borrow()
+			.iter()
+			.find(|(key, _)| key.eq_ignore_ascii_case(name))
+			.map(|(_, value)| match value {
+				Some(value) => IAttrValue::Value(value.clone(), Some('"')),
+				None => IAttrValue::True,
+			})
+	}
+	fn set_attribute(&mut self, name: &str, value: Option<&str>) {
+		let mut attrs = self.0.attrs.borrow_mut();
+		let value = value.map(String::from);
+		match attrs
+			.iter_mut()
+			.find(|(key, _)| key.eq_ignore_ascii_case(name))
+		{
+			Some(attr) => attr.1 = value,
+			None => attrs.push((String::from(name), value)),
+		}
+	}
+	fn remove_attribute(&mut self, name: &str) {
+		self
+			.0
+			.attrs
+			.borrow_mut()
+			.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+	}
+	fn inner_html(&self) -> &str {
+		let mut html = String::new();
+		serialize(&self.0, false, &mut html);
+		to_static_str(html)
+	}
+	fn outer_html(&self) -> &str {
+		let mut html = String::new();
+		serialize(&self.0, true, &mut html);
+		to_static_str(html)
+	}
+	fn insert_adjacent(&mut self, position: &InsertPosition, ele: &BoxDynElement) {
+		insert_at(&self.0, position, node_data(&**ele));
+	}
+	fn remove_child(&mut self, ele: BoxDynElement) {
+		let child = node_data(&*ele);
+		if child
+			.parent
+			.borrow()
+			.upgrade()
+			.map(|parent| Rc::ptr_eq(&parent, &self.0))
+			== Some(true)
+		{
+			detach(&child);
+		}
+	}
+	fn can_insert_node(&self, node: &BoxDynNode) -> bool {
+		node.node_type().is_element()
+			|| self.0.doc.upgrade().map(|doc| doc.node_insertable.get()) == Some(true)
+	}
+	fn insert_adjacent_node(
+		&mut self,
+		position: &InsertPosition,
+		node: &BoxDynNode,
+	) -> Result<(), Box<dyn Error>> {
+		if !self.can_insert_node(node) {
+			return Err(format!("Can't insert the node type '{:?}'", node.node_type()).into());
+		}
+		insert_at(&self.0, position, node_data(&**node));
+		Ok(())
+	}
+}
+
+impl ITextTrait for Node {
+	fn remove(self: Box<Self>) {
+		detach(&self.0);
+	}
+	fn append_text(&mut self, content: &str) {
+		self.0.content.borrow_mut().push_str(content);
+	}
+	fn prepend_text(&mut self, content: &str) {
+		self.0.content.borrow_mut().insert_str(0, content);
+	}
+}
+
+impl IUncareNodeTrait for Node {}
+
+impl IDocumentTrait for Doc {
+	fn get_element_by_id<'b>(&self, id: &str) -> Option<BoxDynElement<'b>> {
+		let root = self.root();
+		let ele = root
+			.get(0)?
+			.childrens()
+			.into_iter()
+			.find(|ele| ele.get_attribute("id").map(|value| value.is_str(id)) == Some(true));
+		ele
+	}
+	fn create_element<'b>(&self, tag_name: &str) -> Option<BoxDynElement<'b>> {
+		Some(Box::new(Node(create_node(
+			&self.0,
+			Kind::Element,
+			tag_name,
+			"",
+		))))
+	}
+	fn create_text_node<'b>(&self, content: &str) -> Option<BoxDynText<'b>> {
+		Some(Box::new(Node(create_node(
+			&self.0,
+			Kind::Text,
+			"",
+			content,
+		))))
+	}
+	fn create_comment<'b>(&self, content: &str) -> Option<BoxDynNode<'b>> {
+		Some(Box::new(Node(create_node(
+			&self.0,
+			Kind::Comment,
+			"",
+			content,
+		))))
+	}
+	fn create_document_fragment<'b>(&self) -> Option<BoxDynElement<'b>> {
+		Some(Box::new(Node(create_node(&self.0, Kind::Fragment, "", ""))))
+	}
+	fn source_code(&self) -> &'static str {
+		""
+	}
+	fn get_root_node<'b>(&self) -> BoxDynNode<'b> {
+		let root = self
+			.0
+			.root
+			.borrow()
+			.clone()
+			.expect("The root must be parsed");
+		Box::new(Node(root))
+	}
+	fn index_provider(&self) -> Option<Rc<dyn IIndexProvider>> {
+		let index = self.0.index.borrow().clone()?;
+		Some(index)
+	}
+	fn statistics(&self) -> Option<Rc<dyn IDocumentStats>> {
+		let index = self.0.index.borrow().clone()?;
+		Some(index)
+	}
+	fn use_ancestor_filter(&self) -> bool {
+		self.0.use_ancestor_filter.get()
+	}
+	fn is_lookup_indexed(&self) -> bool {
+		self.0.lookup_indexed.get()
+	}
+	fn onerror(&self) -> Option<Rc<IErrorHandle>> {
+		let errors = Rc::clone(&self.0.errors);
+		Some(Rc::new(Box::new(move |error: Box<dyn Error>| {
+			errors.borrow_mut().push(error.to_string());
+		})))
+	}
+}
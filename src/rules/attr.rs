@@ -1,10 +1,38 @@
 #![allow(clippy::or_fun_call)]
 
-use crate::constants::{NAME_SELECTOR_ATTR, PRIORITY_ATTR_SELECTOR};
-use crate::interface::{BoxDynElement, IAttrValue};
+use crate::constants::{NAME_SELECTOR_ATTR, NAME_SELECTOR_ATTR_PREFIX, PRIORITY_ATTR_SELECTOR};
+use crate::interface::{BoxDynElement, Elements, IAttrValue};
 use crate::selector::rule::{Matcher, MatcherData};
 use crate::selector::rule::{Rule, RuleDefItem, RuleItem};
+use crate::utils::unescape_css;
+use std::rc::Rc;
+
+pub(crate) type AttrValueHandle = Box<dyn Fn(&Option<IAttrValue>) -> bool>;
 pub fn init(rules: &mut Vec<RuleItem>) {
+	// has any attribute which name starts with the prefix, e.g. `[^data-]`,
+	// it checks `attribute_names` of each element, see its cost
+	// must add before the attribute rule, otherwise the '[' will be matched by it
+	let rule = RuleDefItem(
+		NAME_SELECTOR_ATTR_PREFIX,
+		r##"[^{attr_key}{spaces}]"##,
+		PRIORITY_ATTR_SELECTOR,
+		vec![("attr_key", 0)],
+		Box::new(|data: MatcherData| {
+			let prefix = Rule::param(&data, "attr_key")
+				.expect("The attribute prefix selector's key is not correct")
+				.to_ascii_lowercase();
+			Matcher {
+				one_handle: Some(Box::new(move |ele: &BoxDynElement, _| {
+					ele
+						.attribute_names()
+						.iter()
+						.any(|name| name.to_ascii_lowercase().starts_with(&prefix))
+				})),
+				..Default::default()
+			}
+		}),
+	);
+	rules.push(rule.into());
 	let rule = RuleDefItem(
 		NAME_SELECTOR_ATTR,
 		r##"[{spaces}{attr_key}{spaces}{regexp#(?:([*^$~|!]?)=\s*(?:'((?:\\?+.)*?)'|([^\s\]'"<>/=`]+)|"((?:\\?+.)*?)"))?#}{spaces}]"##,
@@ -15,76 +43,118 @@ pub fn init(rules: &mut Vec<RuleItem>) {
 				Rule::param(&data, "attr_key").expect("The attribute selector's key is not correct");
 			let attr_value = Rule::param(&data, ("regexp", 0, "2"))
 				.or_else(|| Rule::param(&data, ("regexp", 0, "3")))
-				.or_else(|| Rule::param(&data, ("regexp", 0, "4")));
-			let handle: Box<dyn Fn(&Option<IAttrValue>) -> bool> = if let Some(attr_value) = attr_value {
-				if attr_value.is_empty() {
-					// empty attribute value
-					Box::new(|_val: &Option<IAttrValue>| false)
-				} else {
-					match Rule::param(&data, ("regexp", 0, "1")).unwrap_or("") {
-						// begin with value
-						"^" => Box::new(move |val: &Option<IAttrValue>| match val {
-							Some(IAttrValue::Value(v, _)) => v.starts_with(attr_value),
-							_ => false,
-						}),
-						// end with value
-						"$" => Box::new(move |val: &Option<IAttrValue>| match val {
-							Some(IAttrValue::Value(v, _)) => v.ends_with(attr_value),
-							_ => false,
-						}),
-						// contains value
-						"*" => Box::new(move |val: &Option<IAttrValue>| match val {
-							Some(IAttrValue::Value(v, _)) => v.contains(attr_value),
-							_ => false,
-						}),
-						// either equal to value or start with `value` and followed `-`
-						"|" => Box::new(move |val: &Option<IAttrValue>| match val {
-							Some(IAttrValue::Value(v, _)) => {
-								if v == attr_value {
-									return true;
-								}
-								let attr_value: String = format!("{}-", attr_value);
-								v.starts_with(&attr_value)
-							}
-							_ => false,
-						}),
-						// in a value list that splitted by whitespaces
-						"~" => Box::new(move |val: &Option<IAttrValue>| match val {
-							Some(IAttrValue::Value(v, _)) => {
-								let split_v = v.split_ascii_whitespace();
-								for v in split_v {
-									if v == attr_value {
-										return true;
-									}
-								}
-								false
-							}
-							_ => false,
-						}),
-						// has a attribute and who's value not equal to setted value
-						"!" => Box::new(move |val: &Option<IAttrValue>| match val {
-							Some(IAttrValue::Value(v, _)) => attr_value != v,
-							_ => false,
-						}),
-						// equal to value
-						_ => Box::new(move |val: &Option<IAttrValue>| match val {
-							Some(IAttrValue::Value(v, _)) => v == attr_value,
-							_ => false,
-						}),
-					}
-				}
-			} else {
-				// has the attribute name
-				Box::new(|val: &Option<IAttrValue>| val.is_some())
-			};
+				.or_else(|| Rule::param(&data, ("regexp", 0, "4")))
+				.map(unescape_css);
+			let handle: Rc<AttrValueHandle> = Rc::new(make_attr_value_handle(
+				Rule::param(&data, ("regexp", 0, "1")).unwrap_or(""),
+				attr_value,
+			));
+			let cache_value_handle = Rc::clone(&handle);
 			Matcher {
 				one_handle: Some(Box::new(move |ele: &BoxDynElement, _| {
 					let val = ele.get_attribute(attr_key);
 					handle(&val)
 				})),
+				// filter the elements have the attribute in the document index
+				cache_handle: Some(Box::new(move |eles: &Elements| {
+					let index = eles.get(0)?.owner_document()?.index_provider()?;
+					let elements = index.elements_by_attr_name(attr_key)?;
+					Some(elements.filter_by_handle(|_, ele| cache_value_handle(&ele.get_attribute(attr_key))))
+				})),
+				estimate_handle: Some(Box::new(move |stats| stats.attr_count(attr_key))),
 				..Default::default()
 			}
 		}),
 	);
 	rules.push(rule.into());
 }
+
+// the attribute value checker of the operator, e.g. `^`, `$`, check if the attribute exists when the value is `None`
+pub(crate) fn make_attr_value_handle<T>(operator: &str, attr_value: Option<T>) -> AttrValueHandle
+where
+	T: AsRef<str> + 'static,
+{
+	if let Some(attr_value) = attr_value {
+		if attr_value.as_ref().is_empty() {
+			// empty attribute value
+			Box::new(|_val: &Option<IAttrValue>| false)
+		} else {
+			match operator {
+				// begin with value
+				"^" => Box::new(move |val: &Option<IAttrValue>| match val {
+					Some(IAttrValue::Value(v, _)) => v.starts_with(attr_value.as_ref()),
+					_ => false,
+				}),
+				// end with value
+				"$" => Box::new(move |val: &Option<IAttrValue>| match val {
+					Some(IAttrValue::Value(v, _)) => v.ends_with(attr_value.as_ref()),
+					_ => false,
+				}),
+				// contains value
+				"*" => Box::new(move |val: &Option<IAttrValue>| match val {
+					Some(IAttrValue::Value(v, _)) => v.contains(attr_value.as_ref()),
+					_ => false,
+				}),
+				// either equal to value or start with `value` and followed `-`
+				"|" => Box::new(move |val: &Option<IAttrValue>| match val {
+					Some(IAttrValue::Value(v, _)) => {
+						if v == attr_value.as_ref() {
+							return true;
+						}
+						let attr_value: String = format!("{}-", attr_value.as_ref());
+						v.starts_with(&attr_value)
+					}
+					_ => false,
+				}),
+				// in a value list that splitted by whitespaces
+				"~" => Box::new(move |val: &Option<IAttrValue>| match val {
+					Some(IAttrValue::Value(v, _)) => {
+						let split_v = v.split_ascii_whitespace();
+						for v in split_v {
+							if v == attr_value.as_ref() {
+								return true;
+							}
+						}
+						false
+					}
+					_ => false,
+				}),
+				// has a attribute and who's value not equal to setted value
+				"!" => Box::new(move |val: &Option<IAttrValue>| match val {
+					Some(IAttrValue::Value(v, _)) => attr_value.as_ref() != v,
+					_ => false,
+				}),
+				// equal to value
+				_ => Box::new(move |val: &Option<IAttrValue>| match val {
+					Some(IAttrValue::Value(v, _)) => v == attr_value.as_ref(),
+					_ => false,
+				}),
+			}
+		}
+	} else {
+		// has the attribute name
+		Box::new(|val: &Option<IAttrValue>| val.is_some())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::mock::Doc;
+
+	#[test]
+	fn test_attr_escaped_value() {
+		crate::init();
+		let doc = Doc::parse(r#"<p title='a"b'>1</p><p title="a\b">2</p><p title="x">3</p>"#);
+		let root = doc.root();
+		for (selector, expected) in [
+			(r#"[title="a\"b"]"#, "1"),
+			(r#"[title='a"b']"#, "1"),
+			(r#"[title="a\\b"]"#, "2"),
+			(r#"[title^="\61 "]"#, "12"),
+			("[title=x]", "3"),
+			(r#"[title="\x"]"#, "3"),
+		] {
+			assert_eq!(root.find(selector).text(), expected, "{}", selector);
+		}
+	}
+}
@@ -1,9 +1,17 @@
 use crate::constants::{NAME_SELECTOR_CLASS, PRIORITY_CLASS_SELECTOR};
-use crate::interface::{BoxDynElement, IAttrValue};
+use crate::interface::{BoxDynElement, Elements, IAttrValue};
 use crate::selector::rule::{Matcher, MatcherData};
 use crate::selector::rule::{Rule, RuleDefItem, RuleItem};
 use crate::utils::get_class_list;
 
+// the class attribute value contains the class name, also used by the stream matcher
+pub(crate) fn is_class_matched(class_value: &Option<IAttrValue>, class_name: &str) -> bool {
+	match class_value {
+		Some(IAttrValue::Value(names, _)) => get_class_list(names).contains(&class_name),
+		_ => false,
+	}
+}
+
 pub fn init(rules: &mut Vec<RuleItem>) {
 	let rule = RuleDefItem(
 		NAME_SELECTOR_CLASS,
@@ -16,12 +24,21 @@ pub fn init(rules: &mut Vec<RuleItem>) {
 			// matcher
 			Matcher {
 				one_handle: Some(Box::new(move |ele: &BoxDynElement, _| -> bool {
-					if let Some(IAttrValue::Value(names, _)) = ele.get_attribute("class") {
-						let class_list = get_class_list(&names);
-						return class_list.contains(&class_name);
+					is_class_matched(&ele.get_attribute("class"), class_name)
+				})),
+				// use the indexed document lookup
+				cache_handle: Some(Box::new(move |eles: &Elements| {
+					let doc = eles.get(0)?.owner_document()?;
+					if let Some(index) = doc.index_provider() {
+						index.elements_by_class_name(class_name)
+					} else if doc.is_lookup_indexed() {
+						Some(doc.get_elements_by_class_name(class_name))
+					} else {
+						None
 					}
-					false
 				})),
+				estimate_handle: Some(Box::new(move |stats| stats.class_count(class_name))),
+				ancestor_key: Some(format!(".{}", class_name)),
 				..Default::default()
 			}
 		}),
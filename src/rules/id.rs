@@ -42,6 +42,8 @@ pub fn init(rules: &mut Vec<RuleItem>) {
 						}
 						result
 					})),
+					// the id is unique
+					estimate_handle: Some(Box::new(|_| Some(1))),
 					..Default::default()
 				}
 			}),
@@ -1,7 +1,12 @@
 use crate::constants::{NAME_SELECTOR_NAME, PRIORITY_NAME_SELECTOR};
-use crate::interface::BoxDynElement;
+use crate::interface::{BoxDynElement, Elements};
 use crate::selector::rule::{Matcher, MatcherData, Rule, RuleDefItem, RuleItem};
 
+// the element's tag name is the uppercase name, also used by the stream matcher
+pub(crate) fn is_tag_name_matched(tag_name: &str, name: &str) -> bool {
+	tag_name == name
+}
+
 pub fn init(rules: &mut Vec<RuleItem>) {
 	let rule = RuleDefItem(
 		NAME_SELECTOR_NAME,
@@ -9,13 +14,26 @@ pub fn init(rules: &mut Vec<RuleItem>) {
 		PRIORITY_NAME_SELECTOR,
 		vec![("identity", 0)],
 		Box::new(|data: MatcherData| {
-			let name = Rule::param(&data, "identity")
-				.expect("The 'name' selector must have a tag name")
-				.to_ascii_uppercase();
+			let tag_name =
+				Rule::param(&data, "identity").expect("The 'name' selector must have a tag name");
+			let name = tag_name.to_ascii_uppercase();
 			Matcher {
 				one_handle: Some(Box::new(move |ele: &BoxDynElement, _| {
-					return ele.tag_name() == name;
+					is_tag_name_matched(ele.tag_name(), &name)
+				})),
+				// use the indexed document lookup
+				cache_handle: Some(Box::new(move |eles: &Elements| {
+					let doc = eles.get(0)?.owner_document()?;
+					if let Some(index) = doc.index_provider() {
+						index.elements_by_tag_name(tag_name)
+					} else if doc.is_lookup_indexed() {
+						Some(doc.get_elements_by_tag_name(tag_name))
+					} else {
+						None
+					}
 				})),
+				estimate_handle: Some(Box::new(move |stats| stats.tag_count(tag_name))),
+				ancestor_key: Some(tag_name.to_ascii_uppercase()),
 				..Default::default()
 			}
 		}),
@@ -1,7 +1,8 @@
 use crate::constants::{DEF_NODES_LEN, PRIORITY_PSEUDO_SELECTOR};
-use crate::interface::{BoxDynElement, Elements, INodeType};
+use crate::interface::{BoxDynElement, Elements, FilterType, INodeType};
 use crate::selector::pattern::Nth;
-use crate::selector::rule::{Matcher, MatcherData, Rule, RuleDefItem, RuleItem};
+use crate::selector::rule::{Matcher, MatcherData, Rule, RuleDefItem, RuleItem, SpecificityOf};
+use crate::selector::Specificity;
 use std::cmp::Ordering;
 use std::{collections::HashMap, ops::Range};
 
@@ -556,7 +557,56 @@ fn pseudo_not(rules: &mut Vec<RuleItem>) {
 		Box::new(|data: MatcherData| {
 			let selector = Rule::param(&data, "selector").expect("selector param must have.");
 			Matcher {
-				all_handle: Some(Box::new(move |eles: &Elements, _| eles.not(selector))),
+				all_handle: Some(Box::new(move |eles: &Elements, _| {
+					eles.filter_with_type("not", selector, FilterType::Not)
+				})),
+				specificity: Some(SpecificityOf::MaxOf(selector)),
+				..Default::default()
+			}
+		}),
+	);
+	rules.push(rule.into());
+}
+
+/// pseudo selector: `:is`
+fn pseudo_is(rules: &mut Vec<RuleItem>) {
+	let name = ":is";
+	let selector = ":is({spaces}{selector}{spaces})";
+	let rule = RuleDefItem(
+		name,
+		selector,
+		PRIORITY,
+		vec![("selector", 0)],
+		Box::new(|data: MatcherData| {
+			let selector = Rule::param(&data, "selector").expect("selector param must have.");
+			Matcher {
+				all_handle: Some(Box::new(move |eles: &Elements, _| {
+					eles.filter_with_type("is", selector, FilterType::Filter)
+				})),
+				specificity: Some(SpecificityOf::MaxOf(selector)),
+				..Default::default()
+			}
+		}),
+	);
+	rules.push(rule.into());
+}
+
+/// pseudo selector: `:where`, same as `:is` but has no specificity
+fn pseudo_where(rules: &mut Vec<RuleItem>) {
+	let name = ":where";
+	let selector = ":where({spaces}{selector}{spaces})";
+	let rule = RuleDefItem(
+		name,
+		selector,
+		PRIORITY,
+		vec![("selector", 0)],
+		Box::new(|data: MatcherData| {
+			let selector = Rule::param(&data, "selector").expect("selector param must have.");
+			Matcher {
+				all_handle: Some(Box::new(move |eles: &Elements, _| {
+					eles.filter_with_type("where", selector, FilterType::Filter)
+				})),
+				specificity: Some(SpecificityOf::Fixed(Specificity::default())),
 				..Default::default()
 			}
 		}),
@@ -655,8 +705,10 @@ pub fn init(rules: &mut Vec<RuleItem>) {
 	pseudo_nth_last_of_type(rules);
 	// only-of-type
 	pseudo_only_of_type(rules);
-	// not
+	// not, is, where
 	pseudo_not(rules);
+	pseudo_is(rules);
+	pseudo_where(rules);
 	// contains
 	pseudo_contains(rules);
 	// ---- jquery selectors -----
@@ -667,3 +719,28 @@ pub fn init(rules: &mut Vec<RuleItem>) {
 	// :submit alias
 	pseudo_alias_submit(rules);
 }
+
+#[cfg(test)]
+mod test {
+	use crate::mock::Doc;
+
+	#[test]
+	fn test_nth_child() {
+		crate::init();
+		let doc = Doc::parse("<ul><li>1</li><li>2</li><li>3</li><li>4</li></ul><ol><li>5</li></ol>");
+		let root = doc.root();
+		for (selector, expected) in [
+			("li:nth-child(-n+2)", "125"),
+			("li:nth-last-child(-n+2)", "345"),
+			("li:nth-child(-2n+3)", "135"),
+			("li:nth-of-type(-n+1)", "15"),
+			("li:nth-child(3n-1)", "2"),
+			("li:nth-child(2n+5)", ""),
+		] {
+			assert_eq!(root.find(selector).text(), expected, "{}", selector);
+			// check the elements one by one
+			let items = root.find("li");
+			assert_eq!(items.filter(selector).text(), expected, "{}", selector);
+		}
+	}
+}
@@ -0,0 +1,206 @@
+/*
+*
+* the selector ast, parsed before compiled into matchers
+*
+*/
+use super::pattern::Matched;
+use super::rule::Rule;
+use super::Combinator;
+use crate::constants::{
+	NAME_SELECTOR_ALL, NAME_SELECTOR_ATTR, NAME_SELECTOR_ATTR_PREFIX, NAME_SELECTOR_CLASS,
+	NAME_SELECTOR_ID, NAME_SELECTOR_NAME,
+};
+use crate::utils::unescape_css;
+use std::fmt;
+
+/// `SimpleSelector`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimpleSelector {
+	// `*`
+	Universal,
+	// `div`
+	Type(String),
+	// `#id`
+	Id(String),
+	// `.class`
+	Class(String),
+	// `[name]`, `[name^="value"]`
+	Attribute {
+		name: String,
+		operator: Option<String>,
+		value: Option<String>,
+	},
+	// `[^prefix]`
+	AttributePrefix(String),
+	// `:first-child`, `:nth-child(2n+1)`, `:not(.a, .b)`
+	Pseudo {
+		name: String,
+		argument: Option<PseudoArgument>,
+	},
+	// the rules added by `add_rules` that not recognized
+	Other {
+		rule: String,
+		text: String,
+	},
+}
+
+/// `PseudoArgument`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PseudoArgument {
+	// the nested selector list, e.g. `:not()`, `:is()`, `:where()`
+	Selector(SelectorList),
+	// the raw argument, e.g. `:nth-child()`, `:contains()`
+	Raw(String),
+}
+
+impl SimpleSelector {
+	// build from a matched rule
+	pub(crate) fn from_rule(
+		name: &str,
+		rule: &Rule,
+		matched: &[Matched],
+		text: String,
+		nested: Option<SelectorList>,
+	) -> Self {
+		let data = rule.data(matched);
+		let identity = || Rule::param(&data, "identity").unwrap_or("").to_string();
+		match name {
+			NAME_SELECTOR_ALL => SimpleSelector::Universal,
+			NAME_SELECTOR_NAME => SimpleSelector::Type(identity()),
+			NAME_SELECTOR_ID => SimpleSelector::Id(identity()),
+			NAME_SELECTOR_CLASS => SimpleSelector::Class(identity()),
+			NAME_SELECTOR_ATTR => {
+				let value = Rule::param(&data, ("regexp", 0, "2"))
+					.or_else(|| Rule::param(&data, ("regexp", 0, "3")))
+					.or_else(|| Rule::param(&data, ("regexp", 0, "4")));
+				SimpleSelector::Attribute {
+					name: Rule::param(&data, "attr_key").unwrap_or("").to_string(),
+					operator: value
+						.map(|_| format!("{}=", Rule::param(&data, ("regexp", 0, "1")).unwrap_or(""))),
+					value: value.map(unescape_css),
+				}
+			}
+			NAME_SELECTOR_ATTR_PREFIX => {
+				SimpleSelector::AttributePrefix(Rule::param(&data, "attr_key").unwrap_or("").to_string())
+			}
+			_ if name.starts_with(':') => {
+				let argument = if let Some(list) = nested {
+					Some(PseudoArgument::Selector(list))
+				} else if let (Some(start), true) = (text.find('('), text.ends_with(')')) {
+					Some(PseudoArgument::Raw(
+						text[start + 1..text.len() - 1].trim().to_string(),
+					))
+				} else {
+					None
+				};
+				SimpleSelector::Pseudo {
+					name: name.to_string(),
+					argument,
+				}
+			}
+			_ => SimpleSelector::Other {
+				rule: name.to_string(),
+				text,
+			},
+		}
+	}
+}
+
+impl fmt::Display for SimpleSelector {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		use SimpleSelector::*;
+		match self {
+			Universal => f.write_str("*"),
+			Type(name) => f.write_str(name),
+			Id(id) => write!(f, "#{}", id),
+			Class(class_name) => write!(f, ".{}", class_name),
+			Attribute {
+				name,
+				operator,
+				value,
+			} => match (operator, value) {
+				(Some(operator), Some(value)) => {
+					write!(f, "[{}{}\"", name, operator)?;
+					// escape the value so it can be parsed back
+					for ch in value.chars() {
+						match ch {
+							'"' | '\\' => write!(f, "\\{}", ch)?,
+							ch if ch.is_control() => write!(f, "\\{:x} ", ch as u32)?,
+							ch => write!(f, "{}", ch)?,
+						}
+					}
+					f.write_str("\"]")
+				}
+				_ => write!(f, "[{}]", name),
+			},
+			AttributePrefix(prefix) => write!(f, "[^{}]", prefix),
+			Pseudo { name, argument } => match argument {
+				Some(PseudoArgument::Selector(list)) => write!(f, "{}({})", name, list),
+				Some(PseudoArgument::Raw(raw)) => write!(f, "{}({})", name, raw),
+				None => f.write_str(name),
+			},
+			Other { text, .. } => f.write_str(text),
+		}
+	}
+}
+
+/// `CompoundSelector`
+/// the chained simple selectors, e.g. `a.b[c]`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompoundSelector(pub Vec<SimpleSelector>);
+
+impl fmt::Display for CompoundSelector {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for simple in &self.0 {
+			write!(f, "{}", simple)?;
+		}
+		Ok(())
+	}
+}
+
+/// `ComplexSelector`
+/// the compound selectors with the combinator before each,
+/// the combinator of the first compound is `ChildrenAll` unless it's a relative selector like `> a`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ComplexSelector(pub Vec<(Combinator, CompoundSelector)>);
+
+impl fmt::Display for ComplexSelector {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for (index, (comb, compound)) in self.0.iter().enumerate() {
+			let comb = match comb {
+				Combinator::Children => ">",
+				Combinator::NextAll => "~",
+				Combinator::Next => "+",
+				_ => "",
+			};
+			if comb.is_empty() {
+				if index > 0 {
+					f.write_str(" ")?;
+				}
+			} else if index > 0 {
+				write!(f, " {} ", comb)?;
+			} else {
+				write!(f, "{} ", comb)?;
+			}
+			write!(f, "{}", compound)?;
+		}
+		Ok(())
+	}
+}
+
+/// `SelectorList`
+/// the complex selectors splitted by ','
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SelectorList(pub Vec<ComplexSelector>);
+
+impl fmt::Display for SelectorList {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for (index, complex) in self.0.iter().enumerate() {
+			if index > 0 {
+				f.write_str(", ")?;
+			}
+			write!(f, "{}", complex)?;
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,492 @@
+use super::{Combinator, QueryProcess, QueryStrategy, Selector, SelectorSegment};
+use crate::interface::{BoxDynElement, Elements};
+use crate::selector::rule::Matcher;
+use std::cell::Cell;
+use std::fmt;
+use std::rc::Rc;
+
+/// pub enum `StepKind`
+/// how a compound selector runs in `find`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepKind {
+	// select from the context elements or the previous step's result
+	Select,
+	// the lookup optimization, search the compound with the most priority in all descendants first
+	Candidates,
+	// the first compound of the lookup, the candidates must be in it's scope
+	Scope,
+	// check the ancestors or siblings of the candidates from right to left
+	Check,
+}
+
+/// pub enum `CacheKind`
+/// the document cache used by the first segment of a step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheKind {
+	// `get_element_by_id`
+	Id,
+	// the document index or lookups, fall back to walk the tree if the document has none
+	Lookup,
+}
+
+/// pub struct `SegmentPlan`
+#[derive(Debug, Clone)]
+pub struct SegmentPlan {
+	pub name: &'static str,
+	pub source: String,
+	pub combinator: Combinator,
+	pub priority: u32,
+	pub cache: Option<CacheKind>,
+}
+
+/// pub struct `StepPlan`
+/// a compound selector, the segments are applied in order
+#[derive(Debug, Clone)]
+pub struct StepPlan {
+	pub kind: StepKind,
+	pub segments: Vec<SegmentPlan>,
+}
+
+/// pub struct `QueryPlan`
+/// the plan of a selector in the selector list, the steps are in the execution order,
+/// `strategy` is the one runs, the ancestor filter always runs from right to left
+#[derive(Debug, Clone)]
+pub struct QueryPlan {
+	pub lookup: bool,
+	pub strategy: QueryStrategy,
+	pub ancestor_filter: bool,
+	pub steps: Vec<StepPlan>,
+}
+
+/// pub struct `SelectorPlan`
+/// the result of `Selector::explain`
+#[derive(Debug, Clone, Default)]
+pub struct SelectorPlan(pub Vec<QueryPlan>);
+
+// the strategy runs in the context and whether the ancestor filter is used
+fn run_strategy(process: &QueryProcess, use_filter: bool) -> (QueryStrategy, bool) {
+	if Elements::is_ancestor_filtered(process, use_filter) {
+		(QueryStrategy::RightToLeft, true)
+	} else {
+		(process.strategy, false)
+	}
+}
+
+// the compounds in the execution order, `true` if the compound is in `should_in`
+fn execution_order(
+	process: &QueryProcess,
+	strategy: QueryStrategy,
+) -> Vec<(StepKind, bool, usize)> {
+	let QueryProcess {
+		should_in, query, ..
+	} = process;
+	let mut steps = Vec::with_capacity(query.len() + 2);
+	if let QueryStrategy::RightToLeft = strategy {
+		let last_index = query.len() - 1;
+		steps.push((StepKind::Candidates, false, last_index));
+		for index in (0..last_index).rev() {
+			steps.push((StepKind::Check, false, index));
+		}
+	} else if let Some(lookup) = should_in {
+		steps.push((StepKind::Candidates, false, 0));
+		steps.push((StepKind::Scope, true, 0));
+		for index in (1..lookup.len()).rev() {
+			steps.push((StepKind::Check, true, index));
+		}
+		steps.extend((1..query.len()).map(|index| (StepKind::Select, false, index)));
+	} else {
+		steps.extend((0..query.len()).map(|index| (StepKind::Select, false, index)));
+	}
+	steps
+}
+
+// get the compound of the step
+fn compound(process: &QueryProcess, in_lookup: bool, index: usize) -> &[SelectorSegment] {
+	if in_lookup {
+		&process.should_in.as_ref().expect("The lookup must exist")[index]
+	} else {
+		&process.query[index]
+	}
+}
+
+// the document cache of the segment, only the first segment in descendants can use it
+fn cache_of(matcher: &Matcher, comb: &Combinator) -> Option<CacheKind> {
+	if !matches!(comb, Combinator::ChildrenAll) {
+		return None;
+	}
+	if matcher.cache_handle.is_some() {
+		Some(CacheKind::Lookup)
+	} else if matcher.in_cache {
+		Some(CacheKind::Id)
+	} else {
+		None
+	}
+}
+
+impl Selector {
+	/// pub fn `explain`
+	/// describe the plan that `find` uses in the context elements: the steps in the execution order,
+	/// whether the lookup optimization, right to left or the ancestor filter is used and which segments use the document caches,
+	/// `find` parses the selector with the document statistics, use `Elements::explain` to get the same plan
+	pub fn explain(&self, context: &Elements) -> SelectorPlan {
+		let use_filter = context.use_ancestor_filter();
+		let plans = self
+			.process
+			.iter()
+			.map(|process| {
+				let (strategy, ancestor_filter) = run_strategy(process, use_filter);
+				let steps = execution_order(process, strategy)
+					.into_iter()
+					.map(|(kind, in_lookup, index)| {
+						let segments = compound(process, in_lookup, index);
+						let first_comb = match kind {
+							StepKind::Candidates => Combinator::ChildrenAll,
+							StepKind::Check => Combinator::Chain,
+							_ => segments[0].1,
+						};
+						let segments = segments
+							.iter()
+							.enumerate()
+							.map(|(seg_index, (matcher, comb))| SegmentPlan {
+								name: matcher.name,
+								source: matcher.source.clone(),
+								combinator: *comb,
+								priority: matcher.priority,
+								cache: if seg_index == 0 {
+									cache_of(matcher, &first_comb)
+								} else {
+									None
+								},
+							})
+							.collect();
+						StepPlan { kind, segments }
+					})
+					.collect();
+				QueryPlan {
+					lookup: process.should_in.is_some(),
+					strategy,
+					ancestor_filter,
+					steps,
+				}
+			})
+			.collect();
+		SelectorPlan(plans)
+	}
+	/// pub fn `profile`
+	/// count the matcher invocations and the elements visited of each segment in the later queries in the context elements,
+	/// the counters are shared with the returned profiler
+	pub fn profile(&mut self, context: &Elements) -> SelectorProfiler {
+		let use_filter = context.use_ancestor_filter();
+		let mut segments = Vec::new();
+		for (group, process) in self.process.iter_mut().enumerate() {
+			let (strategy, _) = run_strategy(process, use_filter);
+			let order = execution_order(process, strategy);
+			for (step, (_, in_lookup, index)) in order.into_iter().enumerate() {
+				let compound = if in_lookup {
+					&mut process.should_in.as_mut().expect("The lookup must exist")[index]
+				} else {
+					&mut process.query[index]
+				};
+				for (matcher, _) in compound.iter_mut() {
+					let counter = Rc::new(SegmentCounter::default());
+					instrument(matcher, &counter);
+					segments.push(SegmentProfileItem {
+						group,
+						step,
+						source: matcher.source.clone(),
+						counter,
+					});
+				}
+			}
+		}
+		SelectorProfiler { segments }
+	}
+}
+
+#[derive(Default, Debug)]
+struct SegmentCounter {
+	invocations: Cell<usize>,
+	visited: Cell<usize>,
+	matched: Cell<usize>,
+}
+
+impl SegmentCounter {
+	fn count(&self, visited: usize, matched: usize) {
+		self.invocations.set(self.invocations.get() + 1);
+		self.visited.set(self.visited.get() + visited);
+		self.matched.set(self.matched.get() + matched);
+	}
+}
+
+// wrap the handles of the matcher with the counter
+fn instrument(matcher: &mut Matcher, counter: &Rc<SegmentCounter>) {
+	if let Some(handle) = matcher.one_handle.take() {
+		let counter = Rc::clone(counter);
+		matcher.one_handle = Some(Box::new(move |ele: &BoxDynElement, use_cache| {
+			let is_matched = handle(ele, use_cache);
+			counter.count(1, is_matched as usize);
+			is_matched
+		}));
+	}
+	if let Some(handle) = matcher.all_handle.take() {
+		let counter = Rc::clone(counter);
+		matcher.all_handle = Some(Box::new(move |eles: &Elements, use_cache| {
+			let result = handle(eles, use_cache);
+			counter.count(eles.length(), result.length());
+			result
+		}));
+	}
+	if let Some(handle) = matcher.cache_handle.take() {
+		let counter = Rc::clone(counter);
+		matcher.cache_handle = Some(Box::new(move |eles: &Elements| {
+			let result = handle(eles);
+			if let Some(cached) = &result {
+				counter.count(0, cached.length());
+			}
+			result
+		}));
+	}
+}
+
+// the counter of a segment
+#[derive(Debug)]
+struct SegmentProfileItem {
+	group: usize,
+	step: usize,
+	source: String,
+	counter: Rc<SegmentCounter>,
+}
+
+/// pub struct `SegmentProfile`
+/// the counts of a segment, `group` and `step` are the indexs in the `SelectorPlan`,
+/// `visited` counts the elements passed to the matcher, the elements a rule checks inside,
+/// e.g. the siblings of `:nth-child` or the descendants of `:has`, are not counted,
+/// the candidates from the document caches are counted in `matched` without visiting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentProfile {
+	pub group: usize,
+	pub step: usize,
+	pub source: String,
+	pub invocations: usize,
+	pub visited: usize,
+	pub matched: usize,
+}
+
+/// pub struct `SelectorProfiler`
+/// the result of `Selector::profile`
+#[derive(Debug, Default)]
+pub struct SelectorProfiler {
+	segments: Vec<SegmentProfileItem>,
+}
+
+impl SelectorProfiler {
+	/// pub fn `segments`
+	/// the counts of the segments in the execution order
+	pub fn segments(&self) -> Vec<SegmentProfile> {
+		self
+			.segments
+			.iter()
+			.map(|item| SegmentProfile {
+				group: item.group,
+				step: item.step,
+				source: item.source.clone(),
+				invocations: item.counter.invocations.get(),
+				visited: item.counter.visited.get(),
+				matched: item.counter.matched.get(),
+			})
+			.collect()
+	}
+	/// pub fn `visited`
+	/// the total elements passed to the matchers of all the segments
+	pub fn visited(&self) -> usize {
+		self
+			.segments
+			.iter()
+			.map(|item| item.counter.visited.get())
+			.sum()
+	}
+}
+
+impl fmt::Display for StepKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			StepKind::Select => "select",
+			StepKind::Candidates => "candidates",
+			StepKind::Scope => "scope",
+			StepKind::Check => "check",
+		})
+	}
+}
+
+impl fmt::Display for SelectorPlan {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for (group, plan) in self.0.iter().enumerate() {
+			let yes_or_no = |value: bool| if value { "yes" } else { "no" };
+			writeln!(
+				f,
+				"#{} lookup: {}, right to left: {}, ancestor filter: {}",
+				group,
+				yes_or_no(plan.lookup),
+				yes_or_no(plan.strategy == QueryStrategy::RightToLeft),
+				yes_or_no(plan.ancestor_filter)
+			)?;
+			for (step, StepPlan { kind, segments }) in plan.steps.iter().enumerate() {
+				write!(f, "  {}. {}:", step, kind)?;
+				for segment in segments {
+					write!(
+						f,
+						" {:?} `{}`<{}, priority {}",
+						segment.combinator, segment.source, segment.name, segment.priority
+					)?;
+					match segment.cache {
+						Some(CacheKind::Id) => f.write_str(", cache: id")?,
+						Some(CacheKind::Lookup) => f.write_str(", cache: lookup")?,
+						None => {}
+					}
+					f.write_str(">")?;
+				}
+				writeln!(f)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+impl fmt::Display for SelectorProfiler {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for segment in self.segments() {
+			writeln!(
+				f,
+				"#{} step {} `{}`: invocations {}, visited {}, matched {}",
+				segment.group,
+				segment.step,
+				segment.source,
+				segment.invocations,
+				segment.visited,
+				segment.matched
+			)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{CacheKind, StepKind};
+	use crate::mock::Doc;
+	use crate::selector::{QueryStrategy, Selector};
+	#[test]
+	fn test_explain() {
+		crate::init();
+		let doc = Doc::parse(r#"<div><p class="item"><a href="/">a</a></p></div>"#);
+		let root = doc.root();
+		let selector: Selector = "div > .item a[href]".parse().unwrap();
+		let plan = selector.explain(&root);
+		assert_eq!(plan.0.len(), 1);
+		let plan = &plan.0[0];
+		assert!(plan.lookup);
+		let kinds = plan.steps.iter().map(|step| step.kind).collect::<Vec<_>>();
+		assert_eq!(
+			kinds,
+			vec![StepKind::Candidates, StepKind::Scope, StepKind::Select]
+		);
+		// the class runs first and can use the document lookup
+		assert_eq!(plan.steps[0].segments[0].source, ".item");
+		assert_eq!(plan.steps[0].segments[0].cache, Some(CacheKind::Lookup));
+		assert_eq!(plan.steps[1].segments[0].source, "div");
+		// without the lookup
+		let selector = Selector::from_str("#id .item", false).unwrap();
+		let plan = &selector.explain(&root).0[0];
+		assert!(!plan.lookup);
+		assert_eq!(plan.steps[0].segments[0].cache, Some(CacheKind::Id));
+		assert_eq!(plan.steps[1].segments[0].source, ".item");
+		assert!(format!("{}", selector.explain(&root)).contains("cache: id"));
+	}
+	#[test]
+	fn test_explain_in_context() {
+		crate::init();
+		let doc = Doc::parse(&format!(
+			r#"<div><p class="item"><a href="/">a</a></p></div>{}"#,
+			"<i></i>".repeat(20)
+		));
+		let root = doc.root();
+		// `find` uses the lookup only with the document statistics
+		let plan = &root.explain("div > .item a[href]").0[0];
+		assert!(!plan.lookup);
+		assert_eq!(plan.strategy, QueryStrategy::TopDown);
+		// the ancestor filter is chosen when running
+		doc.0.use_ancestor_filter.set(true);
+		let plan = &root.explain("div .item a").0[0];
+		assert!(plan.ancestor_filter);
+		assert_eq!(plan.strategy, QueryStrategy::RightToLeft);
+		let kinds = plan.steps.iter().map(|step| step.kind).collect::<Vec<_>>();
+		assert_eq!(
+			kinds,
+			vec![StepKind::Candidates, StepKind::Check, StepKind::Check]
+		);
+		assert!(root
+			.explain("div .item a")
+			.to_string()
+			.contains("ancestor filter: yes"));
+		// the sibling combinators can't use the ancestor filter
+		assert!(!root.explain("div + .item").0[0].ancestor_filter);
+		// the lookup is used with the statistics of the index, then the ancestor filter is not
+		doc.build_index();
+		let plan = &root.explain("div > .item a[href]").0[0];
+		assert!(plan.lookup);
+		assert!(!plan.ancestor_filter);
+		assert_eq!(plan.steps[0].segments[0].source, ".item");
+		// invalid selectors have no plan
+		assert!(root.explain("div >").0.is_empty());
+	}
+	#[test]
+	fn test_find_profiled() {
+		crate::init();
+		let doc = Doc::parse(
+			r#"<div id="a"><p class="item">1</p><section><p>2</p><p class="item">3</p></section></div><p class="item">4</p>"#,
+		);
+		let root = doc.root();
+		let (result, profiler) = root.find_profiled("div p.item, #a");
+		assert_eq!(result.length(), 3);
+		assert_eq!(result.text(), root.find("div p.item, #a").text());
+		let plan = root.explain("div p.item, #a");
+		let segments = profiler.segments();
+		// the segments are in the plan's order
+		let sources = plan
+			.0
+			.iter()
+			.flat_map(|plan| plan.steps.iter().flat_map(|step| step.segments.iter()))
+			.map(|segment| segment.source.clone())
+			.collect::<Vec<_>>();
+		assert_eq!(
+			segments
+				.iter()
+				.map(|segment| segment.source.clone())
+				.collect::<Vec<_>>(),
+			sources
+		);
+		for segment in &segments {
+			let step = &plan.0[segment.group].steps[segment.step];
+			assert!(step
+				.segments
+				.iter()
+				.any(|item| item.source == segment.source));
+		}
+		// all the elements are checked by `div`, the `#a` is got from the document
+		let div = segments
+			.iter()
+			.find(|segment| segment.source == "div")
+			.unwrap();
+		assert_eq!((div.visited, div.matched), (6, 1));
+		let id = segments
+			.iter()
+			.find(|segment| segment.source == "#a")
+			.unwrap();
+		assert_eq!(id.matched, 1);
+		assert!(profiler.visited() >= 6);
+		assert!(profiler.to_string().contains("`p`"));
+		// the counters are not shared between the calls
+		let (_, profiler) = root.find_profiled("div");
+		assert_eq!(profiler.segments()[0].invocations, 6);
+	}
+}
@@ -1,16 +1,32 @@
+pub mod ast;
+pub mod explain;
 pub mod pattern;
+pub mod rewrite;
 pub mod rule;
+pub mod stream;
 
-use crate::{constants::NAME_SELECTOR_ALL, error::Error};
+use crate::{
+	constants::{NAME_SELECTOR_ALL, NAME_SELECTOR_ID, NAME_SELECTOR_NAME},
+	error::Error,
+	interface::{BoxDynElement, Elements, IDocumentStats, MaybeElement},
+};
 use lazy_static::lazy_static;
 use pattern::{exec, Matched};
-use rule::{Rule, RULES};
+use rule::{Rule, SpecificityOf, RULES};
 use std::{
+	cell::OnceCell,
+	cmp::Reverse,
+	fmt,
+	ops::Add,
 	str::FromStr,
 	sync::{Arc, Mutex},
 };
 
-use self::{pattern::BoxDynPattern, rule::Matcher};
+use self::{
+	ast::{ComplexSelector, CompoundSelector, SelectorList, SimpleSelector},
+	pattern::BoxDynPattern,
+	rule::Matcher,
+};
 
 lazy_static! {
 	static ref SPLITTER: Mutex<Vec<BoxDynPattern>> =
@@ -69,16 +85,66 @@ impl Combinator {
 	}
 }
 
+/// `Specificity`
+/// the (id, class, type) specificity of a selector
+/// https://www.w3.org/TR/selectors-4/#specificity-rules
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Specificity(pub u32, pub u32, pub u32);
+
+impl Specificity {
+	// the specificity of a rule by the rule name
+	fn of_rule(name: &str) -> Self {
+		match name {
+			NAME_SELECTOR_ALL => Specificity(0, 0, 0),
+			NAME_SELECTOR_ID => Specificity(1, 0, 0),
+			NAME_SELECTOR_NAME => Specificity(0, 0, 1),
+			// class, attribute and pseudo class selectors
+			_ => Specificity(0, 1, 0),
+		}
+	}
+}
+
+impl Add for Specificity {
+	type Output = Self;
+	fn add(self, other: Self) -> Self {
+		Specificity(self.0 + other.0, self.1 + other.1, self.2 + other.2)
+	}
+}
+
+impl fmt::Display for Specificity {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "({}, {}, {})", self.0, self.1, self.2)
+	}
+}
+
 pub type SelectorSegment = (Matcher, Combinator);
+
+// how the query process runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueryStrategy {
+	// select the compounds from left to right, use the `should_in` lookup if has
+	#[default]
+	TopDown,
+	// search the candidates of the last compound in the descendants,
+	// then check the previous compounds with the reversed combinators
+	RightToLeft,
+}
+
 #[derive(Default, Debug)]
 pub struct QueryProcess {
 	pub should_in: Option<SelectorGroupsItem>,
 	pub query: SelectorGroupsItem,
+	pub strategy: QueryStrategy,
 }
 
 #[derive(Default, Debug)]
 pub struct Selector {
 	pub process: Vec<QueryProcess>,
+	// the source selector, the ast is parsed from it when first used
+	source: String,
+	ast: OnceCell<SelectorList>,
+	// the specificities, the arguments of `:is()`, `:not()` are parsed when first used
+	specificity: OnceCell<Vec<Specificity>>,
 }
 
 type SelectorGroupsItem = Vec<Vec<SelectorSegment>>;
@@ -87,122 +153,188 @@ impl Selector {
 	pub fn new() -> Self {
 		Selector {
 			process: Vec::with_capacity(1),
+			..Default::default()
 		}
 	}
 	pub fn from_str(context: &str, use_lookup: bool) -> Result<Self, Error> {
+		Selector::from_str_with_stats(context, use_lookup, None)
+	}
+	/// pub fn `from_str_with_stats`
+	/// parse the selector, the planner uses the document statistics to estimate the costs
+	pub fn from_str_with_stats(
+		context: &str,
+		use_lookup: bool,
+		stats: Option<&dyn IDocumentStats>,
+	) -> Result<Self, Error> {
+		let mut selector = Selector::new();
+		if !context.is_empty() {
+			let (groups, _) = {
+				let splitter = SPLITTER.lock().unwrap();
+				let rules = RULES.lock().unwrap();
+				Selector::parse_with(context, &rules, &splitter, true)?
+			};
+			selector.source = String::from(context);
+			// optimize groups to query process
+			selector.optimize(groups, use_lookup, stats);
+		}
+		Ok(selector)
+	}
+	// compile the selector into matcher groups if `compile` is true, otherwise parse it into ast
+	// the rules and splitter must be locked before, because the nested selectors also use them
+	fn parse_with(
+		context: &str,
+		rules: &[(&'static str, Arc<Rule>)],
+		splitter: &[BoxDynPattern],
+		compile: bool,
+	) -> Result<(SelectorGroups, SelectorList), Error> {
 		let chars: Vec<char> = context.chars().collect();
 		let total_len = chars.len();
-		let mut selector = Selector::new();
-		if total_len > 0 {
-			let mut index: usize = 0;
-			let mut comb = Combinator::ChildrenAll;
-			let mut prev_in = PrevInSelector::Begin;
-			let mut last_in = prev_in;
-			let mut groups: SelectorGroups = Vec::new();
-			let splitter = SPLITTER.lock().unwrap();
-			let rules = RULES.lock().unwrap();
-			Selector::add_group(&mut groups);
-			while index < total_len {
-				let next_chars = &chars[index..];
-				// first check if combinator
-				if let Some((matched, len, _)) = Rule::exec_queues(&splitter, next_chars) {
-					let op = matched[0].chars.iter().collect::<String>();
-					let op = op.trim();
-					if prev_in == PrevInSelector::Splitter {
-						// wrong multiple combinator
+		let mut groups: SelectorGroups = Vec::new();
+		let mut ast = SelectorList(vec![ComplexSelector::default()]);
+		let mut index: usize = 0;
+		let mut comb = Combinator::ChildrenAll;
+		let mut prev_in = PrevInSelector::Begin;
+		let mut last_in = prev_in;
+		Selector::add_group(&mut groups);
+		while index < total_len {
+			let next_chars = &chars[index..];
+			// first check if combinator
+			if let Some((matched, len, _)) = Rule::exec_queues(splitter, next_chars) {
+				let op = matched[0].chars.iter().collect::<String>();
+				let op = op.trim();
+				if prev_in == PrevInSelector::Splitter {
+					// wrong multiple combinator
+					return Err(Error::InvalidSelector {
+						context: String::from(context),
+						reason: format!(
+							"Wrong combinator '{}' at index {}",
+							matched[0].chars.iter().collect::<String>(),
+							index
+						),
+					});
+				}
+				// find the match
+				index += len;
+				// set combinator
+				if op == "," {
+					if prev_in != PrevInSelector::Selector {
 						return Err(Error::InvalidSelector {
 							context: String::from(context),
-							reason: format!(
-								"Wrong combinator '{}' at index {}",
-								matched[0].chars.iter().collect::<String>(),
-								index
-							),
+							reason: format!("Wrong empty selector before ',' at index  {}", index),
 						});
 					}
-					// find the match
-					index += len;
-					// set combinator
-					if op == "," {
-						if prev_in != PrevInSelector::Selector {
-							return Err(Error::InvalidSelector {
-								context: String::from(context),
-								reason: format!("Wrong empty selector before ',' at index  {}", index),
-							});
-						}
-						Selector::add_group(&mut groups);
-						comb = Combinator::ChildrenAll;
-					} else {
-						comb = Combinator::from(op);
-					}
-					// set prev is splitter
-					if op.is_empty() {
-						last_in = prev_in;
-						prev_in = PrevInSelector::Splitter;
-					} else {
-						prev_in = PrevInSelector::Splitter;
-						last_in = prev_in;
-					}
-					continue;
+					Selector::add_group(&mut groups);
+					ast.0.push(ComplexSelector::default());
+					comb = Combinator::ChildrenAll;
+				} else {
+					comb = Combinator::from(op);
 				}
-				// then it must match a selector rule
-				let mut is_new_item = true;
-				if prev_in == PrevInSelector::Selector {
-					comb = Combinator::Chain;
-					is_new_item = false;
+				// set prev is splitter
+				if op.is_empty() {
+					last_in = prev_in;
+					prev_in = PrevInSelector::Splitter;
 				} else {
-					prev_in = PrevInSelector::Selector;
+					prev_in = PrevInSelector::Splitter;
 					last_in = prev_in;
 				}
-				let mut finded = false;
-				for (_, r) in rules.iter() {
-					if let Some((mut matched, len, queue_num)) = r.exec(next_chars) {
-						// find the rule
+				continue;
+			}
+			// then it must match a selector rule
+			let mut is_new_item = true;
+			if prev_in == PrevInSelector::Selector {
+				comb = Combinator::Chain;
+				is_new_item = false;
+			} else {
+				prev_in = PrevInSelector::Selector;
+				last_in = prev_in;
+			}
+			let mut finded = false;
+			let start_index = index;
+			for (name, r) in rules.iter() {
+				if let Some((mut matched, len, queue_num)) = r.exec(next_chars) {
+					// find the rule
+					index += len;
+					let queues = &r.queues;
+					let mut nested: Option<SelectorList> = None;
+					if queue_num == queues.len() {
+						finded = true;
+					} else if queues[queue_num].is_nested() {
+						// nested selector
+						let (len, nested_matched) = Selector::parse_until(
+							&chars[index..],
+							&queues[queue_num + 1..],
+							rules,
+							splitter,
+							0,
+						);
 						index += len;
-						let queues = &r.queues;
-						if queue_num == queues.len() {
-							// push to selector
-							Selector::add_group_item(&mut groups, (r.make(&matched), comb), is_new_item);
-							finded = true;
-						} else if queues[queue_num].is_nested() {
-							// nested selector
-							let (len, nested_matched) = Selector::parse_until(
-								&chars[index..],
-								&queues[queue_num + 1..],
-								&rules,
-								&splitter,
-								0,
-							);
-							index += len;
-							matched.extend(nested_matched);
-							Selector::add_group_item(&mut groups, (r.make(&matched), comb), is_new_item);
-							finded = true;
+						// the nested selectors are only needed by the ast, the matcher parses them itself
+						if let (false, Some(nested_selector)) = (
+							compile,
+							nested_matched.iter().find(|m| m.name == "selector"),
+						) {
+							let nested_context = nested_selector.chars.iter().collect::<String>();
+							// keep the raw argument if the nested selector can't be parsed
+							nested = Selector::parse_with(&nested_context, rules, splitter, false)
+								.ok()
+								.map(|(_, list)| list);
 						}
-						break;
+						matched.extend(nested_matched);
+						finded = true;
 					}
-				}
-				if !finded {
-					// no splitter, no selector rule
-					return Err(Error::InvalidSelector {
-						context: String::from(context),
-						reason: format!(
-							"Unrecognized selector '{}' at index {}",
-							next_chars.iter().collect::<String>(),
-							index
-						),
-					});
+					if finded {
+						// push to selector
+						let text = chars[start_index..index].iter().collect::<String>();
+						if compile {
+							let mut matcher = Selector::make_matcher(name, r, &matched);
+							matcher.source = text;
+							Selector::add_group_item(&mut groups, (matcher, comb), is_new_item);
+						} else {
+							let simple = SimpleSelector::from_rule(name, r, &matched, text, nested);
+							Selector::add_ast_item(&mut ast, simple, comb, is_new_item);
+						}
+					}
+					break;
 				}
 			}
-			if last_in != PrevInSelector::Selector {
+			if !finded {
+				// no splitter, no selector rule
 				return Err(Error::InvalidSelector {
 					context: String::from(context),
-					reason: String::from("Wrong selector rule at last"),
+					reason: format!(
+						"Unrecognized selector '{}' at index {}",
+						next_chars.iter().collect::<String>(),
+						index
+					),
 				});
 			}
-			// optimize groups to query process
-			selector.optimize(groups, use_lookup);
 		}
-
-		Ok(selector)
+		if last_in != PrevInSelector::Selector {
+			return Err(Error::InvalidSelector {
+				context: String::from(context),
+				reason: String::from("Wrong selector rule at last"),
+			});
+		}
+		Ok((groups, ast))
+	}
+	// make the matcher, set the specificity by the rule name if the rule not set
+	fn make_matcher(name: &'static str, rule: &Rule, matched: &[Matched]) -> Matcher {
+		let mut matcher = rule.make(matched);
+		matcher.name = name;
+		if matcher.specificity.is_none() {
+			matcher.specificity = Some(SpecificityOf::Fixed(Specificity::of_rule(name)));
+		}
+		matcher
+	}
+	// add a simple selector to the ast
+	fn add_ast_item(ast: &mut SelectorList, simple: SimpleSelector, comb: Combinator, is_new: bool) {
+		if let Some(complex) = ast.0.last_mut() {
+			if is_new {
+				complex.0.push((comb, CompoundSelector(vec![simple])));
+			} else if let Some((_, compound)) = complex.0.last_mut() {
+				compound.0.push(simple);
+			}
+		}
 	}
 	// add a selector group, splitted by ','
 	fn add_group(groups: &mut SelectorGroups) {
@@ -218,57 +350,130 @@ impl Selector {
 			}
 		}
 	}
-	// optimize the parse process
-	fn optimize(&mut self, groups: SelectorGroups, use_lookup: bool) {
+	// plan the query process by the costs, the segments of a compound are sorted by the costs,
+	// and the compound with the least cost is searched first if `use_lookup` is true,
+	// the costs are the estimated counts if the document statistics are provided, otherwise the priorities
+	fn optimize(
+		&mut self,
+		groups: SelectorGroups,
+		use_lookup: bool,
+		stats: Option<&dyn IDocumentStats>,
+	) {
 		let mut process: Vec<QueryProcess> = Vec::with_capacity(groups.len());
 		for mut group in groups {
-			// first optimize the chain selectors, the rule who's priority is bigger will apply first
-			let mut max_index: usize = 0;
-			let mut max_priority: u32 = 0;
-			for (index, r) in group.iter_mut().enumerate() {
-				let mut total_priority = 0;
+			let mut costs: Vec<QueryCost> = Vec::with_capacity(group.len());
+			for r in group.iter_mut() {
 				if r.len() > 1 {
 					let chain_comb = r[0].1;
-					r.sort_by(|a, b| b.0.priority.partial_cmp(&a.0.priority).unwrap());
-					let mut now_first = &mut r[0];
-					if now_first.1 != chain_comb {
-						now_first.1 = chain_comb;
-						total_priority += now_first.0.priority;
-						for n in &mut r[1..] {
-							n.1 = Combinator::Chain;
-							total_priority += n.0.priority;
-						}
-						continue;
-					}
-				}
-				if use_lookup {
-					total_priority = r.iter().map(|p| p.0.priority).sum();
-					if total_priority > max_priority {
-						max_priority = total_priority;
-						max_index = index;
+					r.sort_by_key(|(matcher, _)| segment_cost(matcher, stats));
+					r[0].1 = chain_comb;
+					for n in &mut r[1..] {
+						n.1 = Combinator::Chain;
 					}
 				}
+				costs.push(compound_cost(r, stats));
 			}
-			// if the first combinator is child, and the max_index > 1, use the max_index's rule first
-			if use_lookup && max_index > 0 {
+			// the first compound with the least cost
+			let start_index = costs
+				.iter()
+				.enumerate()
+				.min_by_key(|(_, cost)| **cost)
+				.map(|(index, _)| index)
+				.unwrap_or(0);
+			// if the first combinator is child, search the start compound first, then check from right to left,
+			// if the start compound is the last, needn't select the previous compounds
+			if use_lookup && start_index > 0 && costs[start_index] < costs[0] {
 				let is_child = matches!(
 					group[0][0].1,
 					Combinator::Children | Combinator::ChildrenAll
 				);
 				if is_child {
-					let query = group.split_off(max_index);
-					let should_in = Some(group);
-					process.push(QueryProcess { should_in, query });
+					if start_index == group.len() - 1 {
+						process.push(QueryProcess {
+							should_in: None,
+							query: group,
+							strategy: QueryStrategy::RightToLeft,
+						});
+					} else {
+						let query = group.split_off(start_index);
+						let should_in = Some(group);
+						process.push(QueryProcess {
+							should_in,
+							query,
+							strategy: QueryStrategy::TopDown,
+						});
+					}
 					continue;
 				}
 			}
 			process.push(QueryProcess {
 				should_in: None,
 				query: group,
+				strategy: QueryStrategy::TopDown,
 			});
 		}
 		self.process = process;
 	}
+	/// pub fn `ast`
+	/// the parsed selector list, empty if the selector is not built from a string
+	pub fn ast(&self) -> &SelectorList {
+		self.ast.get_or_init(|| {
+			if self.source.is_empty() {
+				return SelectorList::default();
+			}
+			let splitter = SPLITTER.lock().unwrap();
+			let rules = RULES.lock().unwrap();
+			// the source has been compiled, so it can be parsed again
+			Selector::parse_with(&self.source, &rules, &splitter, false)
+				.map(|(_, ast)| ast)
+				.unwrap_or_default()
+		})
+	}
+	/// pub fn `specificity`
+	/// the specificity of each selector in the selector list
+	pub fn specificity(&self) -> Vec<Specificity> {
+		self
+			.specificity
+			.get_or_init(|| {
+				self
+					.process
+					.iter()
+					.map(
+						|QueryProcess {
+						   should_in, query, ..
+						 }| {
+							should_in
+								.iter()
+								.flatten()
+								.chain(query.iter())
+								.flatten()
+								.fold(Specificity::default(), |total, (matcher, _)| {
+									total + matcher.specificity()
+								})
+						},
+					)
+					.collect()
+			})
+			.clone()
+	}
+	/// pub fn `is_match`
+	/// check if the element matches any selector in the list, the compounds are checked from right to left,
+	/// parse the selector once and use it when checking many elements
+	pub fn is_match(&self, ele: &BoxDynElement) -> bool {
+		self.process.iter().any(
+			|QueryProcess {
+			   should_in, query, ..
+			 }| {
+				let compounds: Vec<&[SelectorSegment]> = should_in
+					.iter()
+					.flatten()
+					.chain(query.iter())
+					.map(|compound| compound.as_slice())
+					.collect();
+				!compounds.is_empty() && is_compounds_matched(&compounds, ele)
+			},
+		)
+	}
 	// change the combinator
 	pub fn head_combinator(&mut self, comb: Combinator) {
 		for p in &mut self.process {
@@ -287,6 +492,10 @@ impl Selector {
 					}
 				};
 			}
+			// the candidates of right to left must be in the descendants
+			if !matches!(comb, Combinator::Children | Combinator::ChildrenAll) {
+				p.strategy = QueryStrategy::TopDown;
+			}
 		}
 	}
 	// make '*' with combinator
@@ -302,7 +511,9 @@ impl Selector {
 			}
 		}
 		let cur_rule = Arc::clone(all_rule.as_ref().expect("All rule must add to rules"));
-		let matcher = cur_rule.make(&[]);
+		let mut matcher = cur_rule.make(&[]);
+		matcher.name = NAME_SELECTOR_ALL;
+		matcher.source = String::from("*");
 		(matcher, comb)
 	}
 	// build a selector from a segment
@@ -310,16 +521,18 @@ impl Selector {
 		let process = QueryProcess {
 			query: vec![vec![segment]],
 			should_in: None,
+			strategy: QueryStrategy::TopDown,
 		};
 		Selector {
 			process: vec![process],
+			..Default::default()
 		}
 	}
 	// parse until
 	pub fn parse_until(
 		chars: &[char],
 		until: &[BoxDynPattern],
-		rules: &[(&str, Arc<Rule>)],
+		rules: &[(&'static str, Arc<Rule>)],
 		splitter: &[BoxDynPattern],
 		level: usize,
 	) -> (usize, Vec<Matched>) {
@@ -380,6 +593,34 @@ impl Selector {
 	}
 }
 
+// the estimated count and the priority, the less the better
+type QueryCost = (usize, Reverse<u32>);
+
+// the cost of a segment, the estimated count is 0 if no statistics
+fn segment_cost(matcher: &Matcher, stats: Option<&dyn IDocumentStats>) -> QueryCost {
+	let count = stats
+		.map(|stats| {
+			matcher
+				.estimate_handle
+				.as_ref()
+				.and_then(|handle| handle(stats))
+				.unwrap_or_else(|| stats.total_elements())
+		})
+		.unwrap_or(0);
+	(count, Reverse(matcher.priority))
+}
+
+// the cost of a compound, the least count of the segments and the total priority
+fn compound_cost(compound: &[SelectorSegment], stats: Option<&dyn IDocumentStats>) -> QueryCost {
+	compound.iter().fold(
+		(usize::MAX, Reverse(0)),
+		|(count, Reverse(priority)), (matcher, _)| {
+			let (cur_count, Reverse(cur_priority)) = segment_cost(matcher, stats);
+			(count.min(cur_count), Reverse(priority + cur_priority))
+		},
+	)
+}
+
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 enum PrevInSelector {
 	Begin,
@@ -387,9 +628,205 @@ enum PrevInSelector {
 	Selector,
 }
 
+// check the rules of a compound on the element, use the `one_handle` if the matcher has,
+// otherwise apply the `all_handle` on the element itself, e.g. `#id`, `:nth-child`
+pub(crate) fn is_compound_matched(compound: &[SelectorSegment], ele: &BoxDynElement) -> bool {
+	compound.iter().all(|(matcher, _)| {
+		if matcher.one_handle.is_some() {
+			matcher.one(ele, None)
+		} else {
+			!matcher.apply(&Elements::with_node(ele), None).is_empty()
+		}
+	})
+}
+
+// the parent element, not the document
+fn parent_element<'b>(ele: &BoxDynElement) -> MaybeElement<'b> {
+	ele
+		.parent()
+		.filter(|parent| parent.node_type().is_element())
+}
+
+// the last compound matches the element, and the compounds before match the related elements
+fn is_compounds_matched(compounds: &[&[SelectorSegment]], ele: &BoxDynElement) -> bool {
+	let (last, prevs) = compounds
+		.split_last()
+		.expect("The compounds must not be empty");
+	if !is_compound_matched(last, ele) {
+		return false;
+	}
+	if prevs.is_empty() {
+		return true;
+	}
+	match last[0].1 {
+		Combinator::Children => parent_element(ele)
+			.map(|parent| is_compounds_matched(prevs, &parent))
+			.unwrap_or(false),
+		Combinator::ChildrenAll => {
+			let mut cur = parent_element(ele);
+			while let Some(parent) = cur {
+				if is_compounds_matched(prevs, &parent) {
+					return true;
+				}
+				cur = parent_element(&parent);
+			}
+			false
+		}
+		Combinator::Next => ele
+			.previous_element_sibling()
+			.map(|sibling| is_compounds_matched(prevs, &sibling))
+			.unwrap_or(false),
+		Combinator::NextAll => {
+			let mut cur = ele.previous_element_sibling();
+			while let Some(sibling) = cur {
+				if is_compounds_matched(prevs, &sibling) {
+					return true;
+				}
+				cur = sibling.previous_element_sibling();
+			}
+			false
+		}
+		_ => is_compounds_matched(prevs, ele),
+	}
+}
+
 impl FromStr for Selector {
 	type Err = Error;
 	fn from_str(selector: &str) -> Result<Self, Self::Err> {
 		Selector::from_str(selector, true)
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::{QueryStrategy::*, Selector, SimpleSelector, Specificity};
+	use crate::interface::IDocumentStats;
+
+	struct Stats;
+	impl IDocumentStats for Stats {
+		fn total_elements(&self) -> usize {
+			1000
+		}
+		fn tag_count(&self, tag_name: &str) -> Option<usize> {
+			Some(if tag_name == "article" { 2 } else { 300 })
+		}
+		fn class_count(&self, class_name: &str) -> Option<usize> {
+			Some(if class_name == "rare" { 1 } else { 500 })
+		}
+		fn attr_count(&self, _attr_name: &str) -> Option<usize> {
+			None
+		}
+	}
+
+	fn specificity(selector: &str) -> Vec<Specificity> {
+		crate::init();
+		selector
+			.parse::<Selector>()
+			.expect("The selector is not correct")
+			.specificity()
+	}
+
+	#[test]
+	fn test_ast_display() {
+		crate::init();
+		for (selector, expected) in [
+			("div", "div"),
+			("*.a#b[c] > p  ~  span+em", "*.a#b[c] > p ~ span + em"),
+			("a[href^='http'], [^data-]", "a[href^=\"http\"], [^data-]"),
+			(
+				"li:nth-child( 2n+1 ):not( .a,#b )",
+				"li:nth-child(2n+1):not(.a, #b)",
+			),
+			("> a:contains(\"x\")", "> a:contains(\"x\")"),
+		] {
+			let parsed: Selector = selector.parse().expect("The selector is not correct");
+			assert_eq!(parsed.ast().to_string(), expected);
+		}
+		// the attribute value is escaped, and can be parsed back
+		for (selector, value) in [
+			(r#"[a='x"y']"#, r#"x"y"#),
+			(r#"[a='x\'y"z\\']"#, r#"x'y"z\"#),
+			(r#"[a="\31 0"]"#, "10"),
+		] {
+			let parsed: Selector = selector.parse().expect("The selector is not correct");
+			let display = parsed.ast().to_string();
+			let reparsed: Selector = display
+				.parse()
+				.expect("The displayed selector is not correct");
+			assert_eq!(reparsed.ast(), parsed.ast());
+			assert!(matches!(
+				&parsed.ast().0[0].0[0].1 .0[0],
+				SimpleSelector::Attribute { value: Some(v), .. } if v == value
+			));
+		}
+	}
+
+	#[test]
+	fn test_specificity() {
+		assert_eq!(specificity("*"), vec![Specificity(0, 0, 0)]);
+		assert_eq!(
+			specificity("li, ul > li.item, #nav a[href]:first-child"),
+			vec![
+				Specificity(0, 0, 1),
+				Specificity(0, 1, 2),
+				Specificity(1, 2, 1)
+			]
+		);
+		assert_eq!(specificity("a:not(#b, .c)"), vec![Specificity(1, 0, 1)]);
+		assert_eq!(specificity(":is(p, .c) span"), vec![Specificity(0, 1, 1)]);
+		assert_eq!(specificity("a:where(#b .c)"), vec![Specificity(0, 0, 1)]);
+		// the specificity is computed when first used, then cached
+		let selector: Selector = "div :is(p, :not(#b)) span".parse().unwrap();
+		assert!(selector.specificity.get().is_none());
+		assert_eq!(selector.specificity(), vec![Specificity(1, 0, 2)]);
+		assert_eq!(
+			selector.specificity.get(),
+			Some(&vec![Specificity(1, 0, 2)])
+		);
+		assert_eq!(
+			specificity("div :is(p, :not(#b)) span"),
+			vec![Specificity(1, 0, 2)]
+		);
+		assert_eq!(Specificity(1, 0, 2).to_string(), "(1, 0, 2)");
+	}
+
+	#[test]
+	fn test_cost_planner() {
+		crate::init();
+		let sources = |selector: &Selector| {
+			selector
+				.process
+				.iter()
+				.map(|p| {
+					let should_in = p.should_in.as_ref().map(|compounds| compounds.len());
+					let first = p.query[0][0].0.source.clone();
+					(should_in, p.strategy, first)
+				})
+				.collect::<Vec<_>>()
+		};
+		// the class is assumed more selective by the priorities
+		let selector = Selector::from_str("article .common p", true).unwrap();
+		assert_eq!(
+			sources(&selector),
+			vec![(Some(1), TopDown, String::from(".common"))]
+		);
+		let selector = Selector::from_str("article .common", true).unwrap();
+		assert_eq!(
+			sources(&selector),
+			vec![(None, RightToLeft, String::from("article"))]
+		);
+		// the statistics show the tag is rarer, so search from left to right
+		let selector = Selector::from_str_with_stats("article .common", true, Some(&Stats)).unwrap();
+		assert_eq!(
+			sources(&selector),
+			vec![(None, TopDown, String::from("article"))]
+		);
+		// reorder the compound parts by the counts
+		let selector = Selector::from_str_with_stats("div .common.rare", true, Some(&Stats)).unwrap();
+		assert_eq!(sources(&selector)[0].1, RightToLeft);
+		assert_eq!(selector.process[0].query[1][0].0.source, ".rare");
+		// the unknown counts use the total
+		let selector = Selector::from_str_with_stats("p[title]", true, Some(&Stats)).unwrap();
+		assert_eq!(sources(&selector), vec![(None, TopDown, String::from("p"))]);
+	}
+}
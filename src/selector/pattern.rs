@@ -323,6 +323,30 @@ impl Nth {
 		}
 		None
 	}
+	/// pub fn `parse`
+	/// parse the whole argument such as `2n+1`, `odd`, return `(n, index)`
+	pub fn parse(content: &str) -> Option<(isize, isize)> {
+		let chars: Vec<char> = content.trim().chars().collect();
+		let matched = Nth.matched(&chars)?;
+		if matched.chars.len() != chars.len() {
+			return None;
+		}
+		let parse_num = |key: &str| match matched.data.get(key) {
+			Some(num) => num.parse::<isize>().ok(),
+			None => Some(0),
+		};
+		Some((parse_num("n")?, parse_num("index")?))
+	}
+	/// pub fn `is_position_matched`
+	/// check if the position, starts from 1, is `n * k + index` for any `k >= 0`
+	pub fn is_position_matched(n: isize, index: isize, position: usize) -> bool {
+		let offset = position as isize - index;
+		if n == 0 {
+			offset == 0
+		} else {
+			offset % n == 0 && offset / n >= 0
+		}
+	}
 	// get indexs allowed
 	pub fn get_allowed_indexs(n: Option<&str>, index: Option<&str>, total: usize) -> Vec<usize> {
 		// has n
@@ -376,12 +400,17 @@ impl Nth {
 			let end = end_loop as usize;
 			let mut allow_indexs = Vec::with_capacity((end - start + 1) as usize);
 			for i in start..=end {
-				let cur_index = (i as isize * n + index) as usize;
-				if cur_index < 1 {
+				// the rounded loop bounds may be out of the range by one
+				let cur_index = i as isize * n + index;
+				if cur_index < 1 || cur_index > total as isize {
 					continue;
 				}
 				// last index need -1 for real list index
-				allow_indexs.push(cur_index - 1);
+				allow_indexs.push(cur_index as usize - 1);
+			}
+			// the indexs are descending when n < 0, but the handles need them ascending
+			if n < 0 {
+				allow_indexs.reverse();
 			}
 			return allow_indexs;
 		}
@@ -544,3 +573,43 @@ pub fn check_params_return<F: Fn() -> BoxDynPattern>(
 	}
 	Ok(cb())
 }
+
+#[cfg(test)]
+mod test {
+	use super::Nth;
+
+	#[test]
+	fn test_nth_parse() {
+		assert_eq!(Nth::parse("odd"), Some((2, 1)));
+		assert_eq!(Nth::parse("-n + 3"), Some((-1, 3)));
+		assert_eq!(Nth::parse("2n-1"), Some((2, -1)));
+		assert_eq!(Nth::parse("3n"), Some((3, 0)));
+		assert_eq!(Nth::parse("+5"), Some((0, 5)));
+		assert_eq!(Nth::parse("n+"), None);
+	}
+	#[test]
+	fn test_nth_allowed() {
+		// the allowed indexs of the rules are the positions the stream matcher checks
+		for (n, index) in [
+			(2, 1),
+			(2, 0),
+			(3, -1),
+			(0, 2),
+			(0, 0),
+			(-1, 3),
+			(-2, 5),
+			(-1, 0),
+			(1, -2),
+		] {
+			for total in 1..12 {
+				let allowed =
+					Nth::get_allowed_indexs(Some(&n.to_string()), Some(&index.to_string()), total);
+				let expected: Vec<usize> = (1..=total)
+					.filter(|&position| Nth::is_position_matched(n, index, position))
+					.map(|position| position - 1)
+					.collect();
+				assert_eq!(allowed, expected, "{}n+{} of {}", n, index, total);
+			}
+		}
+	}
+}
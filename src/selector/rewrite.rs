@@ -0,0 +1,194 @@
+/*
+*
+* rewrite the parsed selector list: scope, normalize, split
+*
+*/
+use super::ast::{ComplexSelector, CompoundSelector, PseudoArgument, SelectorList, SimpleSelector};
+use super::{Combinator, Selector};
+use crate::error::Error;
+
+/// pub fn `scope`
+/// prefix every complex selector with the scope selector, e.g. `.widget`,
+/// a relative selector like `> a` is joined with its own combinator
+pub fn scope(list: &SelectorList, scope: &str) -> Result<SelectorList, Error> {
+	let parsed: Selector = scope.parse()?;
+	let scope_list = parsed.ast();
+	if scope_list.0.len() != 1 {
+		return Err(Error::InvalidSelector {
+			context: String::from(scope),
+			reason: String::from("The scope must be a single complex selector"),
+		});
+	}
+	let prefix = &scope_list.0[0].0;
+	let result = list
+		.0
+		.iter()
+		.map(|complex| {
+			let mut compounds = prefix.clone();
+			compounds.extend(complex.0.iter().cloned());
+			ComplexSelector(compounds)
+		})
+		.collect();
+	Ok(SelectorList(result))
+}
+
+/// pub fn `split`
+/// break the selector list into individual selectors
+pub fn split(list: &SelectorList) -> Vec<SelectorList> {
+	list
+		.0
+		.iter()
+		.map(|complex| SelectorList(vec![complex.clone()]))
+		.collect()
+}
+
+// the order of the simple selectors in a compound
+fn kind_order(simple: &SimpleSelector) -> usize {
+	use SimpleSelector::*;
+	match simple {
+		Universal | Type(_) => 0,
+		Id(_) => 1,
+		Class(_) => 2,
+		Attribute { .. } | AttributePrefix(_) => 3,
+		Pseudo { .. } => 4,
+		Other { .. } => 5,
+	}
+}
+
+// collapse the whitespaces not in quotes
+fn collapse_whitespace(content: &str) -> String {
+	let mut result = String::with_capacity(content.len());
+	let mut quote: Option<char> = None;
+	let mut prev_space = false;
+	for ch in content.trim().chars() {
+		if let Some(q) = quote {
+			if ch == q {
+				quote = None;
+			}
+		} else if ch == '"' || ch == '\'' {
+			quote = Some(ch);
+		} else if ch.is_ascii_whitespace() {
+			if !prev_space {
+				result.push(' ');
+			}
+			prev_space = true;
+			continue;
+		}
+		prev_space = false;
+		result.push(ch);
+	}
+	result
+}
+
+fn normalize_simple(simple: &SimpleSelector) -> SimpleSelector {
+	use SimpleSelector::*;
+	match simple {
+		Type(name) => Type(name.to_ascii_lowercase()),
+		Attribute {
+			name,
+			operator,
+			value,
+		} => Attribute {
+			name: name.to_ascii_lowercase(),
+			operator: operator.clone(),
+			value: value.clone(),
+		},
+		AttributePrefix(prefix) => AttributePrefix(prefix.to_ascii_lowercase()),
+		Pseudo { name, argument } => Pseudo {
+			name: name.to_ascii_lowercase(),
+			argument: argument.as_ref().map(|argument| match argument {
+				PseudoArgument::Selector(list) => PseudoArgument::Selector(normalize(list)),
+				PseudoArgument::Raw(raw) => PseudoArgument::Raw(collapse_whitespace(raw)),
+			}),
+		},
+		_ => simple.clone(),
+	}
+}
+
+fn normalize_compound(compound: &CompoundSelector) -> CompoundSelector {
+	let mut simples: Vec<SimpleSelector> = compound.0.iter().map(normalize_simple).collect();
+	// the universal selector is redundant in a compound with other selectors
+	if simples.len() > 1 {
+		simples.retain(|simple| *simple != SimpleSelector::Universal);
+	}
+	// sort by kind then by text, the pseudo selectors keep their order
+	simples.sort_by_cached_key(|simple| {
+		let text = match simple {
+			SimpleSelector::Pseudo { .. } | SimpleSelector::Other { .. } => String::new(),
+			_ => simple.to_string(),
+		};
+		(kind_order(simple), text)
+	});
+	simples.dedup();
+	CompoundSelector(simples)
+}
+
+/// pub fn `normalize`
+/// lowercase the tag names, attribute names and pseudo names, sort the compound parts,
+/// collapse the whitespaces, the `Display` of the result is the canonical text
+pub fn normalize(list: &SelectorList) -> SelectorList {
+	SelectorList(
+		list
+			.0
+			.iter()
+			.map(|complex| {
+				ComplexSelector(
+					complex
+						.0
+						.iter()
+						.map(|(comb, compound)| {
+							let comb = match comb {
+								Combinator::Chain => Combinator::ChildrenAll,
+								comb => *comb,
+							};
+							(comb, normalize_compound(compound))
+						})
+						.collect(),
+				)
+			})
+			.collect(),
+	)
+}
+
+#[cfg(test)]
+mod test {
+	use super::{normalize, scope, split};
+	use crate::selector::{ast::SelectorList, Selector};
+
+	fn parse(selector: &str) -> SelectorList {
+		crate::init();
+		let parsed: Selector = selector.parse().expect("The selector is not correct");
+		parsed.ast().clone()
+	}
+
+	#[test]
+	fn test_scope() {
+		let list = parse("a, b.c");
+		assert_eq!(
+			scope(&list, ".widget").unwrap().to_string(),
+			".widget a, .widget b.c"
+		);
+		assert_eq!(
+			scope(&list, "#app > .widget").unwrap().to_string(),
+			"#app > .widget a, #app > .widget b.c"
+		);
+		assert_eq!(
+			scope(&parse("> b ~ p"), ".widget").unwrap().to_string(),
+			".widget > b ~ p"
+		);
+		assert!(scope(&list, ".a, .b").is_err());
+	}
+
+	#[test]
+	fn test_normalize_and_split() {
+		let list =
+			parse("DIV:first-child.b[TITLE='x']#id.a,  *.a  >  SPAN:not( P ,  .c ):contains( 'a  b'   )");
+		assert_eq!(
+			normalize(&list).to_string(),
+			r#"div#id.a.b[title="x"]:first-child, .a > span:not(p, .c):contains('a  b')"#
+		);
+		let parts: Vec<String> = split(&list).iter().map(|list| list.to_string()).collect();
+		assert_eq!(parts.len(), 2);
+		assert_eq!(parts[1], "*.a > SPAN:not(P, .c):contains('a  b')");
+	}
+}
@@ -1,5 +1,9 @@
 use super::pattern::{self, exec, to_pattern, BoxDynPattern, Matched, Pattern};
-use crate::{constants::PRIORITY_PSEUDO_SELECTOR, interface::Elements};
+use super::{Selector, Specificity};
+use crate::{
+	constants::PRIORITY_PSEUDO_SELECTOR,
+	interface::{Elements, FilterType, IDocumentStats},
+};
 use crate::{
 	interface::BoxDynElement,
 	utils::{to_static_str, vec_char_to_clean_str},
@@ -14,26 +18,62 @@ lazy_static! {
 // matcher handles
 pub type MatchAllHandle = Box<dyn (for<'a, 'r> Fn(&'a Elements<'r>, Option<bool>) -> Elements<'r>)>;
 pub type MatchOneHandle = Box<dyn Fn(&BoxDynElement, Option<bool>) -> bool>;
+// the fast path by the document lookups, `None` if the document can't provide
+pub type MatchCacheHandle = Box<dyn for<'a, 'r> Fn(&'a Elements<'r>) -> Option<Elements<'r>>>;
+// the estimated count of the matched elements in the document
+pub type MatchEstimateHandle = Box<dyn Fn(&dyn IDocumentStats) -> Option<usize>>;
 // matcher data
 pub type MatcherData = HashMap<SavedDataKey, &'static str>;
 // matcher factory
 pub type MatcherFactory = Box<dyn (Fn(MatcherData) -> Matcher) + Send + Sync>;
 
+// how the matcher counts in the specificity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecificityOf {
+	// fixed by the rule
+	Fixed(Specificity),
+	// the max specificity of the argument selector list, e.g. `:is()`, `:not()`,
+	// the argument is parsed when `Selector::specificity` is first called
+	MaxOf(&'static str),
+}
+
+impl SpecificityOf {
+	// resolve to the specificity
+	pub fn resolve(&self) -> Specificity {
+		match self {
+			SpecificityOf::Fixed(specificity) => *specificity,
+			SpecificityOf::MaxOf(selector) => selector
+				.parse::<Selector>()
+				.map(|selector| selector.specificity().into_iter().max().unwrap_or_default())
+				.unwrap_or_default(),
+		}
+	}
+}
+
 #[derive(Default)]
 pub struct Matcher {
 	pub all_handle: Option<MatchAllHandle>,
 	pub one_handle: Option<MatchOneHandle>,
+	pub cache_handle: Option<MatchCacheHandle>,
+	pub estimate_handle: Option<MatchEstimateHandle>,
+	// the key in the ancestor filter, e.g. `.class`, `DIV`
+	pub ancestor_key: Option<String>,
 	pub priority: u32,
 	pub in_cache: bool,
+	pub specificity: Option<SpecificityOf>,
+	// the rule name and the source text, see `Selector::explain`
+	pub name: &'static str,
+	pub source: String,
 }
 
 impl fmt::Debug for Matcher {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		f.write_str(
 			format!(
-				"Matcher{{ all_handle: {}, one_handle: {} }}",
+				"Matcher{{ all_handle: {}, one_handle: {}, cache_handle: {} }}",
 				self.all_handle.is_some(),
 				self.one_handle.is_some(),
+				self.cache_handle.is_some(),
 			)
 			.as_str(),
 		)
@@ -60,6 +100,14 @@ impl Matcher {
 		let handle = self.one_handle.as_ref().unwrap();
 		handle(ele, use_cache)
 	}
+	// get the specificity
+	pub fn specificity(&self) -> Specificity {
+		self
+			.specificity
+			.as_ref()
+			.map(SpecificityOf::resolve)
+			.unwrap_or_default()
+	}
 	// get all handle
 	pub fn get_all_handle(&self) -> &MatchAllHandle {
 		self.all_handle.as_ref().expect("All handle is None")
@@ -334,11 +382,19 @@ impl Rule {
 		// the mutex rules will trigger a dead lock
 		// so there give up, just lost some performance
 		Matcher {
-			all_handle: Some(Box::new(move |eles: &Elements, _| eles.filter(selector))),
+			all_handle: Some(Box::new(move |eles: &Elements, _| {
+				eles.filter_with_type("filter", selector, FilterType::Filter)
+			})),
 			one_handle: None,
+			cache_handle: None,
+			estimate_handle: None,
+			ancestor_key: None,
 			// priority
 			priority: PRIORITY_PSEUDO_SELECTOR,
 			in_cache: false,
+			specificity: None,
+			name: "",
+			source: String::from(selector),
 		}
 	}
 
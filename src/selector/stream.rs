@@ -0,0 +1,551 @@
+/*
+*
+* match the selector over the start tag, end tag and text events without building a tree
+*
+*/
+use super::ast::{CompoundSelector, PseudoArgument, SimpleSelector};
+use super::pattern::Nth;
+use super::{Combinator, Selector};
+use crate::error::Error;
+use crate::interface::IAttrValue;
+use crate::rules::attr::{make_attr_value_handle, AttrValueHandle};
+use crate::rules::class::is_class_matched;
+use crate::rules::name::is_tag_name_matched;
+use std::collections::HashMap;
+
+/// `StreamEvent`
+pub enum StreamEvent<'e> {
+	// the void elements such as `<br>` must set `self_closing`, they have no end tag
+	StartTag {
+		name: &'e str,
+		attrs: Vec<(&'e str, &'e str)>,
+		self_closing: bool,
+	},
+	EndTag(&'e str),
+	Text(&'e str),
+}
+
+/// `StreamMatch`
+/// the matched element, emitted when it's closed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamMatch {
+	pub name: String,
+	pub attrs: Vec<(String, String)>,
+	// the depth of the element, the top level is 0
+	pub depth: usize,
+	// the texts in the element
+	pub text: String,
+}
+
+// the check of a simple selector which can be decided by the start tag,
+// the tag name, class, attribute value and nth position checks share the predicates with the rules,
+// the others differ from the rules: the attribute names are compared ASCII case-insensitively,
+// the id is compared with the `id` attribute instead of `get_element_by_id`
+enum StreamCheck {
+	Name(String),
+	Id(String),
+	Class(String),
+	Attr(String, AttrValueHandle),
+	AttrPrefix(String),
+	Nth {
+		of_type: bool,
+		n: isize,
+		index: isize,
+	},
+	Not(Vec<Vec<StreamCheck>>),
+	Is(Vec<Vec<StreamCheck>>),
+}
+
+// the start tag with its position in the siblings
+struct StartTag<'e> {
+	// the uppercase tag name, like the `tag_name` of the elements
+	name: String,
+	attrs: &'e [(&'e str, &'e str)],
+	position: usize,
+	type_position: usize,
+}
+
+impl<'e> StartTag<'e> {
+	fn get_attribute(&self, name: &str) -> Option<IAttrValue> {
+		self
+			.attrs
+			.iter()
+			.find(|(attr_name, _)| attr_name.eq_ignore_ascii_case(name))
+			.map(|(_, value)| IAttrValue::Value(String::from(*value), None))
+	}
+}
+
+impl StreamCheck {
+	fn is_matched(&self, tag: &StartTag) -> bool {
+		use StreamCheck::*;
+		match self {
+			Name(name) => is_tag_name_matched(&tag.name, name),
+			Id(id) => {
+				matches!(tag.get_attribute("id"), Some(IAttrValue::Value(value, _)) if &value == id)
+			}
+			Class(class_name) => is_class_matched(&tag.get_attribute("class"), class_name),
+			Attr(name, handle) => handle(&tag.get_attribute(name)),
+			AttrPrefix(prefix) => tag
+				.attrs
+				.iter()
+				.any(|(name, _)| name.to_ascii_lowercase().starts_with(prefix)),
+			Nth { of_type, n, index } => {
+				let position = if *of_type {
+					tag.type_position
+				} else {
+					tag.position
+				};
+				super::pattern::Nth::is_position_matched(*n, *index, position)
+			}
+			Not(list) => !is_any_matched(list, tag),
+			Is(list) => is_any_matched(list, tag),
+		}
+	}
+}
+
+fn is_any_matched(list: &[Vec<StreamCheck>], tag: &StartTag) -> bool {
+	list
+		.iter()
+		.any(|checks| checks.iter().all(|check| check.is_matched(tag)))
+}
+
+fn compile_compound(compound: &CompoundSelector) -> Result<Vec<StreamCheck>, String> {
+	use SimpleSelector::*;
+	let mut checks = Vec::with_capacity(compound.0.len());
+	for simple in &compound.0 {
+		let check = match simple {
+			Universal => continue,
+			Type(name) => StreamCheck::Name(name.to_ascii_uppercase()),
+			Id(id) => StreamCheck::Id(id.clone()),
+			Class(class_name) => StreamCheck::Class(class_name.clone()),
+			Attribute {
+				name,
+				operator,
+				value,
+			} => StreamCheck::Attr(
+				name.clone(),
+				make_attr_value_handle(
+					operator
+						.as_ref()
+						.map(|operator| operator.trim_end_matches('='))
+						.unwrap_or(""),
+					value.clone(),
+				),
+			),
+			AttributePrefix(prefix) => StreamCheck::AttrPrefix(prefix.to_ascii_lowercase()),
+			Pseudo { name, argument } => match (name.as_str(), argument) {
+				(":first-child", None) | (":first-of-type", None) => StreamCheck::Nth {
+					of_type: name.ends_with("-of-type"),
+					n: 0,
+					index: 1,
+				},
+				(":nth-child", Some(PseudoArgument::Raw(raw)))
+				| (":nth-of-type", Some(PseudoArgument::Raw(raw))) => {
+					let (n, index) = Nth::parse(raw)
+						.ok_or_else(|| format!("The argument of '{}' is not correct", simple))?;
+					StreamCheck::Nth {
+						of_type: name.ends_with("-of-type"),
+						n,
+						index,
+					}
+				}
+				(":not", Some(PseudoArgument::Selector(list)))
+				| (":is", Some(PseudoArgument::Selector(list)))
+				| (":where", Some(PseudoArgument::Selector(list))) => {
+					let mut compiled = Vec::with_capacity(list.0.len());
+					for complex in &list.0 {
+						match complex.0.as_slice() {
+							[(Combinator::ChildrenAll, compound)] | [(Combinator::Chain, compound)] => {
+								compiled.push(compile_compound(compound)?)
+							}
+							_ => {
+								return Err(format!(
+									"Only the compound selectors in '{}' can be decided in streaming",
+									name
+								))
+							}
+						}
+					}
+					if name == ":not" {
+						StreamCheck::Not(compiled)
+					} else {
+						StreamCheck::Is(compiled)
+					}
+				}
+				_ => {
+					return Err(format!(
+						"The selector '{}' can't be decided when the start tag is met",
+						simple
+					))
+				}
+			},
+			Other { text, .. } => {
+				return Err(format!(
+					"The selector '{}' can't be decided when the start tag is met",
+					text
+				))
+			}
+		};
+		checks.push(check);
+	}
+	Ok(checks)
+}
+
+// the open element
+#[derive(Default)]
+struct OpenElement {
+	name: String,
+	// matched[group][index]: the element matched the compound at index, and the compounds before
+	matched: Vec<Vec<bool>>,
+	// matched by the element or any of its ancestors
+	reached: Vec<Vec<bool>>,
+	child_count: usize,
+	type_counts: HashMap<String, usize>,
+	result: Option<StreamMatch>,
+}
+
+/// `StreamMatcher`
+/// only the descendant and child combinators, `id`, `class`, attributes, `:first-child`, `:nth-child`,
+/// `:first-of-type`, `:nth-of-type` and the `:not`/`:is`/`:where` with them are supported,
+/// the selectors like `:last-child` need the following nodes so they are rejected when created
+pub struct StreamMatcher {
+	groups: Vec<Vec<(Combinator, Vec<StreamCheck>)>>,
+	// the first is the virtual root
+	stack: Vec<OpenElement>,
+}
+
+impl StreamMatcher {
+	/// pub fn `new`
+	pub fn new(selector: &str) -> Result<Self, Error> {
+		let parsed: Selector = selector.parse()?;
+		let mut groups = Vec::with_capacity(parsed.ast().0.len());
+		for complex in &parsed.ast().0 {
+			let mut group = Vec::with_capacity(complex.0.len());
+			for (index, (comb, compound)) in complex.0.iter().enumerate() {
+				let comb = match comb {
+					Combinator::ChildrenAll | Combinator::Chain => Combinator::ChildrenAll,
+					// `> a` only matches the top level elements
+					Combinator::Children => Combinator::Children,
+					_ => {
+						return Err(Error::InvalidSelector {
+							context: String::from(selector),
+							reason: format!(
+								"The combinator before '{}' at {} is not supported in streaming, only descendant and child combinators are supported",
+								compound, index
+							),
+						})
+					}
+				};
+				let checks = compile_compound(compound).map_err(|reason| Error::InvalidSelector {
+					context: String::from(selector),
+					reason,
+				})?;
+				group.push((comb, checks));
+			}
+			groups.push(group);
+		}
+		let root = OpenElement {
+			matched: groups
+				.iter()
+				.map(|group| vec![false; group.len()])
+				.collect(),
+			reached: groups
+				.iter()
+				.map(|group| vec![false; group.len()])
+				.collect(),
+			..Default::default()
+		};
+		Ok(StreamMatcher {
+			groups,
+			stack: vec![root],
+		})
+	}
+
+	// the states of the new element
+	fn open(&mut self, name: &str, attrs: &[(&str, &str)]) {
+		let is_top = self.stack.len() == 1;
+		let depth = self.stack.len() - 1;
+		let parent = self
+			.stack
+			.last_mut()
+			.expect("The stack must have the virtual root");
+		parent.child_count += 1;
+		let type_position = {
+			let count = parent
+				.type_counts
+				.entry(name.to_ascii_lowercase())
+				.or_insert(0);
+			*count += 1;
+			*count
+		};
+		let tag = StartTag {
+			name: name.to_ascii_uppercase(),
+			attrs,
+			position: parent.child_count,
+			type_position,
+		};
+		let mut matched = Vec::with_capacity(self.groups.len());
+		let mut reached = Vec::with_capacity(self.groups.len());
+		let mut is_matched = false;
+		for (group_index, group) in self.groups.iter().enumerate() {
+			let mut cur_matched = vec![false; group.len()];
+			for (index, (comb, checks)) in group.iter().enumerate() {
+				let is_prev_matched = match (index, comb) {
+					(0, Combinator::Children) => is_top,
+					(0, _) => true,
+					(_, Combinator::Children) => parent.matched[group_index][index - 1],
+					_ => parent.reached[group_index][index - 1],
+				};
+				cur_matched[index] = is_prev_matched && checks.iter().all(|check| check.is_matched(&tag));
+			}
+			is_matched = is_matched || cur_matched.last().copied().unwrap_or(false);
+			reached.push(
+				cur_matched
+					.iter()
+					.zip(&parent.reached[group_index])
+					.map(|(cur, prev)| *cur || *prev)
+					.collect(),
+			);
+			matched.push(cur_matched);
+		}
+		let result = if is_matched {
+			Some(StreamMatch {
+				name: String::from(name),
+				attrs: attrs
+					.iter()
+					.map(|(name, value)| (String::from(*name), String::from(*value)))
+					.collect(),
+				depth,
+				text: String::new(),
+			})
+		} else {
+			None
+		};
+		self.stack.push(OpenElement {
+			name: String::from(name),
+			matched,
+			reached,
+			result,
+			..Default::default()
+		});
+	}
+
+	// close the elements after the index
+	fn close_from(&mut self, index: usize) -> Vec<StreamMatch> {
+		let mut result = Vec::new();
+		while self.stack.len() > index {
+			if let Some(OpenElement {
+				result: Some(matched),
+				..
+			}) = self.stack.pop()
+			{
+				result.push(matched);
+			}
+		}
+		result
+	}
+
+	/// pub fn `feed`
+	/// return the matched elements closed by the event, the inner elements are before the outer,
+	/// an end tag closes the unclosed elements in it, an end tag without start tag is ignored
+	pub fn feed(&mut self, event: StreamEvent) -> Vec<StreamMatch> {
+		match event {
+			StreamEvent::StartTag {
+				name,
+				attrs,
+				self_closing,
+			} => {
+				self.open(name, &attrs);
+				if self_closing {
+					return self.close_from(self.stack.len() - 1);
+				}
+				Vec::new()
+			}
+			StreamEvent::EndTag(name) => {
+				let index = self
+					.stack
+					.iter()
+					.skip(1)
+					.rposition(|ele| ele.name.eq_ignore_ascii_case(name));
+				match index {
+					Some(index) => self.close_from(index + 1),
+					None => Vec::new(),
+				}
+			}
+			StreamEvent::Text(content) => {
+				for ele in self.stack.iter_mut() {
+					if let Some(matched) = &mut ele.result {
+						matched.text.push_str(content);
+					}
+				}
+				Vec::new()
+			}
+		}
+	}
+
+	/// pub fn `finish`
+	/// close all the unclosed elements at the end of the stream
+	pub fn finish(&mut self) -> Vec<StreamMatch> {
+		self.close_from(1)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{StreamEvent, StreamMatcher};
+	use crate::interface::{BoxDynElement, IAttrValue};
+	use crate::mock::Doc;
+	use crate::selector::Selector;
+
+	fn run(selector: &str, events: Vec<StreamEvent>) -> Vec<String> {
+		crate::init();
+		let mut matcher = StreamMatcher::new(selector).expect("The selector is not supported");
+		let mut result = Vec::new();
+		for event in events {
+			result.extend(matcher.feed(event));
+		}
+		result.extend(matcher.finish());
+		result
+			.into_iter()
+			.map(|matched| format!("{}:{}", matched.name, matched.text))
+			.collect()
+	}
+
+	fn start<'e>(name: &'e str, attrs: Vec<(&'e str, &'e str)>) -> StreamEvent<'e> {
+		StreamEvent::StartTag {
+			name,
+			attrs,
+			self_closing: false,
+		}
+	}
+
+	#[test]
+	fn test_stream_matcher() {
+		use StreamEvent::*;
+		// <ul class="list"><li>a</li><li id="b">b<span>c</span></li><li>d</ul>
+		let events = || {
+			vec![
+				start("ul", vec![("class", "list")]),
+				start("li", vec![]),
+				Text("a"),
+				EndTag("li"),
+				start("li", vec![("id", "b")]),
+				Text("b"),
+				start("span", vec![]),
+				Text("c"),
+				EndTag("span"),
+				EndTag("li"),
+				start("li", vec![]),
+				Text("d"),
+				EndTag("ul"),
+			]
+		};
+		assert_eq!(
+			run(".list > li:nth-child(odd)", events()),
+			vec!["li:a", "li:d"]
+		);
+		assert_eq!(run("ul li:not(#b)", events()), vec!["li:a", "li:d"]);
+		assert_eq!(run("> ul span, li#b", events()), vec!["span:c", "li:bc"]);
+		assert_eq!(
+			run("li:first-child, [id^=\"b\"] *", events()),
+			vec!["li:a", "span:c"]
+		);
+		crate::init();
+		assert!(StreamMatcher::new("li:last-child").is_err());
+		assert!(StreamMatcher::new("li + li").is_err());
+	}
+
+	// is start tag, the tag name, the attributes
+	type Tag = (bool, String, Vec<(String, String)>);
+
+	// the start tags and end tags of the element and its descendants, in the document order
+	fn collect_tags(ele: &BoxDynElement, tags: &mut Vec<Tag>) {
+		let name = ele.tag_name().to_ascii_lowercase();
+		let attrs = ele
+			.attributes()
+			.into_iter()
+			.map(|(name, value)| match value {
+				IAttrValue::Value(value, _) => (name, value),
+				IAttrValue::True => (name, String::new()),
+			})
+			.collect();
+		tags.push((true, name.clone(), attrs));
+		for child in ele.children().get_ref() {
+			collect_tags(child, tags);
+		}
+		tags.push((false, name, Vec::new()));
+	}
+
+	#[test]
+	fn test_stream_parity() {
+		crate::init();
+		let doc = Doc::parse(
+			r#"<div id="a" class="box main" data-i="0"><ul data-i="1"><li class="item" data-i="2"></li><li id="b" data-i="3" lang="en-US"><span data-i="4"></span><em class="item" data-i="5"></em><span data-i="6"></span></li><li data-i="7"></li><li class="item last" data-i="8"><p data-i="9"></p></li></ul><p data-x="1" data-i="10"></p></div>"#,
+		);
+		let mut tags = Vec::new();
+		for ele in doc.root().children("").get_ref() {
+			collect_tags(ele, &mut tags);
+		}
+		for selector in [
+			"li",
+			"LI",
+			"#b",
+			"#b span",
+			".item",
+			".box > ul > .item",
+			"[lang|=en]",
+			"[class~=\"last\"]",
+			"[^data-x]",
+			"li:nth-child(2n+1)",
+			"li:nth-child(-n+2)",
+			"span:nth-of-type(2)",
+			"li > :first-of-type",
+			":first-child",
+			"li:not(.item, #b)",
+			"div :is(#b, .last) > *",
+			"ul :where(span)",
+		] {
+			let mut matcher = StreamMatcher::new(selector).expect("The selector is not supported");
+			let mut stream_result = Vec::new();
+			for (is_start, name, attrs) in &tags {
+				let event = if *is_start {
+					StreamEvent::StartTag {
+						name,
+						attrs: attrs
+							.iter()
+							.map(|(name, value)| (name.as_str(), value.as_str()))
+							.collect(),
+						self_closing: false,
+					}
+				} else {
+					StreamEvent::EndTag(name)
+				};
+				stream_result.extend(matcher.feed(event));
+			}
+			stream_result.extend(matcher.finish());
+			let mut stream_result: Vec<String> = stream_result
+				.into_iter()
+				.filter_map(|matched| {
+					matched
+						.attrs
+						.into_iter()
+						.find(|(name, _)| name == "data-i")
+						.map(|(_, value)| value)
+				})
+				.collect();
+			stream_result.sort();
+			let parsed: Selector = selector.parse().unwrap();
+			let mut dom_result: Vec<String> = doc
+				.root()
+				.find("*")
+				.get_ref()
+				.iter()
+				.filter(|ele| parsed.is_match(ele))
+				.filter_map(|ele| ele.get_attribute("data-i").map(|value| value.to_string()))
+				.collect();
+			dom_result.sort();
+			assert!(!dom_result.is_empty(), "{}", selector);
+			assert_eq!(stream_result, dom_result, "{}", selector);
+		}
+	}
+}
@@ -0,0 +1,398 @@
+/*
+*
+* inline the `<style>` rules into the matched elements' `style` attributes
+*
+*/
+use super::{Declaration, Declarations};
+use crate::constants::ATTR_STYLE;
+use crate::interface::{BoxDynElement, Elements, IAttrValue};
+use crate::selector::{Selector, Specificity};
+use std::collections::HashMap;
+
+/// `StyleRule`
+/// a qualified rule, the selector is the whole selector list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StyleRule {
+	pub selector: String,
+	pub declarations: Declarations,
+}
+
+/// `StyleSheet`
+/// the style rules and the at-rules that can't be inlined, e.g. `@media`, `@font-face`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StyleSheet {
+	pub rules: Vec<StyleRule>,
+	pub at_rules: Vec<String>,
+}
+
+impl StyleSheet {
+	/// pub fn `parse`
+	pub fn parse(content: &str) -> Self {
+		let chars: Vec<char> = content.chars().collect();
+		let total = chars.len();
+		let mut sheet = StyleSheet::default();
+		let mut index = 0;
+		while index < total {
+			let ch = chars[index];
+			// skip whitespaces and html comment tokens
+			if ch.is_whitespace() || ch == ';' {
+				index += 1;
+				continue;
+			}
+			if chars[index..].starts_with(&['<', '!', '-', '-']) {
+				index += 4;
+				continue;
+			}
+			if chars[index..].starts_with(&['-', '-', '>']) {
+				index += 3;
+				continue;
+			}
+			if chars[index..].starts_with(&['/', '*']) {
+				index = skip_comment(&chars, index);
+				continue;
+			}
+			let (prelude, end, has_block) = read_prelude(&chars, index);
+			if ch == '@' {
+				// keep the at-rule as it is
+				let end = if has_block {
+					skip_block(&chars, end)
+				} else {
+					end + 1
+				};
+				sheet.at_rules.push(
+					chars[index..end.min(total)]
+						.iter()
+						.collect::<String>()
+						.trim()
+						.to_string(),
+				);
+				index = end;
+				continue;
+			}
+			if !has_block {
+				break;
+			}
+			let block_end = skip_block(&chars, end);
+			let selector = prelude.trim();
+			if !selector.is_empty() {
+				let content_end = if chars[block_end - 1] == '}' {
+					block_end - 1
+				} else {
+					block_end
+				};
+				let block: String = chars[end + 1..content_end].iter().collect();
+				sheet.rules.push(StyleRule {
+					selector: selector.to_string(),
+					declarations: Declarations::parse(&block),
+				});
+			}
+			index = block_end;
+		}
+		sheet
+	}
+}
+
+// skip the comment begin at index, return the index after the comment
+fn skip_comment(chars: &[char], index: usize) -> usize {
+	let mut index = index + 2;
+	while index < chars.len() {
+		if chars[index] == '/' && chars[index - 1] == '*' {
+			return index + 1;
+		}
+		index += 1;
+	}
+	chars.len()
+}
+
+// skip the quoted string begin at index, return the index after the string
+fn skip_string(chars: &[char], index: usize) -> usize {
+	let quote = chars[index];
+	let mut index = index + 1;
+	while index < chars.len() {
+		match chars[index] {
+			'\\' => index += 1,
+			ch if ch == quote => return index + 1,
+			_ => {}
+		}
+		index += 1;
+	}
+	chars.len()
+}
+
+// read until the top level '{' or ';', comments are removed from the prelude
+fn read_prelude(chars: &[char], index: usize) -> (String, usize, bool) {
+	let mut prelude = String::with_capacity(20);
+	let mut index = index;
+	while index < chars.len() {
+		match chars[index] {
+			'{' => return (prelude, index, true),
+			';' => return (prelude, index, false),
+			'"' | '\'' => {
+				let end = skip_string(chars, index);
+				prelude.extend(&chars[index..end]);
+				index = end;
+				continue;
+			}
+			'/' if chars.get(index + 1) == Some(&'*') => {
+				index = skip_comment(chars, index);
+				continue;
+			}
+			ch => prelude.push(ch),
+		}
+		index += 1;
+	}
+	(prelude, index, false)
+}
+
+// skip the block begin with '{' at index, return the index after the matched '}'
+fn skip_block(chars: &[char], index: usize) -> usize {
+	let mut depth = 0;
+	let mut index = index;
+	while index < chars.len() {
+		match chars[index] {
+			'{' => depth += 1,
+			'}' => {
+				depth -= 1;
+				if depth == 0 {
+					return index + 1;
+				}
+			}
+			'"' | '\'' => {
+				index = skip_string(chars, index);
+				continue;
+			}
+			'/' if chars.get(index + 1) == Some(&'*') => {
+				index = skip_comment(chars, index);
+				continue;
+			}
+			_ => {}
+		}
+		index += 1;
+	}
+	chars.len()
+}
+
+// split the selector list by the top level ','
+fn split_selector_list(selector: &str) -> Vec<&str> {
+	let mut result = Vec::with_capacity(2);
+	let mut depth = 0;
+	let mut quote: Option<char> = None;
+	let mut start = 0;
+	let mut prev = '\0';
+	for (index, ch) in selector.char_indices() {
+		if let Some(q) = quote {
+			if ch == q && prev != '\\' {
+				quote = None;
+			}
+		} else {
+			match ch {
+				'"' | '\'' => quote = Some(ch),
+				'(' | '[' => depth += 1,
+				')' | ']' => depth -= 1,
+				',' if depth == 0 => {
+					result.push(selector[start..index].trim());
+					start = index + 1;
+				}
+				_ => {}
+			}
+		}
+		prev = ch;
+	}
+	result.push(selector[start..].trim());
+	result
+}
+
+/// `InlineOptions`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InlineOptions {
+	/// remove the `<style>` tags after inlined, the at-rules and the rules can't be inlined are kept in the style tag
+	pub remove_style_tags: bool,
+}
+
+// the matched declarations of an element
+struct MatchedItem<'a, 'r> {
+	ele: BoxDynElement<'r>,
+	declarations: Vec<(Specificity, usize, &'a Declaration)>,
+}
+
+/// pub fn `inline_styles`
+/// apply the rules in the `<style>` tags under the root to the elements' `style` attribute,
+/// the declarations are cascaded by importance, specificity and source order,
+/// the original inline style wins unless the rule's declaration is important
+pub fn inline_styles(root: &Elements, options: InlineOptions) {
+	let mut style_tags = root.find_handle("style");
+	let sheets: Vec<StyleSheet> = style_tags
+		.get_ref()
+		.iter()
+		.map(|ele| {
+			let content = ele
+				.cloned()
+				.into_text()
+				.map(|text| text.text_content().to_string())
+				.unwrap_or_else(|_| ele.text_content().to_string());
+			StyleSheet::parse(&content)
+		})
+		.collect();
+	let mut matched: Vec<MatchedItem> = Vec::with_capacity(root.length());
+	// the index in `matched` by uuid
+	let mut matched_indexs: HashMap<String, usize> = HashMap::new();
+	let mut leftovers: Vec<String> = Vec::with_capacity(sheets.len());
+	let mut order = 0;
+	for sheet in &sheets {
+		let mut leftover: Vec<String> = sheet.at_rules.clone();
+		for rule in &sheet.rules {
+			let mut unmatched_selectors: Vec<&str> = Vec::new();
+			for selector in split_selector_list(&rule.selector) {
+				let query = match selector.parse::<Selector>() {
+					Ok(query) => query,
+					Err(_) => {
+						// unsupported selectors, e.g. `a:hover`
+						unmatched_selectors.push(selector);
+						continue;
+					}
+				};
+				let cur_specificity = query.specificity().into_iter().max().unwrap_or_default();
+				for ele in root.find_selector(&query).into_iter() {
+					let uuid = ele.uuid().map(String::from);
+					let index = match &uuid {
+						Some(uuid) => matched_indexs.get(uuid).copied(),
+						// compare one by one if the element has no uuid
+						None => matched.iter().position(|item| item.ele.is(&ele)),
+					};
+					let index = match index {
+						Some(index) => index,
+						None => {
+							matched.push(MatchedItem {
+								ele,
+								declarations: Vec::with_capacity(5),
+							});
+							let index = matched.len() - 1;
+							if let Some(uuid) = uuid {
+								matched_indexs.insert(uuid, index);
+							}
+							index
+						}
+					};
+					let declarations = &mut matched[index].declarations;
+					for declaration in &rule.declarations {
+						declarations.push((cur_specificity, order, declaration));
+						order += 1;
+					}
+				}
+			}
+			if !unmatched_selectors.is_empty() {
+				leftover.push(format!(
+					"{} {{ {} }}",
+					unmatched_selectors.join(", "),
+					rule.declarations
+				));
+			}
+		}
+		leftovers.push(leftover.join("\n"));
+	}
+	// merge the declarations
+	for MatchedItem {
+		mut ele,
+		mut declarations,
+	} in matched
+	{
+		declarations.sort_by_key(|item| (item.0, item.1));
+		let mut style = Declarations::default();
+		for (_, _, declaration) in declarations {
+			style.add(declaration.clone());
+		}
+		if let Some(IAttrValue::Value(inline_style, _)) = ele.get_attribute(ATTR_STYLE) {
+			for declaration in &Declarations::parse(&inline_style) {
+				style.add(declaration.clone());
+			}
+		}
+		if !style.is_empty() {
+			ele.set_attribute(ATTR_STYLE, Some(style.to_string().as_str()));
+			Elements::sync_index(&ele, false);
+		}
+	}
+	// remove the style tags
+	if options.remove_style_tags {
+		for (ele, leftover) in style_tags.get_mut_ref().iter_mut().zip(leftovers) {
+			if leftover.is_empty() {
+				if let Some(parent) = ele.parent().as_mut() {
+					parent.remove_child(ele.cloned());
+				}
+			} else {
+				ele.set_text(&leftover);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{inline_styles, split_selector_list, InlineOptions, StyleSheet};
+	use crate::mock::Doc;
+
+	#[test]
+	fn test_inline_styles() {
+		crate::init();
+		let doc = Doc::parse(
+			r#"<style>
+			p { color: red; margin: 0 !important }
+			.a { color: blue; padding: 1px }
+			p:hover { color: gray }
+			#b { color: green }
+			.a { padding: 2px }
+			</style><p class="a" id="b" style="margin: 2px; color: black">x</p><p class="a">y</p><p>z</p>"#,
+		);
+		let root = doc.root();
+		inline_styles(
+			&root,
+			InlineOptions {
+				remove_style_tags: true,
+			},
+		);
+		let target = root.find("#b");
+		// the inline style wins unless the rule is important
+		assert_eq!(target.css("color").as_deref(), Some("black"));
+		assert_eq!(target.css("margin").as_deref(), Some("0"));
+		assert!(target.css_map().get("margin").unwrap().important);
+		// the latter rule wins with the same specificity
+		assert_eq!(target.css("padding").as_deref(), Some("2px"));
+		// the higher specificity wins
+		let second = root.find("p").eq(1);
+		assert_eq!(second.css("color").as_deref(), Some("blue"));
+		assert_eq!(root.find("p").eq(2).css("color").as_deref(), Some("red"));
+		// the rules can't be inlined are kept
+		assert_eq!(root.find("style").text().trim(), "p:hover { color: gray }");
+	}
+
+	#[test]
+	fn test_parse_stylesheet() {
+		let sheet = StyleSheet::parse(
+			r#"<!-- @charset "utf-8"; /* a { color: red } */
+			a, b[title="{}"] { color: red; background: url('a}.png') }
+			@media (max-width: 600px) { a { color: blue } }
+			.empty {} -->"#,
+		);
+		assert_eq!(sheet.rules.len(), 2);
+		assert_eq!(sheet.rules[0].selector, r#"a, b[title="{}"]"#);
+		assert_eq!(
+			sheet.rules[0].declarations.get_value("background"),
+			Some("url('a}.png')")
+		);
+		assert!(sheet.rules[1].declarations.is_empty());
+		assert_eq!(
+			sheet.at_rules,
+			vec![
+				r#"@charset "utf-8";"#,
+				"@media (max-width: 600px) { a { color: blue } }"
+			]
+		);
+	}
+
+	#[test]
+	fn test_split_selector_list() {
+		assert_eq!(
+			split_selector_list("a, b[title=','], :not(c, d)"),
+			vec!["a", "b[title=',']", ":not(c, d)"]
+		);
+	}
+}
@@ -0,0 +1,266 @@
+/*
+*
+* style declarations: `color: red; margin: 0 !important`
+* https://www.w3.org/TR/css-syntax-3/#parse-list-of-declarations
+*/
+use std::fmt;
+
+pub mod inliner;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Declaration {
+	pub name: String,
+	pub value: String,
+	pub important: bool,
+}
+
+impl Declaration {
+	/// pub fn `new`
+	/// the property name is case-insensitive except for custom properties `--*`
+	pub fn new(name: &str, value: &str, important: bool) -> Self {
+		Declaration {
+			name: to_property_name(name),
+			value: value.trim().to_string(),
+			important,
+		}
+	}
+}
+
+impl fmt::Display for Declaration {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}: {}", self.name, self.value)?;
+		if self.important {
+			f.write_str(" !important")?;
+		}
+		Ok(())
+	}
+}
+
+// lowercase the property name if not a custom property
+fn to_property_name(name: &str) -> String {
+	let name = name.trim();
+	if name.starts_with("--") {
+		name.to_string()
+	} else {
+		name.to_ascii_lowercase()
+	}
+}
+
+/// pub fn `split_important`
+/// split the `!important` flag from the value
+pub fn split_important(value: &str) -> (&str, bool) {
+	let value = value.trim_end();
+	let lower = value.to_ascii_lowercase();
+	if lower.ends_with("important") {
+		let rest = value[..value.len() - 9].trim_end();
+		if let Some(rest) = rest.strip_suffix('!') {
+			return (rest.trim_end(), true);
+		}
+	}
+	(value, false)
+}
+
+// split the content by the top level separator, skip the quotes, parentheses and comments
+fn split_top_level(content: &str, separator: char, limit: usize) -> Vec<String> {
+	let mut result: Vec<String> = Vec::with_capacity(5);
+	let mut cur = String::with_capacity(content.len());
+	let mut chars = content.chars().peekable();
+	let mut quote: Option<char> = None;
+	let mut depth = 0;
+	while let Some(ch) = chars.next() {
+		if ch == '\\' {
+			cur.push(ch);
+			if let Some(next) = chars.next() {
+				cur.push(next);
+			}
+			continue;
+		}
+		if let Some(q) = quote {
+			if ch == q {
+				quote = None;
+			}
+			cur.push(ch);
+			continue;
+		}
+		match ch {
+			'"' | '\'' => quote = Some(ch),
+			'(' | '[' | '{' => depth += 1,
+			')' | ']' | '}' if depth > 0 => depth -= 1,
+			'/' if chars.peek() == Some(&'*') => {
+				// comments
+				chars.next();
+				let mut prev = '\0';
+				for ch in chars.by_ref() {
+					if prev == '*' && ch == '/' {
+						break;
+					}
+					prev = ch;
+				}
+				continue;
+			}
+			_ if ch == separator && depth == 0 && result.len() + 1 < limit => {
+				result.push(cur);
+				cur = String::with_capacity(content.len());
+				continue;
+			}
+			_ => {}
+		}
+		cur.push(ch);
+	}
+	result.push(cur);
+	result
+}
+
+/// `Declarations`
+/// the ordered declarations of a style attribute or a style rule
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Declarations(Vec<Declaration>);
+
+impl Declarations {
+	/// pub fn `parse`
+	/// the invalid declarations are ignored,
+	/// the repeated property keeps the latter one unless only the former is important
+	pub fn parse(content: &str) -> Self {
+		let mut declarations = Declarations::default();
+		for item in split_top_level(content, ';', usize::MAX) {
+			let pair = split_top_level(&item, ':', 2);
+			if pair.len() != 2 {
+				continue;
+			}
+			let name = pair[0].trim();
+			let (value, important) = split_important(&pair[1]);
+			let value = value.trim();
+			if name.is_empty() || value.is_empty() {
+				continue;
+			}
+			declarations.add(Declaration::new(name, value, important));
+		}
+		declarations
+	}
+
+	/// pub fn `add`
+	/// append the declaration with the cascade of the same property
+	pub fn add(&mut self, declaration: Declaration) {
+		if let Some(index) = self.position(&declaration.name) {
+			if self.0[index].important && !declaration.important {
+				return;
+			}
+			self.0.remove(index);
+		}
+		self.0.push(declaration);
+	}
+
+	fn position(&self, name: &str) -> Option<usize> {
+		let name = to_property_name(name);
+		self.0.iter().position(|item| item.name == name)
+	}
+
+	/// pub fn `get`
+	pub fn get(&self, name: &str) -> Option<&Declaration> {
+		self.position(name).map(|index| &self.0[index])
+	}
+
+	/// pub fn `get_value`
+	pub fn get_value(&self, name: &str) -> Option<&str> {
+		self.get(name).map(|item| item.value.as_str())
+	}
+
+	/// pub fn `set`
+	/// replace the value in place if the property exists, otherwise append it
+	pub fn set(&mut self, name: &str, value: &str, important: bool) {
+		let declaration = Declaration::new(name, value, important);
+		if let Some(index) = self.position(name) {
+			self.0[index] = declaration;
+		} else {
+			self.0.push(declaration);
+		}
+	}
+
+	/// pub fn `remove`
+	pub fn remove(&mut self, name: &str) -> Option<Declaration> {
+		self.position(name).map(|index| self.0.remove(index))
+	}
+
+	/// pub fn `iter`
+	pub fn iter(&self) -> std::slice::Iter<'_, Declaration> {
+		self.0.iter()
+	}
+
+	/// pub fn `len`
+	pub fn len(&self) -> usize {
+		self.0.len()
+	}
+
+	/// pub fn `is_empty`
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+}
+
+impl fmt::Display for Declarations {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for (index, declaration) in self.0.iter().enumerate() {
+			if index > 0 {
+				f.write_str("; ")?;
+			}
+			write!(f, "{}", declaration)?;
+		}
+		Ok(())
+	}
+}
+
+impl<'a> IntoIterator for &'a Declarations {
+	type Item = &'a Declaration;
+	type IntoIter = std::slice::Iter<'a, Declaration>;
+	fn into_iter(self) -> Self::IntoIter {
+		self.0.iter()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{split_important, Declarations};
+
+	#[test]
+	fn test_parse_declarations() {
+		let style = Declarations::parse(
+			r#"COLOR: red; background: url("a;b.png") no-repeat /* c: d; */; --Main-Color: #fff;
+			margin: 0 ! IMPORTANT; font-family: 'a:b', serif;;invalid; :empty; width:"#,
+		);
+		assert_eq!(style.len(), 5);
+		assert_eq!(style.get_value("color"), Some("red"));
+		assert_eq!(
+			style.get_value("background"),
+			Some(r#"url("a;b.png") no-repeat"#)
+		);
+		assert_eq!(style.get_value("--Main-Color"), Some("#fff"));
+		assert_eq!(style.get_value("--main-color"), None);
+		assert!(style.get("margin").unwrap().important);
+		assert_eq!(style.get_value("font-family"), Some("'a:b', serif"));
+		assert_eq!(
+			style.to_string(),
+			r#"color: red; background: url("a;b.png") no-repeat; --Main-Color: #fff; margin: 0 !important; font-family: 'a:b', serif"#
+		);
+		// repeated properties
+		let style = Declarations::parse(
+			"margin: 0; color: red; margin: 1px; color: blue !important; color: green",
+		);
+		assert_eq!(style.to_string(), "margin: 1px; color: blue !important");
+	}
+
+	#[test]
+	fn test_modify_declarations() {
+		let mut style = Declarations::parse("color: red; margin: 0");
+		style.set("Color", "blue", true);
+		style.set("padding", "1px", false);
+		assert_eq!(
+			style.to_string(),
+			"color: blue !important; margin: 0; padding: 1px"
+		);
+		assert!(style.remove("margin").is_some());
+		assert!(style.remove("margin").is_none());
+		assert_eq!(style.to_string(), "color: blue !important; padding: 1px");
+		assert_eq!(split_important("red!important"), ("red", true));
+		assert_eq!(split_important("unimportant"), ("unimportant", false));
+	}
+}
@@ -1,5 +1,13 @@
+use lazy_static::lazy_static;
+use regex::Regex;
 use std::error::Error;
 
+lazy_static! {
+	// https://tools.ietf.org/html/rfc3986#appendix-B
+	static ref URL_PARTS: Regex =
+		Regex::new(r"^(?:([^:/?#]+):)?(?://([^/?#]*))?([^?#]*)(?:\?([^#]*))?(?:#(.*))?$").unwrap();
+}
+
 pub fn to_static_str(content: String) -> &'static str {
 	Box::leak(content.into_boxed_str())
 }
@@ -143,9 +151,378 @@ pub fn get_class_list(v: &str) -> Vec<&str> {
 	}
 }
 
+/// pub fn `unescape_css`
+/// unescape the css string value, e.g. `\"` to `"`, `\31 ` to `1`
+pub fn unescape_css(v: &str) -> String {
+	let mut result = String::with_capacity(v.len());
+	let mut chars = v.chars().peekable();
+	while let Some(ch) = chars.next() {
+		if ch != '\\' {
+			result.push(ch);
+			continue;
+		}
+		let mut hex = String::with_capacity(6);
+		while hex.len() < 6 {
+			match chars.peek() {
+				Some(next) if next.is_ascii_hexdigit() => {
+					hex.push(*next);
+					chars.next();
+				}
+				_ => break,
+			}
+		}
+		if hex.is_empty() {
+			// escaped character, a backslash at the end is kept
+			result.push(chars.next().unwrap_or('\\'));
+		} else {
+			// hex escape, followed by an optional whitespace
+			let code = u32::from_str_radix(&hex, 16).unwrap_or(0);
+			result.push(match std::char::from_u32(code) {
+				Some(ch) if code != 0 => ch,
+				_ => '\u{FFFD}',
+			});
+			if chars.peek().is_some_and(|next| next.is_ascii_whitespace()) {
+				chars.next();
+			}
+		}
+	}
+	result
+}
+
+struct UrlParts<'a> {
+	scheme: Option<&'a str>,
+	authority: Option<&'a str>,
+	path: &'a str,
+	query: Option<&'a str>,
+	fragment: Option<&'a str>,
+}
+
+fn split_url(url: &str) -> UrlParts<'_> {
+	let caps = URL_PARTS
+		.captures(url)
+		.expect("The url regex matches any string");
+	let part = |index: usize| caps.get(index).map(|m| m.as_str());
+	UrlParts {
+		scheme: part(1),
+		authority: part(2),
+		path: part(3).unwrap_or(""),
+		query: part(4),
+		fragment: part(5),
+	}
+}
+
+// https://tools.ietf.org/html/rfc3986#section-5.2.4
+fn remove_dot_segments(path: &str) -> String {
+	let mut output: Vec<&str> = Vec::with_capacity(5);
+	let segments: Vec<&str> = path.split('/').collect();
+	let total = segments.len();
+	for (index, &segment) in segments.iter().enumerate() {
+		let is_last = index == total - 1;
+		match segment {
+			"." | ".." => {
+				if segment == ".." && output.len() > 1 {
+					output.pop();
+				}
+				// keep the trailing slash
+				if is_last {
+					output.push("");
+				}
+			}
+			_ => output.push(segment),
+		}
+	}
+	let result = output.join("/");
+	if path.starts_with('/') && !result.starts_with('/') {
+		return format!("/{}", result);
+	}
+	result
+}
+
+/**
+ * resolve the url relative to the base url
+ * https://tools.ietf.org/html/rfc3986#section-5.2.2
+*/
+pub fn resolve_url(base: &str, url: &str) -> String {
+	let base = split_url(base.trim());
+	let url = split_url(url.trim());
+	let (scheme, authority, path, query) = if url.scheme.is_some() {
+		(
+			url.scheme,
+			url.authority,
+			remove_dot_segments(url.path),
+			url.query,
+		)
+	} else if url.authority.is_some() {
+		(
+			base.scheme,
+			url.authority,
+			remove_dot_segments(url.path),
+			url.query,
+		)
+	} else if url.path.is_empty() {
+		(
+			base.scheme,
+			base.authority,
+			String::from(base.path),
+			url.query.or(base.query),
+		)
+	} else if url.path.starts_with('/') {
+		(
+			base.scheme,
+			base.authority,
+			remove_dot_segments(url.path),
+			url.query,
+		)
+	} else {
+		// merge the paths
+		let merged = if base.authority.is_some() && base.path.is_empty() {
+			format!("/{}", url.path)
+		} else {
+			let dir = base.path.rfind('/').map(|i| &base.path[..=i]).unwrap_or("");
+			format!("{}{}", dir, url.path)
+		};
+		(
+			base.scheme,
+			base.authority,
+			remove_dot_segments(&merged),
+			url.query,
+		)
+	};
+	let mut result = String::with_capacity(50);
+	if let Some(scheme) = scheme {
+		result.push_str(scheme);
+		result.push(':');
+	}
+	if let Some(authority) = authority {
+		result.push_str("//");
+		result.push_str(authority);
+	}
+	result.push_str(&path);
+	if let Some(query) = query {
+		result.push('?');
+		result.push_str(query);
+	}
+	if let Some(fragment) = url.fragment {
+		result.push('#');
+		result.push_str(fragment);
+	}
+	result
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SrcsetCandidate {
+	pub url: String,
+	pub width: Option<u32>,
+	pub height: Option<u32>,
+	pub density: Option<f64>,
+}
+
+/**
+ * parse the `srcset` attribute value
+ * https://html.spec.whatwg.org/multipage/images.html#parse-a-srcset-attribute
+*/
+pub fn parse_srcset(content: &str) -> Result<Vec<SrcsetCandidate>, String> {
+	let chars: Vec<char> = content.chars().collect();
+	let total = chars.len();
+	let mut index = 0;
+	let mut result: Vec<SrcsetCandidate> = Vec::with_capacity(3);
+	loop {
+		// skip whitespaces and commas
+		while index < total && (chars[index].is_ascii_whitespace() || chars[index] == ',') {
+			index += 1;
+		}
+		if index >= total {
+			break;
+		}
+		let start = index;
+		while index < total && !chars[index].is_ascii_whitespace() {
+			index += 1;
+		}
+		let mut url = chars[start..index].iter().collect::<String>();
+		let mut candidate = SrcsetCandidate::default();
+		if url.ends_with(',') {
+			// no descriptors
+			url = String::from(url.trim_end_matches(','));
+		} else {
+			// collect descriptors until a comma not in parens
+			let start = index;
+			let mut in_parens = false;
+			while index < total {
+				let ch = chars[index];
+				if ch == '(' {
+					in_parens = true;
+				} else if ch == ')' {
+					in_parens = false;
+				} else if ch == ',' && !in_parens {
+					break;
+				}
+				index += 1;
+			}
+			let descriptors = chars[start..index].iter().collect::<String>();
+			for descriptor in descriptors.split_ascii_whitespace() {
+				// the unit may be a multibyte char
+				let (unit_index, _) = descriptor
+					.char_indices()
+					.last()
+					.expect("The descriptor is not empty");
+				let (value, unit) = descriptor.split_at(unit_index);
+				let wrong_descriptor = || format!("Wrong descriptor '{}' of '{}'", descriptor, url);
+				match unit {
+					"w" if candidate.width.is_none() && candidate.density.is_none() => {
+						let width = value.parse::<u32>().map_err(|_| wrong_descriptor())?;
+						if width == 0 {
+							return Err(wrong_descriptor());
+						}
+						candidate.width = Some(width);
+					}
+					"h" if candidate.height.is_none() && candidate.density.is_none() => {
+						let height = value.parse::<u32>().map_err(|_| wrong_descriptor())?;
+						if height == 0 {
+							return Err(wrong_descriptor());
+						}
+						candidate.height = Some(height);
+					}
+					"x"
+						if candidate.density.is_none()
+							&& candidate.width.is_none()
+							&& candidate.height.is_none() =>
+					{
+						let density = value.parse::<f64>().map_err(|_| wrong_descriptor())?;
+						if !density.is_finite() || density <= 0.0 {
+							return Err(wrong_descriptor());
+						}
+						candidate.density = Some(density);
+					}
+					_ => return Err(wrong_descriptor()),
+				}
+			}
+			// the height descriptor must use with a width descriptor
+			if candidate.height.is_some() && candidate.width.is_none() {
+				return Err(format!("Height descriptor without width of '{}'", url));
+			}
+		}
+		candidate.url = url;
+		result.push(candidate);
+	}
+	Ok(result)
+}
+
+/**
+ * get the attribute names from the start tag of the html
+ * https://html.spec.whatwg.org/multipage/parsing.html#before-attribute-name-state
+*/
+pub fn get_start_tag_attr_names(html: &str) -> Vec<String> {
+	let mut names: Vec<String> = Vec::with_capacity(5);
+	// scan the chars lazily, stop at the end of the start tag
+	let mut chars = html.trim_start().chars().peekable();
+	if chars.next() != Some('<') {
+		return names;
+	}
+	// skip the tag name
+	while let Some(&ch) = chars.peek() {
+		if ch.is_ascii_whitespace() || ch == '/' || ch == '>' {
+			break;
+		}
+		chars.next();
+	}
+	while let Some(ch) = chars.next() {
+		if ch.is_ascii_whitespace() || ch == '/' {
+			continue;
+		}
+		if ch == '>' {
+			break;
+		}
+		// attribute name, allow the first character is '='
+		let mut name = String::with_capacity(10);
+		name.push(ch.to_ascii_lowercase());
+		while let Some(&ch) = chars.peek() {
+			if ch.is_ascii_whitespace() || ['/', '>', '='].contains(&ch) {
+				break;
+			}
+			name.push(ch.to_ascii_lowercase());
+			chars.next();
+		}
+		if !names.contains(&name) {
+			names.push(name);
+		}
+		// skip the whitespaces before '='
+		while matches!(chars.peek(), Some(ch) if ch.is_ascii_whitespace()) {
+			chars.next();
+		}
+		if chars.peek() == Some(&'=') {
+			chars.next();
+			while matches!(chars.peek(), Some(ch) if ch.is_ascii_whitespace()) {
+				chars.next();
+			}
+			match chars.peek() {
+				Some(&quote) if quote == '"' || quote == '\'' => {
+					chars.next();
+					for ch in chars.by_ref() {
+						if ch == quote {
+							break;
+						}
+					}
+				}
+				_ => {
+					while let Some(&ch) = chars.peek() {
+						if ch.is_ascii_whitespace() || ch == '>' {
+							break;
+						}
+						chars.next();
+					}
+				}
+			}
+		}
+	}
+	names
+}
+
+/**
+ * the dataset name of the `data-*` attribute, `data-foo-bar` => `fooBar`
+ * https://html.spec.whatwg.org/multipage/dom.html#dom-dataset
+*/
+pub fn to_dataset_name(attr_name: &str) -> Option<String> {
+	let name = attr_name.strip_prefix("data-")?;
+	let mut result = String::with_capacity(name.len());
+	let mut chars = name.chars().peekable();
+	while let Some(ch) = chars.next() {
+		if ch == '-' {
+			if let Some(next) = chars.peek() {
+				if next.is_ascii_lowercase() {
+					result.push(next.to_ascii_uppercase());
+					chars.next();
+					continue;
+				}
+			}
+		}
+		result.push(ch);
+	}
+	Some(result)
+}
+
+/**
+ * the `data-*` attribute name of the dataset name, `fooBar` => `data-foo-bar`
+*/
+pub fn to_data_attr_name(name: &str) -> String {
+	let mut result = String::with_capacity(name.len() + 8);
+	result.push_str("data-");
+	for ch in name.chars() {
+		if ch.is_ascii_uppercase() {
+			result.push('-');
+			result.push(ch.to_ascii_lowercase());
+		} else {
+			result.push(ch);
+		}
+	}
+	result
+}
+
 #[cfg(test)]
 mod test {
-	use super::{divide_isize, RoundType};
+	use super::{
+		divide_isize, get_start_tag_attr_names, parse_srcset, resolve_url, to_data_attr_name,
+		to_dataset_name, unescape_css, RoundType,
+	};
 	#[test]
 	fn test_divide_isize() {
 		// round
@@ -212,4 +589,93 @@ mod test {
 		assert_eq!(divide_isize(-5, 4, RoundType::Ceil), -1);
 		assert_eq!(divide_isize(-4, 4, RoundType::Ceil), -1);
 	}
+	#[test]
+	fn test_resolve_url() {
+		let base = "http://a/b/c/d;p?q";
+		// https://tools.ietf.org/html/rfc3986#section-5.4
+		assert_eq!(resolve_url(base, "g:h"), "g:h");
+		assert_eq!(resolve_url(base, "g"), "http://a/b/c/g");
+		assert_eq!(resolve_url(base, "./g"), "http://a/b/c/g");
+		assert_eq!(resolve_url(base, "g/"), "http://a/b/c/g/");
+		assert_eq!(resolve_url(base, "/g"), "http://a/g");
+		assert_eq!(resolve_url(base, "//g"), "http://g");
+		assert_eq!(resolve_url(base, "?y"), "http://a/b/c/d;p?y");
+		assert_eq!(resolve_url(base, "g?y"), "http://a/b/c/g?y");
+		assert_eq!(resolve_url(base, "#s"), "http://a/b/c/d;p?q#s");
+		assert_eq!(resolve_url(base, "g#s"), "http://a/b/c/g#s");
+		assert_eq!(resolve_url(base, ""), "http://a/b/c/d;p?q");
+		assert_eq!(resolve_url(base, "."), "http://a/b/c/");
+		assert_eq!(resolve_url(base, "./"), "http://a/b/c/");
+		assert_eq!(resolve_url(base, ".."), "http://a/b/");
+		assert_eq!(resolve_url(base, "../"), "http://a/b/");
+		assert_eq!(resolve_url(base, "../g"), "http://a/b/g");
+		assert_eq!(resolve_url(base, "../.."), "http://a/");
+		assert_eq!(resolve_url(base, "../../g"), "http://a/g");
+		assert_eq!(resolve_url(base, "../../../g"), "http://a/g");
+		assert_eq!(resolve_url(base, "/./g"), "http://a/g");
+		assert_eq!(resolve_url(base, "g."), "http://a/b/c/g.");
+		assert_eq!(resolve_url(base, "./../g"), "http://a/b/g");
+		assert_eq!(resolve_url(base, "g/../h"), "http://a/b/c/h");
+		assert_eq!(resolve_url("http://a", "g"), "http://a/g");
+	}
+	#[test]
+	fn test_parse_srcset() {
+		let candidates = parse_srcset("a.png, b.png 2x,c.png 1.5x").unwrap();
+		assert_eq!(candidates.len(), 3);
+		assert_eq!(candidates[0].url, "a.png");
+		assert_eq!(candidates[0].density, None);
+		assert_eq!(candidates[1].url, "b.png");
+		assert_eq!(candidates[1].density, Some(2.0));
+		assert_eq!(candidates[2].density, Some(1.5));
+		let candidates = parse_srcset(" small.jpg 480w,large.jpg  1080w 720h ").unwrap();
+		assert_eq!(candidates[0].width, Some(480));
+		assert_eq!(candidates[1].url, "large.jpg");
+		assert_eq!(candidates[1].height, Some(720));
+		assert!(parse_srcset("a.png 2y").is_err());
+		assert!(parse_srcset("a.png 100w 2x").is_err());
+		assert!(parse_srcset("a.png 100h").is_err());
+		assert!(parse_srcset("a.png 1×").is_err());
+		assert!(parse_srcset("a.png ×").is_err());
+		assert!(parse_srcset("a.png infx").is_err());
+		assert!(parse_srcset("a.png NaNx").is_err());
+		assert!(parse_srcset("a.png 0x").is_err());
+	}
+	#[test]
+	fn test_get_start_tag_attr_names() {
+		assert_eq!(
+			get_start_tag_attr_names(r#"<div id="a" data-foo='b > c' Checked class=d/>"#),
+			vec!["id", "data-foo", "checked", "class"]
+		);
+		assert_eq!(
+			get_start_tag_attr_names("<input disabled>text</input>"),
+			vec!["disabled"]
+		);
+		assert!(get_start_tag_attr_names("<br/>").is_empty());
+		assert_eq!(
+			get_start_tag_attr_names(r#"<p a = "1"b=2 c='3'><i d></i></p>"#),
+			vec!["a", "b", "c"]
+		);
+		assert!(get_start_tag_attr_names("text").is_empty());
+	}
+	#[test]
+	fn test_dataset_name() {
+		assert_eq!(
+			to_dataset_name("data-foo-bar"),
+			Some(String::from("fooBar"))
+		);
+		assert_eq!(to_dataset_name("data-foo-1"), Some(String::from("foo-1")));
+		assert_eq!(to_dataset_name("id"), None);
+		assert_eq!(to_data_attr_name("fooBar"), "data-foo-bar");
+		assert_eq!(to_data_attr_name("foo"), "data-foo");
+	}
+
+	#[test]
+	fn test_unescape_css() {
+		assert_eq!(unescape_css("abc"), "abc");
+		assert_eq!(unescape_css(r#"a\"b\'c"#), "a\"b'c");
+		assert_eq!(unescape_css(r"a\\b"), r"a\b");
+		assert_eq!(unescape_css(r"\31 0"), "10");
+		assert_eq!(unescape_css(r"\0"), "\u{FFFD}");
+		assert_eq!(unescape_css("a\\"), "a\\");
+	}
 }
@@ -0,0 +1,860 @@
+/*
+*
+* XPath 1.0 evaluator over the node traits
+*
+*/
+use super::parser::{number_to_string, Axis, BinaryOp, Expr, NodeTest, PathStart, Step};
+use crate::error::Error;
+use crate::interface::{BoxDynElement, BoxDynNode, BoxDynText, IEnumTyped, INodeType};
+
+/// `XPathNode`
+/// the nodes in the XPath data model, the root node is the element without parent
+pub enum XPathNode<'a> {
+	Element(BoxDynElement<'a>),
+	Text(BoxDynText<'a>),
+	Attribute {
+		owner: BoxDynElement<'a>,
+		index: usize,
+		name: String,
+		value: String,
+	},
+	// comments and other nodes
+	Other(BoxDynNode<'a>),
+}
+
+impl<'a> Clone for XPathNode<'a> {
+	fn clone(&self) -> Self {
+		match self {
+			XPathNode::Element(ele) => XPathNode::Element(ele.cloned()),
+			XPathNode::Text(text) => XPathNode::Text(
+				text
+					.clone_node()
+					.typed()
+					.into_text()
+					.expect("Text node must can use 'into_text'."),
+			),
+			XPathNode::Attribute {
+				owner,
+				index,
+				name,
+				value,
+			} => XPathNode::Attribute {
+				owner: owner.cloned(),
+				index: *index,
+				name: name.clone(),
+				value: value.clone(),
+			},
+			XPathNode::Other(node) => XPathNode::Other(node.clone_node()),
+		}
+	}
+}
+
+impl<'a> XPathNode<'a> {
+	// the node from the child nodes
+	fn from_node(node: BoxDynNode<'a>) -> Option<Self> {
+		match node.node_type() {
+			INodeType::Element | INodeType::Document | INodeType::DocumentFragement | INodeType::Text => {
+				match node.typed() {
+					IEnumTyped::Element(ele) => Some(XPathNode::Element(ele)),
+					IEnumTyped::Text(text) => Some(XPathNode::Text(text)),
+					IEnumTyped::UncareNode(_) => None,
+				}
+			}
+			_ => Some(XPathNode::Other(node)),
+		}
+	}
+
+	/// pub fn `node_type`
+	pub fn node_type(&self) -> INodeType {
+		match self {
+			XPathNode::Element(ele) => ele.node_type(),
+			XPathNode::Text(text) => text.node_type(),
+			XPathNode::Attribute { .. } => INodeType::Other,
+			XPathNode::Other(node) => node.node_type(),
+		}
+	}
+
+	// the element node, not the root
+	fn is_element(&self) -> bool {
+		matches!(self, XPathNode::Element(ele) if ele.node_type().is_element())
+	}
+
+	/// pub fn `parent`
+	/// the parent of an attribute is its owner element
+	pub fn parent(&self) -> Option<XPathNode<'a>> {
+		match self {
+			XPathNode::Element(ele) => ele.parent(),
+			XPathNode::Text(text) => text.parent(),
+			XPathNode::Attribute { owner, .. } => Some(owner.cloned()),
+			XPathNode::Other(node) => node.parent(),
+		}
+		.map(XPathNode::Element)
+	}
+
+	// the element of the node or its parent
+	fn element(&self) -> Option<BoxDynElement<'a>> {
+		match self {
+			XPathNode::Element(ele) => Some(ele.cloned()),
+			_ => self.parent().and_then(|parent| parent.element()),
+		}
+	}
+
+	fn root(&self) -> Option<XPathNode<'a>> {
+		self.element().map(|ele| XPathNode::Element(ele.root()))
+	}
+
+	fn child_nodes(&self) -> Vec<XPathNode<'a>> {
+		match self {
+			XPathNode::Element(ele) => ele
+				.child_nodes()
+				.into_iter()
+				.filter_map(XPathNode::from_node)
+				.collect(),
+			_ => Vec::new(),
+		}
+	}
+
+	fn attributes(&self) -> Vec<XPathNode<'a>> {
+		match self {
+			XPathNode::Element(ele) if ele.node_type().is_element() => ele
+				.attributes()
+				.into_iter()
+				.enumerate()
+				.map(|(index, (name, value))| XPathNode::Attribute {
+					owner: ele.cloned(),
+					index,
+					name,
+					value: value.to_string(),
+				})
+				.collect(),
+			_ => Vec::new(),
+		}
+	}
+
+	// the index in the parent's child nodes
+	fn index(&self) -> usize {
+		match self {
+			XPathNode::Element(ele) => ele.index(),
+			XPathNode::Text(text) => text.index(),
+			XPathNode::Attribute { index, .. } => *index,
+			XPathNode::Other(node) => node.index(),
+		}
+	}
+
+	/// pub fn `order_key`
+	/// the key of the document order, the attributes are after their owner and before its children,
+	/// the key is unique only if `INodeTrait::index` is the index in the parent's child nodes
+	pub fn order_key(&self) -> Vec<usize> {
+		let mut key = match self.parent() {
+			Some(parent) => parent.order_key(),
+			None => return Vec::new(),
+		};
+		if let XPathNode::Attribute { index, .. } = self {
+			key.push(0);
+			key.push(index + 1);
+		} else {
+			key.push(self.index() + 1);
+		}
+		key
+	}
+
+	/// pub fn `string_value`
+	pub fn string_value(&self) -> String {
+		match self {
+			XPathNode::Element(ele) => ele.text_content().to_string(),
+			XPathNode::Text(text) => text.text_content().to_string(),
+			XPathNode::Attribute { value, .. } => value.clone(),
+			XPathNode::Other(node) => node.text_content().to_string(),
+		}
+	}
+
+	/// pub fn `name`
+	/// the lowercase tag name of the element, or the attribute name
+	pub fn name(&self) -> String {
+		match self {
+			XPathNode::Element(ele) if ele.node_type().is_element() => {
+				ele.tag_name().to_ascii_lowercase()
+			}
+			XPathNode::Attribute { name, .. } => name.clone(),
+			_ => String::new(),
+		}
+	}
+}
+
+/// sort the nodes in document order and remove the duplicates
+pub fn sort_and_unique(nodes: Vec<XPathNode>) -> Vec<XPathNode> {
+	let mut keyed: Vec<(Vec<usize>, XPathNode)> = nodes
+		.into_iter()
+		.map(|node| (node.order_key(), node))
+		.collect();
+	keyed.sort_by(|a, b| a.0.cmp(&b.0));
+	keyed.dedup_by(|a, b| a.0 == b.0);
+	keyed.into_iter().map(|(_, node)| node).collect()
+}
+
+/// `XPathValue`
+pub enum XPathValue<'a> {
+	NodeSet(Vec<XPathNode<'a>>),
+	Boolean(bool),
+	Number(f64),
+	String(String),
+}
+
+// https://www.w3.org/TR/1999/REC-xpath-19991116/#function-number
+fn string_to_number(content: &str) -> f64 {
+	let content = content.trim_matches(|ch| matches!(ch, ' ' | '\t' | '\r' | '\n'));
+	let digits = content.strip_prefix('-').unwrap_or(content);
+	let mut parts = digits.splitn(2, '.');
+	let int_part = parts.next().unwrap_or("");
+	let dec_part = parts.next();
+	let is_digits = |part: &str| part.chars().all(|ch| ch.is_ascii_digit());
+	let is_valid = is_digits(int_part)
+		&& dec_part.map(is_digits).unwrap_or(true)
+		&& !(int_part.is_empty() && dec_part.map(|part| part.is_empty()).unwrap_or(true));
+	if is_valid {
+		content.parse::<f64>().unwrap_or(f64::NAN)
+	} else {
+		f64::NAN
+	}
+}
+
+impl<'a> XPathValue<'a> {
+	/// pub fn `boolean`
+	pub fn boolean(&self) -> bool {
+		match self {
+			XPathValue::NodeSet(nodes) => !nodes.is_empty(),
+			XPathValue::Boolean(value) => *value,
+			XPathValue::Number(num) => !(*num == 0.0 || num.is_nan()),
+			XPathValue::String(value) => !value.is_empty(),
+		}
+	}
+	/// pub fn `number`
+	pub fn number(&self) -> f64 {
+		match self {
+			XPathValue::Boolean(value) => {
+				if *value {
+					1.0
+				} else {
+					0.0
+				}
+			}
+			XPathValue::Number(num) => *num,
+			_ => string_to_number(&self.string()),
+		}
+	}
+	/// pub fn `string`
+	pub fn string(&self) -> String {
+		match self {
+			XPathValue::NodeSet(nodes) => nodes
+				.first()
+				.map(|node| node.string_value())
+				.unwrap_or_default(),
+			XPathValue::Boolean(value) => value.to_string(),
+			XPathValue::Number(num) => number_to_string(*num),
+			XPathValue::String(value) => value.clone(),
+		}
+	}
+}
+
+// the evaluation context
+pub(crate) struct Context<'a> {
+	pub node: XPathNode<'a>,
+	pub position: usize,
+	pub size: usize,
+}
+
+fn eval_error(reason: String) -> Error {
+	Error::InvalidXPath {
+		context: String::new(),
+		reason,
+	}
+}
+
+// the nodes on the axis in proximity order
+fn axis_nodes<'a>(node: &XPathNode<'a>, axis: Axis) -> Vec<XPathNode<'a>> {
+	fn descendants<'a>(node: &XPathNode<'a>, result: &mut Vec<XPathNode<'a>>) {
+		for child in node.child_nodes() {
+			let is_element = matches!(child, XPathNode::Element(_));
+			result.push(child.clone());
+			if is_element {
+				descendants(&child, result);
+			}
+		}
+	}
+	fn siblings<'a>(node: &XPathNode<'a>, following: bool) -> Vec<XPathNode<'a>> {
+		if matches!(node, XPathNode::Attribute { .. }) {
+			return Vec::new();
+		}
+		let index = node.index();
+		match node.parent() {
+			Some(parent) => {
+				let mut child_nodes = parent.child_nodes();
+				if following {
+					child_nodes.split_off((index + 1).min(child_nodes.len()))
+				} else {
+					child_nodes.truncate(index);
+					child_nodes.reverse();
+					child_nodes
+				}
+			}
+			None => Vec::new(),
+		}
+	}
+	let mut result = Vec::with_capacity(5);
+	match axis {
+		Axis::Child => result = node.child_nodes(),
+		Axis::Descendant => descendants(node, &mut result),
+		Axis::DescendantOrSelf => {
+			result.push(node.clone());
+			descendants(node, &mut result);
+		}
+		Axis::Parent => result.extend(node.parent()),
+		Axis::Ancestor | Axis::AncestorOrSelf => {
+			if axis == Axis::AncestorOrSelf {
+				result.push(node.clone());
+			}
+			let mut cur = node.parent();
+			while let Some(parent) = cur {
+				cur = parent.parent();
+				result.push(parent);
+			}
+		}
+		Axis::FollowingSibling => result = siblings(node, true),
+		Axis::PrecedingSibling => result = siblings(node, false),
+		Axis::Following => {
+			let mut cur = node.clone();
+			if let XPathNode::Attribute { owner, .. } = node {
+				cur = XPathNode::Element(owner.cloned());
+				descendants(&cur, &mut result);
+			}
+			loop {
+				for sibling in siblings(&cur, true) {
+					result.push(sibling.clone());
+					descendants(&sibling, &mut result);
+				}
+				match cur.parent() {
+					Some(parent) => cur = parent,
+					None => break,
+				}
+			}
+		}
+		Axis::Preceding => {
+			let mut cur = match node {
+				XPathNode::Attribute { owner, .. } => XPathNode::Element(owner.cloned()),
+				_ => node.clone(),
+			};
+			loop {
+				for sibling in siblings(&cur, false) {
+					let mut subtree = vec![sibling.clone()];
+					descendants(&sibling, &mut subtree);
+					subtree.reverse();
+					result.extend(subtree);
+				}
+				match cur.parent() {
+					Some(parent) => cur = parent,
+					None => break,
+				}
+			}
+		}
+		Axis::Attribute => result = node.attributes(),
+		Axis::Namespace => {}
+		Axis::Current => result.push(node.clone()),
+	}
+	result
+}
+
+fn is_node_matched(node: &XPathNode, axis: Axis, test: &NodeTest) -> bool {
+	match test {
+		NodeTest::Node => true,
+		NodeTest::Text => matches!(node, XPathNode::Text(_)),
+		NodeTest::Comment => matches!(node.node_type(), INodeType::Comment),
+		NodeTest::ProcessingInstruction(_) => false,
+		NodeTest::Name(name) => {
+			// the principal node type
+			let is_principal = if axis == Axis::Attribute {
+				matches!(node, XPathNode::Attribute { .. })
+			} else {
+				node.is_element()
+			};
+			if !is_principal {
+				return false;
+			}
+			if name == "*" {
+				return true;
+			}
+			let node_name = node.name();
+			if let Some(prefix) = name.strip_suffix(":*") {
+				return node_name.len() > prefix.len()
+					&& node_name[..prefix.len()].eq_ignore_ascii_case(prefix)
+					&& node_name[prefix.len()..].starts_with(':');
+			}
+			node_name.eq_ignore_ascii_case(name)
+		}
+	}
+}
+
+// filter the nodes by the predicates, the positions are in the nodes' order
+fn filter_by_predicates<'a>(
+	mut nodes: Vec<XPathNode<'a>>,
+	predicates: &[Expr],
+) -> Result<Vec<XPathNode<'a>>, Error> {
+	for predicate in predicates {
+		let size = nodes.len();
+		let mut result = Vec::with_capacity(size);
+		for (index, node) in nodes.into_iter().enumerate() {
+			let ctx = Context {
+				node,
+				position: index + 1,
+				size,
+			};
+			let is_matched = match evaluate(predicate, &ctx)? {
+				XPathValue::Number(num) => num == (index + 1) as f64,
+				value => value.boolean(),
+			};
+			if is_matched {
+				result.push(ctx.node);
+			}
+		}
+		nodes = result;
+	}
+	Ok(nodes)
+}
+
+fn eval_steps<'a>(
+	mut nodes: Vec<XPathNode<'a>>,
+	steps: &[Step],
+) -> Result<Vec<XPathNode<'a>>, Error> {
+	for Step {
+		axis,
+		test,
+		predicates,
+	} in steps
+	{
+		let mut result = Vec::with_capacity(nodes.len());
+		for node in &nodes {
+			let matched: Vec<XPathNode> = axis_nodes(node, *axis)
+				.into_iter()
+				.filter(|node| is_node_matched(node, *axis, test))
+				.collect();
+			result.extend(filter_by_predicates(matched, predicates)?);
+		}
+		nodes = sort_and_unique(result);
+	}
+	Ok(nodes)
+}
+
+fn into_node_set<'a>(value: XPathValue<'a>, reason: &str) -> Result<Vec<XPathNode<'a>>, Error> {
+	match value {
+		XPathValue::NodeSet(nodes) => Ok(nodes),
+		_ => Err(eval_error(format!("{} must be a node-set", reason))),
+	}
+}
+
+// compare the values except the node-sets
+fn compare_atomic(op: BinaryOp, left: &XPathValue, right: &XPathValue) -> bool {
+	use XPathValue::*;
+	match op {
+		BinaryOp::Equal | BinaryOp::NotEqual => {
+			let is_equal = match (left, right) {
+				(Boolean(_), _) | (_, Boolean(_)) => left.boolean() == right.boolean(),
+				(Number(_), _) | (_, Number(_)) => left.number() == right.number(),
+				_ => left.string() == right.string(),
+			};
+			is_equal == (op == BinaryOp::Equal)
+		}
+		_ => {
+			let (left, right) = (left.number(), right.number());
+			match op {
+				BinaryOp::Less => left < right,
+				BinaryOp::LessEqual => left <= right,
+				BinaryOp::Greater => left > right,
+				_ => left >= right,
+			}
+		}
+	}
+}
+
+// https://www.w3.org/TR/1999/REC-xpath-19991116/#booleans
+fn compare(op: BinaryOp, left: XPathValue, right: XPathValue) -> bool {
+	use XPathValue::*;
+	// convert the node to the type of the other side
+	fn atomic<'a>(node: &XPathNode, other: &XPathValue) -> XPathValue<'a> {
+		match other {
+			Number(_) => Number(string_to_number(&node.string_value())),
+			_ => String(node.string_value()),
+		}
+	}
+	match (&left, &right) {
+		(NodeSet(left_nodes), NodeSet(right_nodes)) => {
+			let right_values: Vec<XPathValue> = right_nodes
+				.iter()
+				.map(|node| String(node.string_value()))
+				.collect();
+			left_nodes.iter().any(|node| {
+				let left_value = String(node.string_value());
+				right_values
+					.iter()
+					.any(|right_value| compare_atomic(op, &left_value, right_value))
+			})
+		}
+		(NodeSet(_), Boolean(_)) | (Boolean(_), NodeSet(_)) => {
+			compare_atomic(op, &Boolean(left.boolean()), &Boolean(right.boolean()))
+		}
+		(NodeSet(nodes), other) => nodes
+			.iter()
+			.any(|node| compare_atomic(op, &atomic(node, other), other)),
+		(other, NodeSet(nodes)) => nodes
+			.iter()
+			.any(|node| compare_atomic(op, other, &atomic(node, other))),
+		_ => compare_atomic(op, &left, &right),
+	}
+}
+
+fn round(num: f64) -> f64 {
+	if num.is_nan() || num.is_infinite() {
+		return num;
+	}
+	let result = (num + 0.5).floor();
+	if result == 0.0 && num < 0.0 {
+		-0.0
+	} else {
+		result
+	}
+}
+
+fn eval_function<'a>(
+	name: &str,
+	args: &[Expr],
+	ctx: &Context<'a>,
+) -> Result<XPathValue<'a>, Error> {
+	use XPathValue::*;
+	let arg = |index: usize| -> Result<XPathValue<'a>, Error> { evaluate(&args[index], ctx) };
+	let string_arg = |index: usize| -> Result<std::string::String, Error> {
+		if index < args.len() {
+			Ok(arg(index)?.string())
+		} else {
+			Ok(ctx.node.string_value())
+		}
+	};
+	// the first node of the argument node-set or the context node
+	let node_arg = |method: &str| -> Result<Option<XPathNode<'a>>, Error> {
+		if args.is_empty() {
+			Ok(Some(ctx.node.clone()))
+		} else {
+			Ok(
+				into_node_set(arg(0)?, &format!("The argument of '{}'", method))?
+					.into_iter()
+					.next(),
+			)
+		}
+	};
+	Ok(match name {
+		// node-set functions
+		"last" => Number(ctx.size as f64),
+		"position" => Number(ctx.position as f64),
+		"count" => Number(into_node_set(arg(0)?, "The argument of 'count'")?.len() as f64),
+		"id" => {
+			let ids: Vec<std::string::String> = match arg(0)? {
+				NodeSet(nodes) => nodes.iter().map(|node| node.string_value()).collect(),
+				value => vec![value.string()],
+			};
+			let mut result = Vec::new();
+			if let Some(ele) = ctx.node.element() {
+				if let Some(doc) = ele.owner_document() {
+					for id in ids.iter().flat_map(|ids| ids.split_whitespace()) {
+						if let Some(ele) = doc.get_element_by_id(id) {
+							result.push(XPathNode::Element(ele));
+						}
+					}
+				}
+			}
+			NodeSet(sort_and_unique(result))
+		}
+		"local-name" => String(
+			node_arg(name)?
+				.map(|node| {
+					let name = node.name();
+					match name.find(':') {
+						Some(index) => name[index + 1..].to_string(),
+						None => name,
+					}
+				})
+				.unwrap_or_default(),
+		),
+		"namespace-uri" => {
+			node_arg(name)?;
+			String(std::string::String::new())
+		}
+		"name" => String(node_arg(name)?.map(|node| node.name()).unwrap_or_default()),
+		// string functions
+		"string" => String(string_arg(0)?),
+		"concat" => {
+			let mut result = std::string::String::new();
+			for index in 0..args.len() {
+				result.push_str(&string_arg(index)?);
+			}
+			String(result)
+		}
+		"starts-with" => Boolean(string_arg(0)?.starts_with(&string_arg(1)?)),
+		"contains" => Boolean(string_arg(0)?.contains(&string_arg(1)?)),
+		"substring-before" => {
+			let (content, search) = (string_arg(0)?, string_arg(1)?);
+			String(
+				content
+					.find(&search)
+					.map(|index| content[..index].to_string())
+					.unwrap_or_default(),
+			)
+		}
+		"substring-after" => {
+			let (content, search) = (string_arg(0)?, string_arg(1)?);
+			String(
+				content
+					.find(&search)
+					.map(|index| content[index + search.len()..].to_string())
+					.unwrap_or_default(),
+			)
+		}
+		"substring" => {
+			let content = string_arg(0)?;
+			let start = round(arg(1)?.number());
+			let end = if args.len() > 2 {
+				start + round(arg(2)?.number())
+			} else {
+				f64::INFINITY
+			};
+			String(
+				content
+					.chars()
+					.enumerate()
+					.filter(|(index, _)| {
+						let position = (*index + 1) as f64;
+						position >= start && position < end
+					})
+					.map(|(_, ch)| ch)
+					.collect(),
+			)
+		}
+		"string-length" => Number(string_arg(0)?.chars().count() as f64),
+		"normalize-space" => String(
+			string_arg(0)?
+				.split_whitespace()
+				.collect::<Vec<&str>>()
+				.join(" "),
+		),
+		"translate" => {
+			let (content, from, to) = (string_arg(0)?, string_arg(1)?, string_arg(2)?);
+			let from: Vec<char> = from.chars().collect();
+			let to: Vec<char> = to.chars().collect();
+			String(
+				content
+					.chars()
+					.filter_map(|ch| match from.iter().position(|&c| c == ch) {
+						Some(index) => to.get(index).copied(),
+						None => Some(ch),
+					})
+					.collect(),
+			)
+		}
+		// boolean functions
+		"boolean" => Boolean(arg(0)?.boolean()),
+		"not" => Boolean(!arg(0)?.boolean()),
+		"true" => Boolean(true),
+		"false" => Boolean(false),
+		"lang" => {
+			let lang = string_arg(0)?.to_ascii_lowercase();
+			let mut cur = ctx.node.element();
+			let mut result = false;
+			while let Some(ele) = cur {
+				if let Some(value) = ele
+					.get_attribute("xml:lang")
+					.or_else(|| ele.get_attribute("lang"))
+				{
+					let value = value.to_string().to_ascii_lowercase();
+					result = value == lang || value.starts_with(&format!("{}-", lang));
+					break;
+				}
+				cur = ele.parent();
+			}
+			Boolean(result)
+		}
+		// number functions
+		"number" => Number(if args.is_empty() {
+			string_to_number(&ctx.node.string_value())
+		} else {
+			arg(0)?.number()
+		}),
+		"sum" => Number(
+			into_node_set(arg(0)?, "The argument of 'sum'")?
+				.iter()
+				.map(|node| string_to_number(&node.string_value()))
+				.sum(),
+		),
+		"floor" => Number(arg(0)?.number().floor()),
+		"ceiling" => Number(arg(0)?.number().ceil()),
+		"round" => Number(round(arg(0)?.number())),
+		_ => return Err(eval_error(format!("Unsupported function '{}'", name))),
+	})
+}
+
+/// evaluate the expression in the context
+pub(crate) fn evaluate<'a>(expr: &Expr, ctx: &Context<'a>) -> Result<XPathValue<'a>, Error> {
+	use XPathValue::*;
+	Ok(match expr {
+		Expr::Literal(value) => String(value.clone()),
+		Expr::Number(num) => Number(*num),
+		Expr::Variable(name) => {
+			return Err(eval_error(format!("Unsupported variable '${}'", name)));
+		}
+		Expr::Negate(expr) => Number(-evaluate(expr, ctx)?.number()),
+		Expr::Function(name, args) => eval_function(name, args, ctx)?,
+		Expr::Filter(expr, predicates) => {
+			let nodes = into_node_set(evaluate(expr, ctx)?, "The filtered expression")?;
+			NodeSet(filter_by_predicates(nodes, predicates)?)
+		}
+		Expr::Path(start, steps) => {
+			let nodes = match start {
+				PathStart::Root => ctx.root().into_iter().collect(),
+				PathStart::Context => vec![ctx.node.clone()],
+				PathStart::Filter(expr) => into_node_set(evaluate(expr, ctx)?, "The path start")?,
+			};
+			NodeSet(eval_steps(nodes, steps)?)
+		}
+		Expr::Binary(op, left, right) => {
+			let left = evaluate(left, ctx)?;
+			match op {
+				BinaryOp::Or => {
+					if left.boolean() {
+						return Ok(Boolean(true));
+					}
+					Boolean(evaluate(right, ctx)?.boolean())
+				}
+				BinaryOp::And => {
+					if !left.boolean() {
+						return Ok(Boolean(false));
+					}
+					Boolean(evaluate(right, ctx)?.boolean())
+				}
+				BinaryOp::Union => {
+					let mut nodes = into_node_set(left, "The operand of '|'")?;
+					nodes.extend(into_node_set(evaluate(right, ctx)?, "The operand of '|'")?);
+					NodeSet(sort_and_unique(nodes))
+				}
+				BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Multiply | BinaryOp::Div | BinaryOp::Mod => {
+					let (left, right) = (left.number(), evaluate(right, ctx)?.number());
+					Number(match op {
+						BinaryOp::Plus => left + right,
+						BinaryOp::Minus => left - right,
+						BinaryOp::Multiply => left * right,
+						BinaryOp::Div => left / right,
+						_ => left % right,
+					})
+				}
+				_ => Boolean(compare(*op, left, evaluate(right, ctx)?)),
+			}
+		}
+	})
+}
+
+impl<'a> Context<'a> {
+	fn root(&self) -> Option<XPathNode<'a>> {
+		self.node.root()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{round, string_to_number, XPathNode};
+	use crate::interface::BoxDynElement;
+	use crate::mock::Doc;
+	use crate::xpath::XPath;
+
+	// the elements are named by their ids
+	fn eval(ele: &BoxDynElement, expr: &str) -> Vec<String> {
+		let nodes = XPath::parse(expr)
+			.and_then(|xpath| xpath.evaluate(ele))
+			.expect("The xpath must be correct")
+			.into_nodes()
+			.expect("The result must be a node-set");
+		nodes
+			.iter()
+			.map(|node| match node {
+				XPathNode::Element(ele) => ele
+					.get_attribute("id")
+					.map(|id| id.to_string())
+					.unwrap_or_else(|| node.name()),
+				XPathNode::Text(_) => format!("'{}'", node.string_value()),
+				XPathNode::Attribute { .. } => format!("@{}", node.name()),
+				XPathNode::Other(_) => String::from("#comment"),
+			})
+			.collect()
+	}
+
+	#[test]
+	fn test_string_to_number() {
+		assert_eq!(string_to_number(" 12 "), 12.0);
+		assert_eq!(string_to_number("-1.5"), -1.5);
+		assert_eq!(string_to_number(".5"), 0.5);
+		assert_eq!(string_to_number("3."), 3.0);
+		assert!(string_to_number("").is_nan());
+		assert!(string_to_number(".").is_nan());
+		assert!(string_to_number("+1").is_nan());
+		assert!(string_to_number("1e3").is_nan());
+		assert_eq!(round(2.5), 3.0);
+		assert_eq!(round(-2.5), -2.0);
+		assert!(round(-0.2).is_sign_negative());
+	}
+
+	#[test]
+	fn test_evaluate() {
+		crate::init();
+		let doc = Doc::parse(
+			r#"<div id="a">x<p id="p1" class="t">1</p>y<p id="p2">2<b id="b">b</b></p><!--c--><span id="s">3</span></div><ul id="u"><li>4</li><li id="l2">5</li></ul>"#,
+		);
+		let root = doc.root();
+		let root = root.get(0).unwrap();
+		// axes
+		assert_eq!(
+			eval(root, "/div/node()"),
+			["'x'", "p1", "'y'", "p2", "#comment", "s"]
+		);
+		assert_eq!(eval(root, "//p/b/ancestor::*"), ["a", "p2"]);
+		assert_eq!(eval(root, "//b/ancestor-or-self::p"), ["p2"]);
+		assert_eq!(eval(root, "//div/descendant::*"), ["p1", "p2", "b", "s"]);
+		assert_eq!(eval(root, "//p/@*"), ["@id", "@class", "@id"]);
+		assert_eq!(eval(root, "//p/text()"), ["'1'", "'2'"]);
+		// the text and element siblings are different nodes
+		assert_eq!(
+			eval(root, "//div/text() | //div/p | //div/text()"),
+			["'x'", "p1", "'y'", "p2"]
+		);
+		assert_eq!(
+			eval(root, "//p[1]/following-sibling::node()"),
+			["'y'", "p2", "#comment", "s"]
+		);
+		assert_eq!(eval(root, "//span/preceding-sibling::*[1]"), ["p2"]);
+		assert_eq!(eval(root, "//b/following::*"), ["s", "u", "li", "l2"]);
+		assert_eq!(eval(root, "//span/preceding::*"), ["p1", "p2", "b"]);
+		// predicates
+		assert_eq!(eval(root, "//p[2]"), ["p2"]);
+		assert_eq!(eval(root, "//li[last()]"), ["l2"]);
+		assert_eq!(eval(root, "(//p | //li)[last() - 1]"), ["li"]);
+		assert_eq!(eval(root, "//p[b]"), ["p2"]);
+		assert_eq!(eval(root, "//*[@class='t']"), ["p1"]);
+		assert_eq!(eval(root, "//li[. = '5' or position() = 1]"), ["li", "l2"]);
+		assert_eq!(eval(root, "id('s p1 none')"), ["p1", "s"]);
+		// relative to the context element
+		let div = doc.root().find("#a");
+		let div = div.get(0).unwrap();
+		assert_eq!(eval(div, "p"), ["p1", "p2"]);
+		assert_eq!(
+			eval(div, ".//text()"),
+			["'x'", "'1'", "'y'", "'2'", "'b'", "'3'"]
+		);
+		assert_eq!(eval(div, "../ul/li"), ["li", "l2"]);
+		assert_eq!(eval(div, "following-sibling::*/li[1]"), ["li"]);
+		assert_eq!(eval(div, "/ul"), ["u"]);
+		let count = XPath::parse("count(//li) + count(.//p)").unwrap();
+		assert_eq!(count.evaluate(div).unwrap().number(), 4.0);
+	}
+}
@@ -0,0 +1,102 @@
+/*
+*
+* XPath 1.0 over the element traits
+*
+*/
+mod eval;
+pub mod parser;
+pub mod translate;
+use crate::error::Error;
+use crate::interface::{BoxDynElement, Elements, Texts};
+use eval::Context;
+pub use eval::{XPathNode, XPathValue};
+use parser::Expr;
+use std::str::FromStr;
+pub use translate::{css_to_xpath, xpath_to_css};
+
+/// `XPath`
+/// the parsed xpath expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct XPath {
+	expr: Expr,
+}
+
+impl XPath {
+	/// pub fn `parse`
+	pub fn parse(context: &str) -> Result<Self, Error> {
+		Ok(XPath {
+			expr: parser::parse(context)?,
+		})
+	}
+	/// pub fn `expr`
+	pub fn expr(&self) -> &Expr {
+		&self.expr
+	}
+	/// pub fn `evaluate`
+	/// evaluate with the element as the context node,
+	/// the root node is the root element of the context node's tree
+	pub fn evaluate<'a>(&self, ele: &BoxDynElement<'a>) -> Result<XPathValue<'a>, Error> {
+		let ctx = Context {
+			node: XPathNode::Element(ele.cloned()),
+			position: 1,
+			size: 1,
+		};
+		eval::evaluate(&self.expr, &ctx).map_err(|err| match err {
+			Error::InvalidXPath { reason, .. } => Error::InvalidXPath {
+				context: self.expr.to_string(),
+				reason,
+			},
+			err => err,
+		})
+	}
+}
+
+impl FromStr for XPath {
+	type Err = Error;
+	fn from_str(context: &str) -> Result<Self, Self::Err> {
+		XPath::parse(context)
+	}
+}
+
+impl<'a> XPathValue<'a> {
+	/// pub fn `into_nodes`
+	/// the nodes of a node-set, otherwise `None`
+	pub fn into_nodes(self) -> Option<Vec<XPathNode<'a>>> {
+		match self {
+			XPathValue::NodeSet(nodes) => Some(nodes),
+			_ => None,
+		}
+	}
+	/// pub fn `into_elements`
+	/// the elements in the node-set
+	pub fn into_elements(self) -> Elements<'a> {
+		let nodes = self.into_nodes().unwrap_or_default();
+		let mut result = Elements::with_capacity(nodes.len());
+		for node in nodes {
+			if let XPathNode::Element(ele) = node {
+				if ele.node_type().is_element() {
+					result.push(ele);
+				}
+			}
+		}
+		result
+	}
+	/// pub fn `into_texts`
+	/// the text nodes in the node-set
+	pub fn into_texts(self) -> Texts<'a> {
+		let nodes = self.into_nodes().unwrap_or_default();
+		let mut result = Texts::with_capacity(nodes.len());
+		for node in nodes {
+			if let XPathNode::Text(text) = node {
+				result.get_mut_ref().push(text);
+			}
+		}
+		result
+	}
+}
+
+/// pub fn `sort_nodes`
+/// sort the nodes in document order and remove the duplicates
+pub fn sort_nodes(nodes: Vec<XPathNode>) -> Vec<XPathNode> {
+	eval::sort_and_unique(nodes)
+}
@@ -0,0 +1,838 @@
+/*
+*
+* XPath 1.0 lexer and parser
+* https://www.w3.org/TR/1999/REC-xpath-19991116/
+*
+*/
+use crate::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+	Ancestor,
+	AncestorOrSelf,
+	Attribute,
+	Child,
+	Descendant,
+	DescendantOrSelf,
+	Following,
+	FollowingSibling,
+	Namespace,
+	Parent,
+	Preceding,
+	PrecedingSibling,
+	Current,
+}
+
+impl Axis {
+	fn from_name(name: &str) -> Option<Self> {
+		use Axis::*;
+		Some(match name {
+			"ancestor" => Ancestor,
+			"ancestor-or-self" => AncestorOrSelf,
+			"attribute" => Attribute,
+			"child" => Child,
+			"descendant" => Descendant,
+			"descendant-or-self" => DescendantOrSelf,
+			"following" => Following,
+			"following-sibling" => FollowingSibling,
+			"namespace" => Namespace,
+			"parent" => Parent,
+			"preceding" => Preceding,
+			"preceding-sibling" => PrecedingSibling,
+			"self" => Current,
+			_ => return None,
+		})
+	}
+	/// pub fn `name`
+	pub fn name(&self) -> &'static str {
+		use Axis::*;
+		match self {
+			Ancestor => "ancestor",
+			AncestorOrSelf => "ancestor-or-self",
+			Attribute => "attribute",
+			Child => "child",
+			Descendant => "descendant",
+			DescendantOrSelf => "descendant-or-self",
+			Following => "following",
+			FollowingSibling => "following-sibling",
+			Namespace => "namespace",
+			Parent => "parent",
+			Preceding => "preceding",
+			PrecedingSibling => "preceding-sibling",
+			Current => "self",
+		}
+	}
+	/// pub fn `is_reverse`
+	/// the proximity positions of a reverse axis are in reverse document order
+	pub fn is_reverse(&self) -> bool {
+		matches!(
+			self,
+			Axis::Ancestor | Axis::AncestorOrSelf | Axis::Preceding | Axis::PrecedingSibling
+		)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeTest {
+	// `*`, `name`, `prefix:*`, `prefix:name`
+	Name(String),
+	// `node()`
+	Node,
+	// `text()`
+	Text,
+	// `comment()`
+	Comment,
+	// `processing-instruction('target')`
+	ProcessingInstruction(Option<String>),
+}
+
+impl fmt::Display for NodeTest {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			NodeTest::Name(name) => f.write_str(name),
+			NodeTest::Node => f.write_str("node()"),
+			NodeTest::Text => f.write_str("text()"),
+			NodeTest::Comment => f.write_str("comment()"),
+			NodeTest::ProcessingInstruction(Some(target)) => {
+				write!(f, "processing-instruction('{}')", target)
+			}
+			NodeTest::ProcessingInstruction(None) => f.write_str("processing-instruction()"),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+	pub axis: Axis,
+	pub test: NodeTest,
+	pub predicates: Vec<Expr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+	Or,
+	And,
+	Equal,
+	NotEqual,
+	Less,
+	LessEqual,
+	Greater,
+	GreaterEqual,
+	Plus,
+	Minus,
+	Multiply,
+	Div,
+	Mod,
+	Union,
+}
+
+impl BinaryOp {
+	fn symbol(&self) -> &'static str {
+		use BinaryOp::*;
+		match self {
+			Or => "or",
+			And => "and",
+			Equal => "=",
+			NotEqual => "!=",
+			Less => "<",
+			LessEqual => "<=",
+			Greater => ">",
+			GreaterEqual => ">=",
+			Plus => "+",
+			Minus => "-",
+			Multiply => "*",
+			Div => "div",
+			Mod => "mod",
+			Union => "|",
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathStart {
+	// absolute path, begin with `/`
+	Root,
+	// relative path
+	Context,
+	// filter expression, e.g. `(//a)[1]/b`
+	Filter(Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+	Binary(BinaryOp, Box<Expr>, Box<Expr>),
+	Negate(Box<Expr>),
+	Literal(String),
+	Number(f64),
+	Variable(String),
+	Function(String, Vec<Expr>),
+	// primary expression with predicates
+	Filter(Box<Expr>, Vec<Expr>),
+	Path(PathStart, Vec<Step>),
+}
+
+fn fmt_predicates(f: &mut fmt::Formatter<'_>, predicates: &[Expr]) -> fmt::Result {
+	for predicate in predicates {
+		write!(f, "[{}]", predicate)?;
+	}
+	Ok(())
+}
+
+impl fmt::Display for Expr {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Expr::Binary(op, left, right) => write!(f, "({} {} {})", left, op.symbol(), right),
+			Expr::Negate(expr) => write!(f, "-{}", expr),
+			Expr::Literal(value) => {
+				if value.contains('"') {
+					write!(f, "'{}'", value)
+				} else {
+					write!(f, "\"{}\"", value)
+				}
+			}
+			Expr::Number(num) => f.write_str(&number_to_string(*num)),
+			Expr::Variable(name) => write!(f, "${}", name),
+			Expr::Function(name, args) => {
+				write!(f, "{}(", name)?;
+				for (index, arg) in args.iter().enumerate() {
+					if index > 0 {
+						f.write_str(", ")?;
+					}
+					write!(f, "{}", arg)?;
+				}
+				f.write_str(")")
+			}
+			Expr::Filter(expr, predicates) => {
+				write!(f, "({})", expr)?;
+				fmt_predicates(f, predicates)
+			}
+			Expr::Path(start, steps) => {
+				match start {
+					PathStart::Root => f.write_str("/")?,
+					PathStart::Filter(expr) => write!(f, "{}/", expr)?,
+					PathStart::Context => {}
+				}
+				for (index, step) in steps.iter().enumerate() {
+					if index > 0 {
+						f.write_str("/")?;
+					}
+					write!(f, "{}::{}", step.axis.name(), step.test)?;
+					fmt_predicates(f, &step.predicates)?;
+				}
+				Ok(())
+			}
+		}
+	}
+}
+
+/// pub fn `number_to_string`
+/// https://www.w3.org/TR/1999/REC-xpath-19991116/#function-string
+pub fn number_to_string(num: f64) -> String {
+	if num.is_nan() {
+		String::from("NaN")
+	} else if num.is_infinite() {
+		String::from(if num > 0.0 { "Infinity" } else { "-Infinity" })
+	} else if num == 0.0 {
+		String::from("0")
+	} else {
+		num.to_string()
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+	LeftParen,
+	RightParen,
+	LeftBracket,
+	RightBracket,
+	Dot,
+	DoubleDot,
+	At,
+	Comma,
+	DoubleColon,
+	Slash,
+	DoubleSlash,
+	Pipe,
+	Plus,
+	Minus,
+	Equal,
+	NotEqual,
+	Less,
+	LessEqual,
+	Greater,
+	GreaterEqual,
+	// `*` as the multiply operator
+	Multiply,
+	// `and`, `or`, `mod`, `div`
+	OperatorName(&'static str),
+	// `*`, `name`, `prefix:*`, `prefix:name`
+	NameTest(String),
+	// `node`, `text`, `comment`, `processing-instruction`
+	NodeType(String),
+	FunctionName(String),
+	AxisName(Axis),
+	Literal(String),
+	Number(f64),
+	Variable(String),
+}
+
+fn is_name_start(ch: char) -> bool {
+	ch.is_alphabetic() || ch == '_'
+}
+
+fn is_name_char(ch: char) -> bool {
+	ch.is_alphanumeric() || ch == '_' || ch == '-' || ch == '.'
+}
+
+// read a NCName begin at index
+fn read_ncname(chars: &[char], index: usize) -> Option<(String, usize)> {
+	if index < chars.len() && is_name_start(chars[index]) {
+		let mut end = index + 1;
+		while end < chars.len() && is_name_char(chars[end]) {
+			end += 1;
+		}
+		return Some((chars[index..end].iter().collect(), end));
+	}
+	None
+}
+
+fn invalid(context: &str, reason: String) -> Error {
+	Error::InvalidXPath {
+		context: String::from(context),
+		reason,
+	}
+}
+
+// https://www.w3.org/TR/1999/REC-xpath-19991116/#exprlex
+fn tokenize(context: &str) -> Result<Vec<Token>, Error> {
+	let chars: Vec<char> = context.chars().collect();
+	let total = chars.len();
+	let mut tokens: Vec<Token> = Vec::with_capacity(total / 2);
+	let mut index = 0;
+	while index < total {
+		let ch = chars[index];
+		if ch.is_whitespace() {
+			index += 1;
+			continue;
+		}
+		// the preceding token decides whether `*` and names are operators
+		let is_operator_position = match tokens.last() {
+			None => false,
+			Some(token) => !matches!(
+				token,
+				Token::At
+					| Token::DoubleColon
+					| Token::LeftParen
+					| Token::LeftBracket
+					| Token::Comma
+					| Token::Slash
+					| Token::DoubleSlash
+					| Token::Pipe
+					| Token::Plus
+					| Token::Minus
+					| Token::Equal
+					| Token::NotEqual
+					| Token::Less
+					| Token::LessEqual
+					| Token::Greater
+					| Token::GreaterEqual
+					| Token::Multiply
+					| Token::OperatorName(_)
+			),
+		};
+		let next = chars.get(index + 1).copied();
+		let (token, len) = match ch {
+			'(' => (Token::LeftParen, 1),
+			')' => (Token::RightParen, 1),
+			'[' => (Token::LeftBracket, 1),
+			']' => (Token::RightBracket, 1),
+			'@' => (Token::At, 1),
+			',' => (Token::Comma, 1),
+			'|' => (Token::Pipe, 1),
+			'+' => (Token::Plus, 1),
+			'-' => (Token::Minus, 1),
+			'=' => (Token::Equal, 1),
+			'!' if next == Some('=') => (Token::NotEqual, 2),
+			'<' if next == Some('=') => (Token::LessEqual, 2),
+			'<' => (Token::Less, 1),
+			'>' if next == Some('=') => (Token::GreaterEqual, 2),
+			'>' => (Token::Greater, 1),
+			'/' if next == Some('/') => (Token::DoubleSlash, 2),
+			'/' => (Token::Slash, 1),
+			':' if next == Some(':') => (Token::DoubleColon, 2),
+			'.' if next == Some('.') => (Token::DoubleDot, 2),
+			'.' if !next.map(|ch| ch.is_ascii_digit()).unwrap_or(false) => (Token::Dot, 1),
+			'*' if is_operator_position => (Token::Multiply, 1),
+			'*' => (Token::NameTest(String::from("*")), 1),
+			'"' | '\'' => {
+				let end = chars[index + 1..]
+					.iter()
+					.position(|&c| c == ch)
+					.ok_or_else(|| invalid(context, format!("Unclosed literal at index {}", index)))?;
+				let literal: String = chars[index + 1..index + 1 + end].iter().collect();
+				(Token::Literal(literal), end + 2)
+			}
+			'$' => {
+				let (name, end) = read_ncname(&chars, index + 1)
+					.ok_or_else(|| invalid(context, format!("Wrong variable at index {}", index)))?;
+				(Token::Variable(name), end - index)
+			}
+			_ if ch.is_ascii_digit() || ch == '.' => {
+				let mut end = index;
+				let mut has_dot = false;
+				while end < total && (chars[end].is_ascii_digit() || (chars[end] == '.' && !has_dot)) {
+					has_dot = has_dot || chars[end] == '.';
+					end += 1;
+				}
+				let num: String = chars[index..end].iter().collect();
+				let num = num
+					.parse::<f64>()
+					.map_err(|e| invalid(context, format!("Wrong number '{}': {}", num, e)))?;
+				(Token::Number(num), end - index)
+			}
+			_ if is_name_start(ch) => {
+				let (name, mut end) = read_ncname(&chars, index).expect("Name start checked");
+				if is_operator_position {
+					let operator = match name.as_str() {
+						"and" => "and",
+						"or" => "or",
+						"mod" => "mod",
+						"div" => "div",
+						_ => {
+							return Err(invalid(
+								context,
+								format!(
+									"Unexpected name '{}' at index {}, expect an operator",
+									name, index
+								),
+							))
+						}
+					};
+					(Token::OperatorName(operator), end - index)
+				} else {
+					// qualified name or `prefix:*`
+					let mut name = name;
+					if chars.get(end) == Some(&':') && chars.get(end + 1) != Some(&':') {
+						if chars.get(end + 1) == Some(&'*') {
+							name.push_str(":*");
+							end += 2;
+						} else if let Some((local, local_end)) = read_ncname(&chars, end + 1) {
+							name.push(':');
+							name.push_str(&local);
+							end = local_end;
+						}
+					}
+					// look ahead the next non whitespace character
+					let mut look = end;
+					while look < total && chars[look].is_whitespace() {
+						look += 1;
+					}
+					let token = if chars.get(look) == Some(&'(') {
+						match name.as_str() {
+							"node" | "text" | "comment" | "processing-instruction" => Token::NodeType(name),
+							_ => Token::FunctionName(name),
+						}
+					} else if chars.get(look) == Some(&':') && chars.get(look + 1) == Some(&':') {
+						Token::AxisName(
+							Axis::from_name(&name)
+								.ok_or_else(|| invalid(context, format!("Unknown axis '{}'", name)))?,
+						)
+					} else {
+						Token::NameTest(name)
+					};
+					(token, end - index)
+				}
+			}
+			_ => {
+				return Err(invalid(
+					context,
+					format!("Unexpected character '{}' at index {}", ch, index),
+				))
+			}
+		};
+		tokens.push(token);
+		index += len;
+	}
+	Ok(tokens)
+}
+
+// the core function library: name, min args, max args
+const FUNCTIONS: [(&str, usize, usize); 27] = [
+	("last", 0, 0),
+	("position", 0, 0),
+	("count", 1, 1),
+	("id", 1, 1),
+	("local-name", 0, 1),
+	("namespace-uri", 0, 1),
+	("name", 0, 1),
+	("string", 0, 1),
+	("concat", 2, usize::MAX),
+	("starts-with", 2, 2),
+	("contains", 2, 2),
+	("substring-before", 2, 2),
+	("substring-after", 2, 2),
+	("substring", 2, 3),
+	("string-length", 0, 1),
+	("normalize-space", 0, 1),
+	("translate", 3, 3),
+	("boolean", 1, 1),
+	("not", 1, 1),
+	("true", 0, 0),
+	("false", 0, 0),
+	("lang", 1, 1),
+	("number", 0, 1),
+	("sum", 1, 1),
+	("floor", 1, 1),
+	("ceiling", 1, 1),
+	("round", 1, 1),
+];
+
+struct Parser<'c> {
+	context: &'c str,
+	tokens: Vec<Token>,
+	index: usize,
+}
+
+impl<'c> Parser<'c> {
+	fn peek(&self) -> Option<&Token> {
+		self.tokens.get(self.index)
+	}
+	fn next(&mut self) -> Option<Token> {
+		let token = self.tokens.get(self.index).cloned();
+		self.index += 1;
+		token
+	}
+	fn eat(&mut self, token: &Token) -> bool {
+		if self.peek() == Some(token) {
+			self.index += 1;
+			return true;
+		}
+		false
+	}
+	fn expect(&mut self, token: &Token) -> Result<(), Error> {
+		if self.eat(token) {
+			return Ok(());
+		}
+		Err(self.error(format!("expect {:?}", token)))
+	}
+	fn error(&self, reason: String) -> Error {
+		let found = match self.peek() {
+			Some(token) => format!("{:?}", token),
+			None => String::from("the end"),
+		};
+		invalid(self.context, format!("{} but found {}", reason, found))
+	}
+	// binary expressions from the lowest precedence
+	fn parse_binary(&mut self, level: usize) -> Result<Expr, Error> {
+		const LEVELS: [&[(Token, BinaryOp)]; 6] = [
+			&[(Token::OperatorName("or"), BinaryOp::Or)],
+			&[(Token::OperatorName("and"), BinaryOp::And)],
+			&[
+				(Token::Equal, BinaryOp::Equal),
+				(Token::NotEqual, BinaryOp::NotEqual),
+			],
+			&[
+				(Token::Less, BinaryOp::Less),
+				(Token::LessEqual, BinaryOp::LessEqual),
+				(Token::Greater, BinaryOp::Greater),
+				(Token::GreaterEqual, BinaryOp::GreaterEqual),
+			],
+			&[
+				(Token::Plus, BinaryOp::Plus),
+				(Token::Minus, BinaryOp::Minus),
+			],
+			&[
+				(Token::Multiply, BinaryOp::Multiply),
+				(Token::OperatorName("div"), BinaryOp::Div),
+				(Token::OperatorName("mod"), BinaryOp::Mod),
+			],
+		];
+		if level == LEVELS.len() {
+			return self.parse_unary();
+		}
+		let mut left = self.parse_binary(level + 1)?;
+		'outer: loop {
+			for (token, op) in LEVELS[level] {
+				if self.eat(token) {
+					let right = self.parse_binary(level + 1)?;
+					left = Expr::Binary(*op, Box::new(left), Box::new(right));
+					continue 'outer;
+				}
+			}
+			break;
+		}
+		Ok(left)
+	}
+	fn parse_unary(&mut self) -> Result<Expr, Error> {
+		if self.eat(&Token::Minus) {
+			return Ok(Expr::Negate(Box::new(self.parse_unary()?)));
+		}
+		let mut left = self.parse_path()?;
+		while self.eat(&Token::Pipe) {
+			let right = self.parse_path()?;
+			left = Expr::Binary(BinaryOp::Union, Box::new(left), Box::new(right));
+		}
+		Ok(left)
+	}
+	fn parse_path(&mut self) -> Result<Expr, Error> {
+		let is_filter = matches!(
+			self.peek(),
+			Some(Token::LeftParen)
+				| Some(Token::Literal(_))
+				| Some(Token::Number(_))
+				| Some(Token::Variable(_))
+				| Some(Token::FunctionName(_))
+		);
+		if is_filter {
+			let primary = self.parse_primary()?;
+			let predicates = self.parse_predicates()?;
+			let expr = if predicates.is_empty() {
+				primary
+			} else {
+				Expr::Filter(Box::new(primary), predicates)
+			};
+			let mut steps = Vec::new();
+			if self.parse_slash(&mut steps)? {
+				self.parse_relative(&mut steps)?;
+				return Ok(Expr::Path(PathStart::Filter(Box::new(expr)), steps));
+			}
+			return Ok(expr);
+		}
+		let mut steps = Vec::with_capacity(3);
+		if self.parse_slash(&mut steps)? {
+			// only '/' selects the root node
+			if steps.is_empty() && !self.is_step_start() {
+				return Ok(Expr::Path(PathStart::Root, steps));
+			}
+			self.parse_relative(&mut steps)?;
+			return Ok(Expr::Path(PathStart::Root, steps));
+		}
+		self.parse_relative(&mut steps)?;
+		Ok(Expr::Path(PathStart::Context, steps))
+	}
+	// parse '/' or '//', '//' is short for '/descendant-or-self::node()/'
+	fn parse_slash(&mut self, steps: &mut Vec<Step>) -> Result<bool, Error> {
+		if self.eat(&Token::Slash) {
+			return Ok(true);
+		}
+		if self.eat(&Token::DoubleSlash) {
+			steps.push(Step {
+				axis: Axis::DescendantOrSelf,
+				test: NodeTest::Node,
+				predicates: vec![],
+			});
+			return Ok(true);
+		}
+		Ok(false)
+	}
+	fn is_step_start(&self) -> bool {
+		matches!(
+			self.peek(),
+			Some(Token::Dot)
+				| Some(Token::DoubleDot)
+				| Some(Token::At)
+				| Some(Token::AxisName(_))
+				| Some(Token::NameTest(_))
+				| Some(Token::NodeType(_))
+		)
+	}
+	fn parse_relative(&mut self, steps: &mut Vec<Step>) -> Result<(), Error> {
+		loop {
+			steps.push(self.parse_step()?);
+			if !self.parse_slash(steps)? {
+				break;
+			}
+		}
+		Ok(())
+	}
+	fn parse_step(&mut self) -> Result<Step, Error> {
+		if self.eat(&Token::Dot) {
+			return Ok(Step {
+				axis: Axis::Current,
+				test: NodeTest::Node,
+				predicates: vec![],
+			});
+		}
+		if self.eat(&Token::DoubleDot) {
+			return Ok(Step {
+				axis: Axis::Parent,
+				test: NodeTest::Node,
+				predicates: vec![],
+			});
+		}
+		let axis = match self.peek() {
+			Some(Token::At) => {
+				self.index += 1;
+				Axis::Attribute
+			}
+			Some(Token::AxisName(axis)) => {
+				let axis = *axis;
+				self.index += 1;
+				self.expect(&Token::DoubleColon)?;
+				axis
+			}
+			_ => Axis::Child,
+		};
+		let test = match self.next() {
+			Some(Token::NameTest(name)) => NodeTest::Name(name),
+			Some(Token::NodeType(name)) => {
+				self.expect(&Token::LeftParen)?;
+				let test = match name.as_str() {
+					"node" => NodeTest::Node,
+					"text" => NodeTest::Text,
+					"comment" => NodeTest::Comment,
+					_ => {
+						if let Some(Token::Literal(target)) = self.peek() {
+							let target = target.clone();
+							self.index += 1;
+							NodeTest::ProcessingInstruction(Some(target))
+						} else {
+							NodeTest::ProcessingInstruction(None)
+						}
+					}
+				};
+				self.expect(&Token::RightParen)?;
+				test
+			}
+			_ => {
+				self.index -= 1;
+				return Err(self.error(String::from("expect a node test")));
+			}
+		};
+		Ok(Step {
+			axis,
+			test,
+			predicates: self.parse_predicates()?,
+		})
+	}
+	fn parse_predicates(&mut self) -> Result<Vec<Expr>, Error> {
+		let mut predicates = Vec::new();
+		while self.eat(&Token::LeftBracket) {
+			predicates.push(self.parse_binary(0)?);
+			self.expect(&Token::RightBracket)?;
+		}
+		Ok(predicates)
+	}
+	fn parse_primary(&mut self) -> Result<Expr, Error> {
+		match self.next() {
+			Some(Token::LeftParen) => {
+				let expr = self.parse_binary(0)?;
+				self.expect(&Token::RightParen)?;
+				Ok(expr)
+			}
+			Some(Token::Literal(value)) => Ok(Expr::Literal(value)),
+			Some(Token::Number(num)) => Ok(Expr::Number(num)),
+			Some(Token::Variable(name)) => Ok(Expr::Variable(name)),
+			Some(Token::FunctionName(name)) => {
+				self.expect(&Token::LeftParen)?;
+				let mut args = Vec::new();
+				if !self.eat(&Token::RightParen) {
+					loop {
+						args.push(self.parse_binary(0)?);
+						if self.eat(&Token::RightParen) {
+							break;
+						}
+						self.expect(&Token::Comma)?;
+					}
+				}
+				match FUNCTIONS.iter().find(|(fn_name, _, _)| *fn_name == name) {
+					Some((_, min, max)) => {
+						if args.len() < *min || args.len() > *max {
+							return Err(invalid(
+								self.context,
+								format!(
+									"Wrong arguments count {} of function '{}'",
+									args.len(),
+									name
+								),
+							));
+						}
+					}
+					None => {
+						return Err(invalid(
+							self.context,
+							format!("Unsupported function '{}'", name),
+						))
+					}
+				}
+				Ok(Expr::Function(name, args))
+			}
+			_ => {
+				self.index -= 1;
+				Err(self.error(String::from("expect a primary expression")))
+			}
+		}
+	}
+}
+
+/// pub fn `parse`
+pub fn parse(context: &str) -> Result<Expr, Error> {
+	let tokens = tokenize(context)?;
+	if tokens.is_empty() {
+		return Err(invalid(context, String::from("Empty expression")));
+	}
+	let mut parser = Parser {
+		context,
+		tokens,
+		index: 0,
+	};
+	let expr = parser.parse_binary(0)?;
+	if parser.peek().is_some() {
+		return Err(parser.error(String::from("expect the end")));
+	}
+	Ok(expr)
+}
+
+#[cfg(test)]
+mod test {
+	use super::{number_to_string, parse};
+
+	fn canonical(context: &str) -> String {
+		parse(context)
+			.expect("The xpath is not correct")
+			.to_string()
+	}
+
+	#[test]
+	fn test_parse() {
+		assert_eq!(canonical("/"), "/");
+		assert_eq!(
+			canonical("//div[@id='a']/p[2]"),
+			"/descendant-or-self::node()/child::div[(attribute::id = \"a\")]/child::p[2]"
+		);
+		assert_eq!(
+			canonical("../a | ./text()"),
+			"(parent::node()/child::a | self::node()/child::text())"
+		);
+		assert_eq!(
+			canonical("count(ancestor-or-self::*) * 2 div 4 mod 3"),
+			"(((count(ancestor-or-self::*) * 2) div 4) mod 3)"
+		);
+		assert_eq!(
+			canonical("(//a)[last()]/@href"),
+			"(/descendant-or-self::node()/child::a)[last()]/attribute::href"
+		);
+		assert_eq!(canonical("-1 - -.5"), "(-1 - -0.5)");
+		assert_eq!(
+			canonical("a or b and not(c)"),
+			"(child::a or (child::b and not(child::c)))"
+		);
+		assert_eq!(canonical("svg:rect/x:*"), "child::svg:rect/child::x:*");
+		assert!(parse("").is_err());
+		assert!(parse("//a[").is_err());
+		assert!(parse("unknown(1)").is_err());
+		assert!(parse("substring('a')").is_err());
+		assert!(parse("foo::a").is_err());
+		assert!(parse("a b").is_err());
+	}
+
+	#[test]
+	fn test_number_to_string() {
+		assert_eq!(number_to_string(f64::NAN), "NaN");
+		assert_eq!(number_to_string(-0.0), "0");
+		assert_eq!(number_to_string(2.0), "2");
+		assert_eq!(number_to_string(-1.5), "-1.5");
+		assert_eq!(number_to_string(f64::NEG_INFINITY), "-Infinity");
+	}
+}
@@ -0,0 +1,902 @@
+/*
+*
+* translate between the css selectors and the xpath expressions
+*
+*/
+use super::parser::{self, Axis, BinaryOp, Expr, NodeTest, PathStart, Step};
+use crate::error::Error;
+use crate::selector::ast::{
+	ComplexSelector, CompoundSelector, PseudoArgument, SelectorList, SimpleSelector,
+};
+use crate::selector::{pattern::Nth, Combinator, Selector};
+
+// ---------------- css to xpath ----------------
+
+// quote the literal, use `concat()` when it contains both quotes
+fn quote_literal(value: &str) -> String {
+	if !value.contains('"') {
+		format!("\"{}\"", value)
+	} else if !value.contains('\'') {
+		format!("'{}'", value)
+	} else {
+		let parts: Vec<String> = value
+			.split('"')
+			.map(|part| format!("\"{}\"", part))
+			.collect();
+		format!("concat({})", parts.join(", '\"', "))
+	}
+}
+
+// remove the quotes of the raw argument, e.g. `:contains('a')`
+fn unquote(raw: &str) -> &str {
+	let raw = raw.trim();
+	for quote in ['"', '\''].iter() {
+		if raw.len() >= 2 && raw.starts_with(*quote) && raw.ends_with(*quote) {
+			return &raw[1..raw.len() - 1];
+		}
+	}
+	raw
+}
+
+// the condition of the position among the siblings, e.g. `count(preceding-sibling::*) + 1`
+fn nth_condition(siblings: &str, a: isize, b: isize) -> String {
+	let position = format!("count({}) + 1", siblings);
+	if a == 0 {
+		return format!("{} = {}", position, b);
+	}
+	if a > 0 {
+		let offset = if b >= 0 {
+			format!("{} - {}", position, b)
+		} else {
+			format!("{} + {}", position, -b)
+		};
+		format!("({}) mod {} = 0 and {} >= {}", offset, a, position, b)
+	} else {
+		format!(
+			"({} - ({})) mod {} = 0 and {} <= {}",
+			b, position, -a, position, b
+		)
+	}
+}
+
+fn attr_condition(name: &str, operator: &Option<String>, value: &Option<String>) -> String {
+	let attr = format!("@{}", name);
+	let (operator, value) = match (operator, value) {
+		(Some(operator), Some(value)) => (operator.as_str(), value.as_str()),
+		_ => return attr,
+	};
+	// the attribute selector never matches an empty value
+	if value.is_empty() {
+		return String::from("false()");
+	}
+	let literal = quote_literal(value);
+	match operator {
+		"^=" => format!("starts-with({}, {})", attr, literal),
+		"$=" => format!(
+			"substring({}, string-length({}) - {} + 1) = {}",
+			attr,
+			attr,
+			value.chars().count(),
+			literal
+		),
+		"*=" => format!("contains({}, {})", attr, literal),
+		"~=" => format!(
+			"contains(concat(\" \", normalize-space({}), \" \"), {})",
+			attr,
+			quote_literal(&format!(" {} ", value))
+		),
+		"|=" => format!(
+			"({} = {} or starts-with({}, {}))",
+			attr,
+			literal,
+			attr,
+			quote_literal(&format!("{}-", value))
+		),
+		"!=" => format!("{} != {}", attr, literal),
+		_ => format!("{} = {}", attr, literal),
+	}
+}
+
+// the nested selector must be a compound selector
+fn self_condition(complex: &ComplexSelector) -> Result<String, String> {
+	match complex.0.as_slice() {
+		[(Combinator::ChildrenAll, compound)] | [(Combinator::Chain, compound)] => {
+			let (name, conditions) = compound_to_xpath(compound)?;
+			let mut step = format!("self::{}", name);
+			for condition in conditions {
+				step.push_str(&format!("[{}]", condition));
+			}
+			Ok(step)
+		}
+		_ => Err(format!(
+			"Only the compound selectors in the pseudo selector can be translated, found '{}'",
+			complex
+		)),
+	}
+}
+
+fn pseudo_condition(
+	type_name: &str,
+	pseudo: &str,
+	argument: &Option<PseudoArgument>,
+) -> Result<String, String> {
+	let of_type = || {
+		if type_name == "*" {
+			Err(format!(
+				"The pseudo selector '{}' must be used with a tag name",
+				pseudo
+			))
+		} else {
+			Ok(type_name)
+		}
+	};
+	let raw = match argument {
+		Some(PseudoArgument::Raw(raw)) => raw.as_str(),
+		_ => "",
+	};
+	Ok(match pseudo {
+		":empty" => String::from("not(* | text())"),
+		":first-child" => String::from("not(preceding-sibling::*)"),
+		":last-child" => String::from("not(following-sibling::*)"),
+		":only-child" => String::from("not(preceding-sibling::*) and not(following-sibling::*)"),
+		":first-of-type" => format!("not(preceding-sibling::{})", of_type()?),
+		":last-of-type" => format!("not(following-sibling::{})", of_type()?),
+		":only-of-type" => {
+			let name = of_type()?;
+			format!(
+				"not(preceding-sibling::{}) and not(following-sibling::{})",
+				name, name
+			)
+		}
+		":nth-child" | ":nth-last-child" | ":nth-of-type" | ":nth-last-of-type" => {
+			let (a, b) = Nth::parse(raw)
+				.ok_or_else(|| format!("The argument '{}' of '{}' is not correct", raw, pseudo))?;
+			let axis = if pseudo.contains("-last-") {
+				"following-sibling"
+			} else {
+				"preceding-sibling"
+			};
+			let test = if pseudo.ends_with("-of-type") {
+				of_type()?
+			} else {
+				"*"
+			};
+			nth_condition(&format!("{}::{}", axis, test), a, b)
+		}
+		":not" | ":is" | ":where" => {
+			let list = match argument {
+				Some(PseudoArgument::Selector(list)) => list,
+				_ => return Err(format!("The argument of '{}' is not a selector", pseudo)),
+			};
+			let conditions = list
+				.0
+				.iter()
+				.map(self_condition)
+				.collect::<Result<Vec<String>, String>>()?
+				.join(" or ");
+			if pseudo == ":not" {
+				format!("not({})", conditions)
+			} else {
+				conditions
+			}
+		}
+		":contains" => format!("contains(string(.), {})", quote_literal(unquote(raw))),
+		":header" => {
+			String::from("self::h1 or self::h2 or self::h3 or self::h4 or self::h5 or self::h6")
+		}
+		":input" => String::from("self::input or self::select or self::textarea or self::button"),
+		":submit" => String::from("(self::input or self::button) and @type = \"submit\""),
+		_ => {
+			return Err(format!(
+				"The pseudo selector '{}' can't be translated to xpath",
+				pseudo
+			))
+		}
+	})
+}
+
+// the node test and the predicates of the compound selector
+fn compound_to_xpath(compound: &CompoundSelector) -> Result<(String, Vec<String>), String> {
+	use SimpleSelector::*;
+	// the type name is also used by the `*-of-type` pseudo selectors
+	let name = compound
+		.0
+		.iter()
+		.find_map(|simple| match simple {
+			Type(name) => Some(name.clone()),
+			_ => None,
+		})
+		.unwrap_or_else(|| String::from("*"));
+	let mut conditions = Vec::with_capacity(compound.0.len());
+	for simple in &compound.0 {
+		match simple {
+			Universal | Type(_) => {}
+			Id(id) => conditions.push(format!("@id = {}", quote_literal(id))),
+			Class(class_name) => conditions.push(attr_condition(
+				"class",
+				&Some(String::from("~=")),
+				&Some(class_name.clone()),
+			)),
+			Attribute {
+				name,
+				operator,
+				value,
+			} => conditions.push(attr_condition(name, operator, value)),
+			AttributePrefix(prefix) => conditions.push(format!(
+				"@*[starts-with(name(), {})]",
+				quote_literal(&prefix.to_ascii_lowercase())
+			)),
+			Pseudo {
+				name: pseudo,
+				argument,
+			} => conditions.push(pseudo_condition(&name, pseudo, argument)?),
+			Other { text, .. } => {
+				return Err(format!(
+					"The selector '{}' can't be translated to xpath",
+					text
+				))
+			}
+		}
+	}
+	Ok((name, conditions))
+}
+
+fn complex_to_xpath(complex: &ComplexSelector) -> Result<String, String> {
+	let mut path = String::with_capacity(30);
+	for (index, (comb, compound)) in complex.0.iter().enumerate() {
+		let (name, conditions) = compound_to_xpath(compound)?;
+		if index > 0 {
+			path.push('/');
+		}
+		match comb {
+			Combinator::ChildrenAll | Combinator::Chain => {
+				path.push_str(&format!("descendant::{}", name))
+			}
+			Combinator::Children => path.push_str(&format!("child::{}", name)),
+			Combinator::NextAll => path.push_str(&format!("following-sibling::{}", name)),
+			Combinator::Next => {
+				path.push_str("following-sibling::*[1]");
+				if name != "*" {
+					path.push_str(&format!("[self::{}]", name));
+				}
+			}
+			_ => return Err(format!("The combinator '{:?}' can't be translated", comb)),
+		}
+		for condition in conditions {
+			path.push_str(&format!("[{}]", condition));
+		}
+	}
+	Ok(path)
+}
+
+/// pub fn `css_to_xpath`
+/// translate the selector into a xpath expression relative to the context node,
+/// the leading descendant combinator is the `descendant` axis, so the context node itself is excluded just like `find`
+pub fn css_to_xpath(selector: &str) -> Result<String, Error> {
+	let parsed: Selector = selector.parse()?;
+	let paths = parsed
+		.ast()
+		.0
+		.iter()
+		.map(complex_to_xpath)
+		.collect::<Result<Vec<String>, String>>()
+		.map_err(|reason| Error::InvalidSelector {
+			context: String::from(selector),
+			reason,
+		})?;
+	Ok(paths.join(" | "))
+}
+
+// ---------------- xpath to css ----------------
+
+// the integer of the number expression
+fn as_integer(expr: &Expr) -> Option<isize> {
+	match expr {
+		Expr::Number(num) if num.fract() == 0.0 => Some(*num as isize),
+		Expr::Negate(expr) => as_integer(expr).map(|num| -num),
+		_ => None,
+	}
+}
+
+// a relative path with only one step
+fn as_single_step(expr: &Expr) -> Option<&Step> {
+	match expr {
+		Expr::Path(PathStart::Context, steps) if steps.len() == 1 => Some(&steps[0]),
+		_ => None,
+	}
+}
+
+fn as_name_step(expr: &Expr, axis: Axis) -> Option<&str> {
+	match as_single_step(expr) {
+		Some(Step {
+			axis: cur_axis,
+			test: NodeTest::Name(name),
+			predicates,
+		}) if *cur_axis == axis && predicates.is_empty() => Some(name),
+		_ => None,
+	}
+}
+
+// `@name`
+fn as_attribute(expr: &Expr) -> Option<&str> {
+	as_name_step(expr, Axis::Attribute).filter(|name| *name != "*")
+}
+
+// `.` or `string(.)`
+fn is_string_value(expr: &Expr) -> bool {
+	match expr {
+		Expr::Function(name, args) if name == "string" => args.len() == 1 && is_string_value(&args[0]),
+		_ => matches!(
+			as_single_step(expr),
+			Some(Step {
+				axis: Axis::Current,
+				test: NodeTest::Node,
+				predicates,
+			}) if predicates.is_empty()
+		),
+	}
+}
+
+fn is_literal(expr: &Expr, value: &str) -> bool {
+	matches!(expr, Expr::Literal(literal) if literal == value)
+}
+
+fn as_literal(expr: &Expr) -> Option<&str> {
+	match expr {
+		Expr::Literal(literal) => Some(literal),
+		_ => None,
+	}
+}
+
+// the identifier can be used in `#id` or `.class`
+fn is_identifier(value: &str) -> bool {
+	!value.is_empty()
+		&& !value.starts_with(|ch: char| ch.is_ascii_digit())
+		&& !value.starts_with("--")
+		&& !value.starts_with("-0")
+		&& value
+			.chars()
+			.all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' || !ch.is_ascii())
+}
+
+fn attribute_selector(name: &str, operator: &str, value: &str) -> SimpleSelector {
+	SimpleSelector::Attribute {
+		name: String::from(name),
+		operator: Some(String::from(operator)),
+		value: Some(String::from(value)),
+	}
+}
+
+// `count(preceding-sibling::*) + 1`, return the axis and the name
+fn as_sibling_position(expr: &Expr) -> Option<(Axis, &str)> {
+	if let Expr::Binary(BinaryOp::Plus, left, right) = expr {
+		if as_integer(right) == Some(1) {
+			if let Expr::Function(name, args) = left.as_ref() {
+				if name == "count" && args.len() == 1 {
+					for axis in [Axis::PrecedingSibling, Axis::FollowingSibling].iter() {
+						if let Some(name) = as_name_step(&args[0], *axis) {
+							return Some((*axis, name));
+						}
+					}
+				}
+			}
+		}
+	}
+	None
+}
+
+fn nth_pseudo(axis: Axis, name: &str, argument: String) -> SimpleSelector {
+	let pseudo = match (axis == Axis::PrecedingSibling, name == "*") {
+		(true, true) => ":nth-child",
+		(true, false) => ":nth-of-type",
+		(false, true) => ":nth-last-child",
+		(false, false) => ":nth-last-of-type",
+	};
+	SimpleSelector::Pseudo {
+		name: String::from(pseudo),
+		argument: Some(PseudoArgument::Raw(argument)),
+	}
+}
+
+fn nth_argument(a: isize, b: isize) -> String {
+	let a_part = match a {
+		0 => return b.to_string(),
+		1 => String::from("n"),
+		-1 => String::from("-n"),
+		a => format!("{}n", a),
+	};
+	match b {
+		0 => a_part,
+		b if b > 0 => format!("{}+{}", a_part, b),
+		b => format!("{}{}", a_part, b),
+	}
+}
+
+// the nth pseudo selectors generated by `css_to_xpath`
+fn as_nth<'e>(expr: &'e Expr, type_name: &str) -> Option<SimpleSelector> {
+	// the name of the siblings must be `*` or the type name
+	let check_name = |position: Option<(Axis, &'e str)>| {
+		position.filter(|(_, name)| *name == "*" || name.eq_ignore_ascii_case(type_name))
+	};
+	match expr {
+		// `P = b`
+		Expr::Binary(BinaryOp::Equal, left, right) => {
+			let (axis, name) = check_name(as_sibling_position(left))?;
+			Some(nth_pseudo(axis, name, as_integer(right)?.to_string()))
+		}
+		// `(P - b) mod a = 0 and P >= b`, `(b - (P)) mod a = 0 and P <= b`
+		Expr::Binary(BinaryOp::And, left, right) => {
+			let (modulo, a) = match left.as_ref() {
+				Expr::Binary(BinaryOp::Equal, left, zero) if as_integer(zero) == Some(0) => {
+					match left.as_ref() {
+						Expr::Binary(BinaryOp::Mod, modulo, a) => (modulo, as_integer(a)?),
+						_ => return None,
+					}
+				}
+				_ => return None,
+			};
+			let (op, position, b) = match right.as_ref() {
+				Expr::Binary(op, position, b) => (*op, position, as_integer(b)?),
+				_ => return None,
+			};
+			let (axis, name) = check_name(as_sibling_position(position))?;
+			let is_matched = match (op, modulo.as_ref()) {
+				(BinaryOp::GreaterEqual, Expr::Binary(BinaryOp::Minus, cur, offset)) => {
+					cur == position && as_integer(offset) == Some(b)
+				}
+				(BinaryOp::GreaterEqual, Expr::Binary(BinaryOp::Plus, cur, offset)) => {
+					cur == position && as_integer(offset) == Some(-b)
+				}
+				(BinaryOp::LessEqual, Expr::Binary(BinaryOp::Minus, offset, cur)) => {
+					cur == position && as_integer(offset) == Some(b)
+				}
+				_ => false,
+			};
+			if !is_matched || a <= 0 {
+				return None;
+			}
+			let a = if op == BinaryOp::LessEqual { -a } else { a };
+			Some(nth_pseudo(axis, name, nth_argument(a, b)))
+		}
+		_ => None,
+	}
+}
+
+// the self steps split by `or`, e.g. `self::a or self::b[@c]`
+fn as_self_list(expr: &Expr, list: &mut SelectorList) -> Result<(), String> {
+	match expr {
+		Expr::Binary(BinaryOp::Or, left, right) => {
+			as_self_list(left, list)?;
+			as_self_list(right, list)
+		}
+		_ => {
+			let mut simples = Vec::with_capacity(3);
+			predicate_to_simples(expr, "*", &mut simples)?;
+			let compound = into_compound("*", simples)?;
+			list
+				.0
+				.push(ComplexSelector(vec![(Combinator::ChildrenAll, compound)]));
+			Ok(())
+		}
+	}
+}
+
+fn predicate_to_simples(
+	expr: &Expr,
+	type_name: &str,
+	simples: &mut Vec<SimpleSelector>,
+) -> Result<(), String> {
+	use SimpleSelector::*;
+	if let Some(nth) = as_nth(expr, type_name) {
+		simples.push(nth);
+		return Ok(());
+	}
+	let untranslatable = || {
+		format!(
+			"The predicate '[{}]' can't be translated to css selector",
+			expr
+		)
+	};
+	match expr {
+		Expr::Binary(BinaryOp::And, left, right) => {
+			predicate_to_simples(left, type_name, simples)?;
+			predicate_to_simples(right, type_name, simples)?;
+		}
+		Expr::Binary(BinaryOp::Or, left, right) => {
+			// `(@attr = "value" or starts-with(@attr, "value-"))`
+			if let (Expr::Binary(BinaryOp::Equal, attr, value), Expr::Function(name, args)) =
+				(left.as_ref(), right.as_ref())
+			{
+				if let (Some(attr), Some(value), "starts-with", [prefix_attr, prefix]) = (
+					as_attribute(attr),
+					as_literal(value),
+					name.as_str(),
+					args.as_slice(),
+				) {
+					if as_attribute(prefix_attr) == Some(attr)
+						&& as_literal(prefix) == Some(&format!("{}-", value))
+					{
+						simples.push(attribute_selector(attr, "|=", value));
+						return Ok(());
+					}
+				}
+			}
+			let mut list = SelectorList::default();
+			as_self_list(expr, &mut list)?;
+			simples.push(Pseudo {
+				name: String::from(":is"),
+				argument: Some(PseudoArgument::Selector(list)),
+			});
+		}
+		Expr::Binary(op @ BinaryOp::Equal, left, right)
+		| Expr::Binary(op @ BinaryOp::NotEqual, left, right) => {
+			// `substring(@attr, string-length(@attr) - 1 + 1) = "value"`
+			if let (Expr::Function(name, args), Some(value)) = (left.as_ref(), as_literal(right)) {
+				if let ("substring", [attr, _]) = (name.as_str(), args.as_slice()) {
+					if let (Some(attr), BinaryOp::Equal) = (as_attribute(attr), op) {
+						simples.push(attribute_selector(attr, "$=", value));
+						return Ok(());
+					}
+				}
+			}
+			let (name, value) = match (as_attribute(left), as_literal(right)) {
+				(Some(name), Some(value)) => (name, value),
+				_ => match (as_attribute(right), as_literal(left)) {
+					(Some(name), Some(value)) => (name, value),
+					_ => return Err(untranslatable()),
+				},
+			};
+			if *op == BinaryOp::NotEqual {
+				simples.push(attribute_selector(name, "!=", value));
+			} else if name == "id" && is_identifier(value) {
+				simples.push(Id(String::from(value)));
+			} else {
+				simples.push(attribute_selector(name, "=", value));
+			}
+		}
+		Expr::Function(name, args) => match (name.as_str(), args.as_slice()) {
+			("starts-with", [attr, value]) => match (as_attribute(attr), as_literal(value)) {
+				(Some(attr), Some(value)) => simples.push(attribute_selector(attr, "^=", value)),
+				_ => return Err(untranslatable()),
+			},
+			("contains", [haystack, value]) => {
+				let value = as_literal(value).ok_or_else(untranslatable)?;
+				if let Some(attr) = as_attribute(haystack) {
+					simples.push(attribute_selector(attr, "*=", value));
+				} else if is_string_value(haystack) {
+					let quote = if value.contains('"') { '\'' } else { '"' };
+					simples.push(Pseudo {
+						name: String::from(":contains"),
+						argument: Some(PseudoArgument::Raw(format!("{}{}{}", quote, value, quote))),
+					});
+				} else {
+					// `contains(concat(" ", normalize-space(@attr), " "), " value ")`
+					let attr = match haystack {
+						Expr::Function(name, args) if name == "concat" && args.len() == 3 => match &args[1] {
+							Expr::Function(name, inner)
+								if name == "normalize-space"
+									&& inner.len() == 1
+									&& is_literal(&args[0], " ")
+									&& is_literal(&args[2], " ") =>
+							{
+								as_attribute(&inner[0])
+							}
+							_ => None,
+						},
+						_ => None,
+					}
+					.ok_or_else(untranslatable)?;
+					let word = value
+						.strip_prefix(' ')
+						.and_then(|value| value.strip_suffix(' '))
+						.ok_or_else(untranslatable)?;
+					if attr == "class" && is_identifier(word) {
+						simples.push(Class(String::from(word)));
+					} else {
+						simples.push(attribute_selector(attr, "~=", word));
+					}
+				}
+			}
+			("not", [inner]) => {
+				// the child pseudo selectors
+				for (axis, child, of_type) in [
+					(Axis::PrecedingSibling, ":first-child", ":first-of-type"),
+					(Axis::FollowingSibling, ":last-child", ":last-of-type"),
+				]
+				.iter()
+				{
+					if let Some(name) = as_name_step(inner, *axis) {
+						let pseudo = if name == "*" {
+							child
+						} else if name.eq_ignore_ascii_case(type_name) {
+							of_type
+						} else {
+							return Err(untranslatable());
+						};
+						simples.push(Pseudo {
+							name: String::from(*pseudo),
+							argument: None,
+						});
+						return Ok(());
+					}
+				}
+				// `not(* | text())`
+				if let Expr::Binary(BinaryOp::Union, left, right) = inner {
+					let is_text = matches!(
+						as_single_step(right),
+						Some(Step { axis: Axis::Child, test: NodeTest::Text, predicates }) if predicates.is_empty()
+					);
+					if as_name_step(left, Axis::Child) == Some("*") && is_text {
+						simples.push(Pseudo {
+							name: String::from(":empty"),
+							argument: None,
+						});
+						return Ok(());
+					}
+				}
+				let mut list = SelectorList::default();
+				as_self_list(inner, &mut list)?;
+				simples.push(Pseudo {
+					name: String::from(":not"),
+					argument: Some(PseudoArgument::Selector(list)),
+				});
+			}
+			_ => return Err(untranslatable()),
+		},
+		_ => {
+			let step = as_single_step(expr).ok_or_else(untranslatable)?;
+			match step {
+				// `@attr`, `@*[starts-with(name(), "prefix")]`
+				Step {
+					axis: Axis::Attribute,
+					test: NodeTest::Name(name),
+					predicates,
+				} => {
+					if name != "*" && predicates.is_empty() {
+						simples.push(Attribute {
+							name: name.clone(),
+							operator: None,
+							value: None,
+						});
+					} else if let [Expr::Function(func, args)] = predicates.as_slice() {
+						match (func.as_str(), args.as_slice()) {
+							("starts-with", [Expr::Function(name_func, name_args), Expr::Literal(prefix)])
+								if name == "*" && name_func == "name" && name_args.is_empty() =>
+							{
+								simples.push(AttributePrefix(prefix.clone()))
+							}
+							_ => return Err(untranslatable()),
+						}
+					} else {
+						return Err(untranslatable());
+					}
+				}
+				// `self::name[predicates]`
+				Step {
+					axis: Axis::Current,
+					test: NodeTest::Name(name),
+					predicates,
+				} => {
+					if name != "*" {
+						simples.push(Type(name.clone()));
+					}
+					let type_name = if name == "*" { type_name } else { name };
+					for predicate in predicates {
+						predicate_to_simples(predicate, type_name, simples)?;
+					}
+				}
+				_ => return Err(untranslatable()),
+			}
+		}
+	}
+	Ok(())
+}
+
+// put the type selector first, a compound can only have one type
+fn into_compound(name: &str, simples: Vec<SimpleSelector>) -> Result<CompoundSelector, String> {
+	let mut type_name = if name == "*" { None } else { Some(name) };
+	let mut others = Vec::with_capacity(simples.len());
+	for simple in &simples {
+		match simple {
+			SimpleSelector::Type(cur_name) => match type_name {
+				Some(name) if !name.eq_ignore_ascii_case(cur_name) => {
+					return Err(format!(
+						"The node can't be both '{}' and '{}'",
+						name, cur_name
+					));
+				}
+				_ => type_name = Some(cur_name),
+			},
+			simple => others.push(simple.clone()),
+		}
+	}
+	let mut result = Vec::with_capacity(others.len() + 1);
+	match type_name {
+		Some(name) => result.push(SimpleSelector::Type(String::from(name))),
+		None if others.is_empty() => result.push(SimpleSelector::Universal),
+		None => {}
+	}
+	result.extend(others);
+	Ok(CompoundSelector(result))
+}
+
+// the positional predicate of the child axis: `[1]`, `[last()]`, `[position() = 2]`
+fn positional_pseudo(expr: &Expr, name: &str) -> Option<SimpleSelector> {
+	let is_last =
+		|expr: &Expr| matches!(expr, Expr::Function(name, args) if name == "last" && args.is_empty());
+	let is_position = |expr: &Expr| matches!(expr, Expr::Function(name, args) if name == "position" && args.is_empty());
+	let target = match expr {
+		Expr::Binary(BinaryOp::Equal, left, right) if is_position(left) => right.as_ref(),
+		expr => expr,
+	};
+	if is_last(target) {
+		return Some(SimpleSelector::Pseudo {
+			name: String::from(if name == "*" {
+				":last-child"
+			} else {
+				":last-of-type"
+			}),
+			argument: None,
+		});
+	}
+	as_integer(target).map(|index| nth_pseudo(Axis::PrecedingSibling, name, index.to_string()))
+}
+
+fn is_descendant_or_self_node(step: &Step) -> bool {
+	step.axis == Axis::DescendantOrSelf && step.test == NodeTest::Node && step.predicates.is_empty()
+}
+
+fn path_to_complex(expr: &Expr) -> Result<ComplexSelector, String> {
+	let steps = match expr {
+		Expr::Path(PathStart::Context, steps) => steps.as_slice(),
+		Expr::Path(PathStart::Root, steps)
+			if steps
+				.first()
+				.map(is_descendant_or_self_node)
+				.unwrap_or(false) =>
+		{
+			steps.as_slice()
+		}
+		Expr::Path(PathStart::Root, _) => {
+			return Err(String::from(
+				"Only the absolute path begin with '//' can be translated",
+			))
+		}
+		_ => return Err(format!("The expression '{}' is not a location path", expr)),
+	};
+	// skip the leading `.`
+	let steps = match steps.first() {
+		Some(Step {
+			axis: Axis::Current,
+			test: NodeTest::Node,
+			predicates,
+		}) if predicates.is_empty() => &steps[1..],
+		_ => steps,
+	};
+	let mut compounds = Vec::with_capacity(steps.len());
+	let mut after_descendant = false;
+	for step in steps {
+		if is_descendant_or_self_node(step) {
+			if after_descendant {
+				return Err(String::from("The repeated '//' can't be translated"));
+			}
+			after_descendant = true;
+			continue;
+		}
+		let name = match &step.test {
+			NodeTest::Name(name) => name.as_str(),
+			test => return Err(format!("The node test '{}' can't be translated", test)),
+		};
+		let mut predicates = step.predicates.as_slice();
+		let mut simples = Vec::with_capacity(predicates.len());
+		let comb = match (step.axis, after_descendant) {
+			(Axis::Child, true) | (Axis::Descendant, false) => Combinator::ChildrenAll,
+			(Axis::Child, false) => Combinator::Children,
+			(Axis::FollowingSibling, false) => {
+				if predicates.first().and_then(as_integer) == Some(1) {
+					if name != "*" {
+						return Err(format!(
+							"The step 'following-sibling::{}[1]' can't be translated, use 'following-sibling::*[1][self::{}]' instead",
+							name, name
+						));
+					}
+					predicates = &predicates[1..];
+					Combinator::Next
+				} else {
+					Combinator::NextAll
+				}
+			}
+			(axis, _) => return Err(format!("The axis '{}' can't be translated", axis.name())),
+		};
+		after_descendant = false;
+		// the positional predicate must be the first
+		if step.axis == Axis::Child {
+			if let Some(pseudo) = predicates
+				.first()
+				.and_then(|expr| positional_pseudo(expr, name))
+			{
+				simples.push(pseudo);
+				predicates = &predicates[1..];
+			}
+		}
+		for predicate in predicates {
+			predicate_to_simples(predicate, name, &mut simples)?;
+		}
+		compounds.push((comb, into_compound(name, simples)?));
+	}
+	if after_descendant || compounds.is_empty() {
+		return Err(String::from("The path must end with an element step"));
+	}
+	Ok(ComplexSelector(compounds))
+}
+
+fn expr_to_list(expr: &Expr, list: &mut SelectorList) -> Result<(), String> {
+	match expr {
+		Expr::Binary(BinaryOp::Union, left, right) => {
+			expr_to_list(left, list)?;
+			expr_to_list(right, list)
+		}
+		expr => {
+			list.0.push(path_to_complex(expr)?);
+			Ok(())
+		}
+	}
+}
+
+/// pub fn `xpath_to_css`
+/// translate the location paths into a selector, the paths are relative to the context node
+/// or begin with `//`, the predicates can be the forms generated by `css_to_xpath`
+pub fn xpath_to_css(xpath: &str) -> Result<String, Error> {
+	let expr = parser::parse(xpath)?;
+	let mut list = SelectorList::default();
+	expr_to_list(&expr, &mut list).map_err(|reason| Error::InvalidXPath {
+		context: String::from(xpath),
+		reason,
+	})?;
+	Ok(list.to_string())
+}
+
+#[cfg(test)]
+mod test {
+	use super::{css_to_xpath, xpath_to_css};
+
+	#[test]
+	fn test_css_to_xpath() {
+		crate::init();
+		assert_eq!(
+			css_to_xpath("div#app > a.nav[href^='http'], p + span").unwrap(),
+			r#"descendant::div[@id = "app"]/child::a[contains(concat(" ", normalize-space(@class), " "), " nav ")][starts-with(@href, "http")] | descendant::p/following-sibling::*[1][self::span]"#
+		);
+		assert_eq!(
+			css_to_xpath("li:nth-child(2n+1):not(.a, [b])").unwrap(),
+			r#"descendant::li[(count(preceding-sibling::*) + 1 - 1) mod 2 = 0 and count(preceding-sibling::*) + 1 >= 1][not(self::*[contains(concat(" ", normalize-space(@class), " "), " a ")] or self::*[@b])]"#
+		);
+		assert_eq!(
+			css_to_xpath(r#"p:contains('say "hi"')"#).unwrap(),
+			r#"descendant::p[contains(string(.), 'say "hi"')]"#
+		);
+		assert!(css_to_xpath(":first-of-type").is_err());
+		assert!(css_to_xpath(":not(a > b)").is_err());
+	}
+
+	#[test]
+	fn test_xpath_to_css() {
+		crate::init();
+		assert_eq!(
+			xpath_to_css(r#"//div[@id='app']/a[1][contains(concat(' ', normalize-space(@class), ' '), ' nav ')] | ./p/following-sibling::*[1][self::span]"#).unwrap(),
+			"div#app > a:nth-of-type(1).nav, > p + span"
+		);
+		assert!(xpath_to_css("//a/..").is_err());
+		assert!(xpath_to_css("//a[@href = $url]").is_err());
+		// round trip
+		for selector in &[
+			"div#app > a.nav[href^=\"http\"], p + span",
+			"li:nth-child(2n+1):not(.a, [b]) ~ li:nth-last-of-type(-n+3)",
+			"ul:first-child li:last-of-type:empty[^data-]",
+			"a[title$=\"x\"][lang|=\"en\"][rel~=\"next\"][target!=\"_blank\"]:contains(\"a\")",
+		] {
+			let xpath = css_to_xpath(selector).unwrap();
+			assert_eq!(&xpath_to_css(&xpath).unwrap(), selector);
+		}
+	}
+}